@@ -0,0 +1,22 @@
+use std::{env, fs, path::Path};
+
+fn main() {
+    // Migrations are embedded, so new ones must trigger a rebuild
+    println!("cargo:rerun-if-changed=migrations");
+
+    // Versions are named the way diesel names them: the directory name up to
+    // the first `_`, without dashes
+    let mut versions: Vec<String> = fs::read_dir("migrations")
+        .expect("failed to read migrations")
+        .map(|entry| entry.expect("failed to read migrations").path())
+        .filter(|path| path.join("up.sql").exists())
+        .map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.split('_').next().unwrap().replace('-', "")
+        })
+        .collect();
+    versions.sort();
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("migration_versions.rs");
+    fs::write(out, format!("&{:?}", versions)).expect("failed to write migration versions");
+}
@@ -0,0 +1 @@
+DROP TABLE game_servers;
\ No newline at end of file
@@ -0,0 +1,18 @@
+CREATE TABLE game_servers (
+    id BIGSERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    address VARCHAR(255) NOT NULL,
+    port INTEGER NOT NULL,
+    version VARCHAR(32) NOT NULL,
+    max_players INTEGER NOT NULL,
+    player_count INTEGER NOT NULL DEFAULT 0,
+    region VARCHAR(32) NOT NULL,
+    tags TEXT[] NOT NULL DEFAULT '{}',
+    token BYTEA UNIQUE NOT NULL,
+    online BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_heartbeat TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (address, port)
+);
+
+CREATE INDEX game_servers_online_idx ON game_servers (online);
@@ -0,0 +1 @@
+DROP TABLE join_tickets;
\ No newline at end of file
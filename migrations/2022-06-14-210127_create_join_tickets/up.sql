@@ -0,0 +1,9 @@
+CREATE TABLE join_tickets (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users ON DELETE CASCADE,
+    server_id BIGINT NOT NULL REFERENCES game_servers ON DELETE CASCADE,
+    ticket BYTEA UNIQUE NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
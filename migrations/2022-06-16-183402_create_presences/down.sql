@@ -0,0 +1,5 @@
+DROP TABLE presences;
+
+ALTER TABLE users
+    DROP COLUMN last_seen_at,
+    DROP COLUMN show_presence;
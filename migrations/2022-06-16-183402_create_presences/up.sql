@@ -0,0 +1,12 @@
+ALTER TABLE users
+    ADD COLUMN last_seen_at TIMESTAMPTZ,
+    ADD COLUMN show_presence BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE TABLE presences (
+    user_id BIGINT PRIMARY KEY REFERENCES users ON DELETE CASCADE,
+    server_id BIGINT REFERENCES game_servers ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX presences_server_id_idx ON presences (server_id);
@@ -0,0 +1,15 @@
+CREATE TABLE releases (
+    id BIGSERIAL PRIMARY KEY,
+    version VARCHAR(32) NOT NULL,
+    channel VARCHAR(16) NOT NULL,
+    platform VARCHAR(16) NOT NULL,
+    file_url TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    sha256 BYTEA NOT NULL,
+    signature TEXT,
+    release_date TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (version, channel, platform)
+);
+
+CREATE INDEX releases_channel_date_idx ON releases (channel, release_date DESC);
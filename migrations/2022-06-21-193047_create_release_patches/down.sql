@@ -0,0 +1 @@
+DROP TABLE release_patches;
\ No newline at end of file
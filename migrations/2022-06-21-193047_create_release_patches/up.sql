@@ -0,0 +1,10 @@
+CREATE TABLE release_patches (
+    id BIGSERIAL PRIMARY KEY,
+    release_id BIGINT NOT NULL REFERENCES releases (id) ON DELETE CASCADE,
+    from_version VARCHAR(32) NOT NULL,
+    file_url TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    sha256 BYTEA NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (release_id, from_version)
+);
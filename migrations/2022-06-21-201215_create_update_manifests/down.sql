@@ -0,0 +1 @@
+DROP TABLE update_manifests;
\ No newline at end of file
@@ -0,0 +1,12 @@
+CREATE TABLE update_manifests (
+    id BIGSERIAL PRIMARY KEY,
+    channel VARCHAR(16) NOT NULL,
+    platform VARCHAR(16) NOT NULL,
+    key_id VARCHAR(64) NOT NULL,
+    manifest TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    issued_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX update_manifests_target_idx ON update_manifests (channel, platform, issued_at DESC);
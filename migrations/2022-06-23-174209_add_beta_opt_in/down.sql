@@ -0,0 +1 @@
+ALTER TABLE users DROP COLUMN beta_opt_in;
\ No newline at end of file
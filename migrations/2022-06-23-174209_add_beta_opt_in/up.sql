@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN beta_opt_in BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1 @@
+DROP TABLE search_documents;
\ No newline at end of file
@@ -0,0 +1,18 @@
+CREATE TABLE search_documents (
+    id BIGSERIAL PRIMARY KEY,
+    kind VARCHAR(16) NOT NULL,
+    url VARCHAR(256) NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    headings TEXT NOT NULL,
+    body TEXT NOT NULL,
+    -- Release notes can only be found by those who can see their channel
+    channel VARCHAR(16),
+    search TSVECTOR GENERATED ALWAYS AS (
+        setweight(to_tsvector('english', title), 'A') ||
+        setweight(to_tsvector('english', headings), 'B') ||
+        setweight(to_tsvector('english', body), 'C')
+    ) STORED
+);
+
+CREATE INDEX search_documents_kind_idx ON search_documents (kind);
+CREATE INDEX search_documents_search_idx ON search_documents USING GIN (search);
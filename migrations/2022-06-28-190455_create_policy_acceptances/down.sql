@@ -0,0 +1 @@
+DROP TABLE policy_acceptances;
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE policy_acceptances (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    policy VARCHAR(64) NOT NULL,
+    version VARCHAR(32) NOT NULL,
+    accepted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, policy, version)
+);
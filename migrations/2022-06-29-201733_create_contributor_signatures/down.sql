@@ -0,0 +1,2 @@
+DROP TABLE contributor_signatures;
+DROP FUNCTION reject_contributor_signature_changes;
\ No newline at end of file
@@ -0,0 +1,28 @@
+CREATE TABLE contributor_signatures (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users(id),
+    legal_name VARCHAR(128) NOT NULL,
+    github_handle VARCHAR(39) NOT NULL,
+    version VARCHAR(32) NOT NULL,
+    -- SHA-256 hash of the agreement's source, as it was when signed
+    document_sha256 BYTEA NOT NULL,
+    signed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (user_id, document_sha256)
+);
+
+CREATE INDEX contributor_signatures_github_handle_idx ON contributor_signatures (LOWER(github_handle));
+
+-- Signatures are a legal record, so they can never be changed or removed
+CREATE FUNCTION reject_contributor_signature_changes() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'contributor signatures are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER contributor_signatures_immutable
+    BEFORE UPDATE OR DELETE ON contributor_signatures
+    FOR EACH ROW EXECUTE FUNCTION reject_contributor_signature_changes();
+
+CREATE TRIGGER contributor_signatures_no_truncate
+    BEFORE TRUNCATE ON contributor_signatures
+    FOR EACH STATEMENT EXECUTE FUNCTION reject_contributor_signature_changes();
@@ -0,0 +1,25 @@
+CREATE TABLE wiki_pages (
+    id BIGSERIAL PRIMARY KEY,
+    slug VARCHAR(128) NOT NULL UNIQUE,
+    -- Title of the latest revision
+    title VARCHAR(128) NOT NULL,
+    -- Lowest role allowed to edit the page
+    protection VARCHAR(16) NOT NULL DEFAULT 'user',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE wiki_revisions (
+    id BIGSERIAL PRIMARY KEY,
+    page_id BIGINT NOT NULL REFERENCES wiki_pages(id) ON DELETE CASCADE,
+    author_id BIGINT NOT NULL REFERENCES users(id),
+    title VARCHAR(128) NOT NULL,
+    content TEXT NOT NULL,
+    summary VARCHAR(256) NOT NULL DEFAULT '',
+    -- The earlier revision this one restored, if it was a revert
+    reverted_from BIGINT REFERENCES wiki_revisions(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX wiki_revisions_page_idx ON wiki_revisions (page_id, id DESC);
+CREATE INDEX wiki_revisions_created_at_idx ON wiki_revisions (created_at DESC);
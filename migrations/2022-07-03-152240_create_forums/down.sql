@@ -0,0 +1,4 @@
+DROP TABLE forum_post_edits;
+DROP TABLE forum_posts;
+DROP TABLE forum_threads;
+DROP TABLE forum_categories;
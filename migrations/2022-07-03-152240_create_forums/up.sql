@@ -0,0 +1,50 @@
+CREATE TABLE forum_categories (
+    id BIGSERIAL PRIMARY KEY,
+    slug VARCHAR(64) NOT NULL UNIQUE,
+    name VARCHAR(64) NOT NULL,
+    description VARCHAR(256) NOT NULL DEFAULT '',
+    -- Categories are listed in ascending order
+    position INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE forum_threads (
+    id BIGSERIAL PRIMARY KEY,
+    category_id BIGINT NOT NULL REFERENCES forum_categories(id) ON DELETE CASCADE,
+    author_id BIGINT NOT NULL REFERENCES users(id),
+    title VARCHAR(128) NOT NULL,
+    pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Locked threads only take replies from moderators
+    locked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_post_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX forum_threads_category_idx ON forum_threads (category_id, pinned DESC, last_post_at DESC);
+
+CREATE TABLE forum_posts (
+    id BIGSERIAL PRIMARY KEY,
+    thread_id BIGINT NOT NULL REFERENCES forum_threads(id) ON DELETE CASCADE,
+    author_id BIGINT NOT NULL REFERENCES users(id),
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    edited_at TIMESTAMPTZ
+);
+
+CREATE INDEX forum_posts_thread_idx ON forum_posts (thread_id, id);
+CREATE INDEX forum_posts_author_idx ON forum_posts (author_id);
+
+-- Earlier versions of edited posts
+CREATE TABLE forum_post_edits (
+    id BIGSERIAL PRIMARY KEY,
+    post_id BIGINT NOT NULL REFERENCES forum_posts(id) ON DELETE CASCADE,
+    editor_id BIGINT NOT NULL REFERENCES users(id),
+    -- The content before the edit
+    content TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX forum_post_edits_post_idx ON forum_post_edits (post_id, id);
+
+INSERT INTO forum_categories (slug, name, description) VALUES
+    ('general', 'General', 'Anything about Fumohouse.');
@@ -0,0 +1,5 @@
+DROP TABLE message_reports;
+DROP TABLE messages;
+DROP TABLE conversation_members;
+DROP TABLE conversations;
+DROP TABLE user_blocks;
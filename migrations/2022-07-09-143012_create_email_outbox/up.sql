@@ -0,0 +1,19 @@
+-- Emails are rendered when queued and sent by a background worker
+CREATE TABLE email_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    recipient VARCHAR(320) NOT NULL,
+    subject VARCHAR(256) NOT NULL,
+    -- Which template the email was rendered from, such as `test`
+    template VARCHAR(64) NOT NULL,
+    html_body TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    -- `pending` until sent, or `failed` once out of attempts
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX email_outbox_pending_idx ON email_outbox (next_attempt_at) WHERE status = 'pending';
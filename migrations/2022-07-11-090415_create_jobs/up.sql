@@ -0,0 +1,26 @@
+CREATE TABLE jobs (
+    id BIGSERIAL PRIMARY KEY,
+    -- Which handler runs the job, such as `purge_expired_sessions`
+    kind VARCHAR(64) NOT NULL,
+    payload JSONB NOT NULL,
+    -- `pending`, `running`, `succeeded`, or `dead` once out of attempts
+    status VARCHAR(16) NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    max_attempts INT NOT NULL,
+    -- When a pending job is due. While running, when the worker's hold on
+    -- the job runs out and another worker may pick it up.
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Recurring jobs schedule their next run this many seconds after finishing
+    repeat_every INT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX jobs_due_idx ON jobs (run_at) WHERE status IN ('pending', 'running');
+CREATE INDEX jobs_status_idx ON jobs (status, id DESC);
+
+-- Every web instance schedules the recurring jobs at startup, but only one
+-- run of each can be waiting at a time
+CREATE UNIQUE INDEX jobs_recurring_idx ON jobs (kind)
+    WHERE repeat_every IS NOT NULL AND status IN ('pending', 'running');
@@ -0,0 +1,13 @@
+-- Logins which haven't succeeded, for throttling guesses. A successful login
+-- clears the account's attempts.
+CREATE TABLE login_attempts (
+    id BIGSERIAL PRIMARY KEY,
+    -- Unknown for clients Rocket can't find an address for
+    ip_address VARCHAR(45),
+    -- As typed, lowercased, whether or not it names a user
+    username TEXT NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX login_attempts_ip_address_idx ON login_attempts (ip_address, attempted_at);
+CREATE INDEX login_attempts_username_idx ON login_attempts (username, attempted_at);
@@ -0,0 +1,15 @@
+CREATE OR REPLACE FUNCTION reject_contributor_signature_changes() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'contributor signatures are immutable';
+END;
+$$ LANGUAGE plpgsql;
+
+-- Fails if any signatures have been detached from deleted accounts
+ALTER TABLE contributor_signatures
+    DROP CONSTRAINT contributor_signatures_user_id_fkey,
+    ADD CONSTRAINT contributor_signatures_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users(id),
+    ALTER COLUMN user_id SET NOT NULL;
+
+DROP INDEX contributor_signatures_github_id_idx;
+ALTER TABLE contributor_signatures DROP COLUMN github_id;
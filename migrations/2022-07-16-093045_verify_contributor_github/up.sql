@@ -0,0 +1,32 @@
+-- The GitHub account a signature was made as, verified through GitHub.
+-- Earlier signatures only have the username that was typed in.
+ALTER TABLE contributor_signatures ADD COLUMN github_id BIGINT;
+
+CREATE INDEX contributor_signatures_github_id_idx ON contributor_signatures (github_id);
+
+-- Deleting an account keeps its signatures, detached from it
+ALTER TABLE contributor_signatures
+    ALTER COLUMN user_id DROP NOT NULL,
+    DROP CONSTRAINT contributor_signatures_user_id_fkey,
+    ADD CONSTRAINT contributor_signatures_user_id_fkey
+        FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE OR REPLACE FUNCTION reject_contributor_signature_changes() RETURNS TRIGGER AS $$
+BEGIN
+    -- Only the signer's account being deleted may change a signature, and
+    -- only by detaching it
+    IF TG_OP = 'UPDATE'
+        AND NEW.user_id IS NULL
+        AND NOT EXISTS (SELECT 1 FROM users WHERE id = OLD.user_id)
+        AND (NEW.id, NEW.legal_name, NEW.github_handle, NEW.github_id, NEW.version,
+             NEW.document_sha256, NEW.signed_at)
+            IS NOT DISTINCT FROM
+            (OLD.id, OLD.legal_name, OLD.github_handle, OLD.github_id, OLD.version,
+             OLD.document_sha256, OLD.signed_at)
+    THEN
+        RETURN NEW;
+    END IF;
+
+    RAISE EXCEPTION 'contributor signatures are immutable';
+END;
+$$ LANGUAGE plpgsql;
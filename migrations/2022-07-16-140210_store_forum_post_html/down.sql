@@ -0,0 +1 @@
+ALTER TABLE forum_posts DROP COLUMN content_html;
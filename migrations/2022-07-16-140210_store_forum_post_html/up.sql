@@ -0,0 +1,3 @@
+-- Rendered when the post is saved. Posts from before this are rendered
+-- when shown, until they're next edited.
+ALTER TABLE forum_posts ADD COLUMN content_html TEXT;
@@ -0,0 +1,4 @@
+DROP TRIGGER notifications_count_unread ON notifications;
+DROP FUNCTION count_unread_notifications();
+
+ALTER TABLE users DROP COLUMN unread_notifications;
@@ -0,0 +1,27 @@
+-- Shown on nearly every page, so it's kept up to date rather than counted
+ALTER TABLE users ADD COLUMN unread_notifications BIGINT NOT NULL DEFAULT 0;
+
+UPDATE users SET unread_notifications = (
+    SELECT COUNT(*) FROM notifications
+    WHERE notifications.user_id = users.id AND notifications.read_at IS NULL
+);
+
+CREATE FUNCTION count_unread_notifications() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND OLD.read_at IS NULL THEN
+        UPDATE users SET unread_notifications = unread_notifications - 1
+        WHERE id = OLD.user_id;
+    END IF;
+
+    IF TG_OP IN ('INSERT', 'UPDATE') AND NEW.read_at IS NULL THEN
+        UPDATE users SET unread_notifications = unread_notifications + 1
+        WHERE id = NEW.user_id;
+    END IF;
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER notifications_count_unread
+    AFTER INSERT OR UPDATE OF user_id, read_at OR DELETE ON notifications
+    FOR EACH ROW EXECUTE FUNCTION count_unread_notifications();
@@ -0,0 +1,22 @@
+-- The one-to-one conversation of each pair of users, keyed so that there
+-- can only ever be one, with the lower user ID first
+CREATE TABLE direct_conversations (
+    low_user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    high_user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    conversation_id BIGINT UNIQUE NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+    PRIMARY KEY (low_user_id, high_user_id),
+    CHECK (low_user_id < high_user_id)
+);
+
+-- Where a pair already has several, the oldest carries on
+INSERT INTO direct_conversations (low_user_id, high_user_id, conversation_id)
+SELECT DISTINCT ON (low_user_id, high_user_id) low_user_id, high_user_id, id
+FROM (
+    SELECT conversations.id, MIN(user_id) AS low_user_id, MAX(user_id) AS high_user_id
+    FROM conversations
+    INNER JOIN conversation_members ON conversation_members.conversation_id = conversations.id
+    WHERE NOT conversations.is_group
+    GROUP BY conversations.id
+    HAVING COUNT(*) = 2
+) AS pairs
+ORDER BY low_user_id, high_user_id, id;
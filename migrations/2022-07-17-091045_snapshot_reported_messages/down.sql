@@ -0,0 +1,12 @@
+DELETE FROM message_reports WHERE message_id IS NULL;
+
+ALTER TABLE message_reports
+    DROP CONSTRAINT message_reports_message_id_fkey,
+    ADD CONSTRAINT message_reports_message_id_fkey
+        FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE,
+    ALTER COLUMN message_id SET NOT NULL,
+    DROP COLUMN sent_at,
+    DROP COLUMN content,
+    DROP COLUMN author,
+    DROP COLUMN author_id,
+    DROP COLUMN conversation_id;
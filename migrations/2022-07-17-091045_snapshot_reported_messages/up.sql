@@ -0,0 +1,28 @@
+-- Reports keep the message as it was reported, so deleting the message,
+-- its conversation or its author doesn't take the evidence with it
+ALTER TABLE message_reports
+    ADD COLUMN conversation_id BIGINT,
+    ADD COLUMN author_id BIGINT REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN author VARCHAR(50),
+    ADD COLUMN content TEXT,
+    ADD COLUMN sent_at TIMESTAMPTZ;
+
+UPDATE message_reports
+SET conversation_id = messages.conversation_id,
+    author_id = messages.author_id,
+    author = users.username,
+    content = messages.content,
+    sent_at = messages.created_at
+FROM messages
+INNER JOIN users ON users.id = messages.author_id
+WHERE messages.id = message_reports.message_id;
+
+ALTER TABLE message_reports
+    ALTER COLUMN conversation_id SET NOT NULL,
+    ALTER COLUMN author SET NOT NULL,
+    ALTER COLUMN content SET NOT NULL,
+    ALTER COLUMN sent_at SET NOT NULL,
+    ALTER COLUMN message_id DROP NOT NULL,
+    DROP CONSTRAINT message_reports_message_id_fkey,
+    ADD CONSTRAINT message_reports_message_id_fkey
+        FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE SET NULL;
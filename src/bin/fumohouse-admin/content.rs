@@ -0,0 +1,116 @@
+use super::{CliError, CliResult};
+use fumohouse_web::util::{markdown::PageCache, Changelog, Mailer, News};
+use rocket_dyn_templates::tera::Tera;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+pub fn run(args: &[&str]) -> CliResult {
+    match args {
+        ["validate"] => validate(),
+        _ => Err(CliError::Usage),
+    }
+}
+
+/// Prints how loading one kind of content went, returning whether it worked.
+fn report<T, E: ToString>(what: &str, result: Result<T, E>) -> bool {
+    match result {
+        Ok(_) => {
+            println!("ok      {}", what);
+            true
+        }
+        Err(err) => {
+            println!("FAILED  {}: {}", what, err.to_string());
+            false
+        }
+    }
+}
+
+fn validate() -> CliResult {
+    let template_dir = rocket::Config::figment()
+        .extract_inner::<PathBuf>("template_dir")
+        .unwrap_or_else(|_| "templates".into());
+
+    let pages = PageCache::load();
+
+    let results = [
+        report("markdown pages", pages.as_ref()),
+        match pages {
+            Ok(ref pages) => report("changelog", Changelog::load(pages)),
+            Err(_) => report("changelog", Err::<(), _>("needs the markdown pages")),
+        },
+        match pages {
+            Ok(ref pages) => report("news", News::load(pages)),
+            Err(_) => report("news", Err::<(), _>("needs the markdown pages")),
+        },
+        report("site templates", load_templates(&template_dir)),
+        report("email templates", Mailer::load(&template_dir)),
+    ];
+
+    let failed = results.iter().filter(|ok| !**ok).count();
+
+    if failed > 0 {
+        return Err(format!("{} of {} checks failed", failed, results.len()).into());
+    }
+
+    Ok(())
+}
+
+/// Parses every template, named the way Rocket names them, so that missing
+/// parents and macros are caught as well as syntax errors.
+fn load_templates(template_dir: &Path) -> Result<Tera, String> {
+    let mut files = Vec::new();
+    find_templates(template_dir, template_dir, &mut files)
+        .map_err(|err| format!("failed to read {}: {}", template_dir.display(), err))?;
+
+    let mut tera = Tera::default();
+    tera.add_template_files(files).map_err(|err| {
+        // Tera's errors say which template failed in the source
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(&err);
+
+        while let Some(err) = source {
+            message = format!("{}: {}", message, err);
+            source = err.source();
+        }
+
+        message
+    })?;
+
+    Ok(tera)
+}
+
+fn find_templates(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(PathBuf, Option<String>)>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            find_templates(root, &path, files)?;
+            continue;
+        }
+
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) if relative.to_string_lossy().ends_with(".tera") => relative,
+            _ => continue,
+        };
+
+        // Everything from the first dot of the file name is dropped
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let (dir_part, file_name) = relative.rsplit_once('/').unwrap_or(("", &relative));
+        let stem = file_name.split('.').next().unwrap_or_default();
+
+        let name = match dir_part {
+            "" => stem.to_string(),
+            _ => format!("{}/{}", dir_part, stem),
+        };
+
+        files.push((path, Some(name)));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use super::{connect, options, CliError, CliResult};
+use chrono::{Duration, Utc};
+use fumohouse_web::{
+    models::{Invite, NewInvite},
+    util::invite,
+};
+
+pub fn run(args: &[&str]) -> CliResult {
+    match args {
+        ["create", args @ ..] => create(args),
+        ["list"] => list(),
+        _ => Err(CliError::Usage),
+    }
+}
+
+fn create(args: &[&str]) -> CliResult {
+    let options = options(args, &["uses", "days", "note"])?;
+
+    let max_uses = match options.get("uses") {
+        Some(uses) => uses
+            .parse::<i32>()
+            .ok()
+            .filter(|uses| *uses > 0)
+            .ok_or_else(|| CliError::Failed("--uses must be a positive number".to_string()))?,
+        None => 1,
+    };
+
+    let expires_at = match options.get("days") {
+        Some(days) => {
+            let days = days
+                .parse::<i64>()
+                .ok()
+                .filter(|days| *days > 0)
+                .ok_or_else(|| CliError::Failed("--days must be a positive number".to_string()))?;
+
+            Some(Utc::now() + Duration::days(days))
+        }
+        None => None,
+    };
+
+    let c = &mut connect()?;
+    let code = invite::generate_code();
+
+    let invite = Invite::create(
+        c,
+        &NewInvite {
+            code: &code,
+            note: options.get("note").copied(),
+            max_uses,
+            expires_at,
+        },
+    )?;
+
+    match invite.expires_at {
+        Some(at) => eprintln!(
+            "Created an invite for {} uses, expiring {}:",
+            invite.max_uses,
+            at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        None => eprintln!("Created an invite for {} uses:", invite.max_uses),
+    }
+    println!("{}", invite.code);
+
+    Ok(())
+}
+
+fn list() -> CliResult {
+    let c = &mut connect()?;
+
+    println!(
+        "{:<16}  {:>9}  {:<16}  {:<6}  note",
+        "code", "used", "expires", "usable"
+    );
+
+    for invite in Invite::list(c)? {
+        println!(
+            "{:<16}  {:>9}  {:<16}  {:<6}  {}",
+            invite.code,
+            format!("{}/{}", invite.uses, invite.max_uses),
+            invite
+                .expires_at
+                .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "never".to_string()),
+            if invite.is_usable() { "yes" } else { "no" },
+            invite.note.as_deref().unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
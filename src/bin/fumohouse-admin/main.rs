@@ -0,0 +1,141 @@
+use diesel::{Connection, PgConnection};
+use std::{collections::HashMap, env};
+
+mod content;
+mod invites;
+mod manifests;
+mod migrations;
+mod sessions;
+mod users;
+
+const USAGE: &str = "\
+Usage: fumohouse-admin <command>
+
+Commands which use the database read DATABASE_URL, from .env if present.
+
+Users:
+    user create <username> [role]
+        Creates a user with a random password, which is printed.
+    user list [role]
+        Lists users, optionally only those with at least the given role.
+    user disable <username>
+        Bans a user and signs them out everywhere.
+    user enable <username>
+        Lifts a user's ban.
+    user promote <username> <role>
+        Sets a user's role: user, tester, moderator or admin.
+    user reset-password <username>
+        Sets a new random password, which is printed, and signs the user
+        out everywhere.
+
+Sessions:
+    session purge
+        Deletes expired sessions and presence.
+    session revoke <username>
+        Signs a user out everywhere.
+
+Invites:
+    invite create [--uses <count>] [--days <days>] [--note <note>]
+        Creates an invite code, which by default can be used once and
+        never expires. Codes are only needed when INVITE_ONLY is set.
+    invite list
+        Lists invite codes.
+
+Launcher updates:
+    manifest generate-key <key id>
+        Writes a new manifest signing key to <key id>.key and prints
+        the entry to add to UPDATE_PUBLIC_KEYS.
+    manifest sign <key file> <manifest file> [build dir]
+        Signs an update manifest downloaded from /admin/manifests and
+        prints the result. If a build directory is given, the hashes of
+        the files the manifest references are checked against it first.
+
+Migrations:
+    migrate status
+        Prints the schema version and any migrations yet to be applied.
+    migrate run
+        Applies pending migrations. The site does this itself when it
+        starts, unless AUTO_MIGRATE is set to false.
+
+Content:
+    content validate
+        Loads the markdown pages, changelog, news and templates the way
+        the site does at startup and reports any errors.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    if let Err(err) = fumohouse_web::util::setup_logging(false) {
+        eprintln!("warning: failed to set up logging: {}", err);
+    }
+
+    let _ = dotenvy::dotenv();
+
+    let result = match args.as_slice() {
+        ["user", args @ ..] => users::run(args),
+        ["session", args @ ..] => sessions::run(args),
+        ["invite", args @ ..] => invites::run(args),
+        ["manifest", args @ ..] => manifests::run(args),
+        ["migrate", args @ ..] => migrations::run(args),
+        ["content", args @ ..] => content::run(args),
+        _ => Err(CliError::Usage),
+    };
+
+    let code = match result {
+        Ok(()) => 0,
+        Err(CliError::Usage) => {
+            eprintln!("{}", USAGE);
+            2
+        }
+        Err(CliError::Failed(err)) => {
+            eprintln!("error: {}", err);
+            1
+        }
+    };
+
+    std::process::exit(code);
+}
+
+pub enum CliError {
+    /// The arguments didn't match any command
+    Usage,
+    Failed(String),
+}
+
+impl<E: ToString> From<E> for CliError {
+    fn from(err: E) -> CliError {
+        CliError::Failed(err.to_string())
+    }
+}
+
+pub type CliResult = Result<(), CliError>;
+
+pub fn connect() -> Result<PgConnection, CliError> {
+    let url = env::var("DATABASE_URL")
+        .map_err(|_| CliError::Failed("DATABASE_URL is not set".to_string()))?;
+
+    PgConnection::establish(&url)
+        .map_err(|err| CliError::Failed(format!("failed to connect to the database: {}", err)))
+}
+
+/// Parses `--name value` pairs, allowing only the given names.
+pub fn options<'a>(
+    args: &[&'a str],
+    allowed: &[&str],
+) -> Result<HashMap<&'a str, &'a str>, CliError> {
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let name = match arg.strip_prefix("--") {
+            Some(name) if allowed.contains(&name) => name,
+            _ => return Err(CliError::Usage),
+        };
+
+        let value = args.next().ok_or(CliError::Usage)?;
+        options.insert(name, *value);
+    }
+
+    Ok(options)
+}
@@ -0,0 +1,88 @@
+use super::{CliError, CliResult};
+use fumohouse_web::util::{self, update};
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+pub fn run(args: &[&str]) -> CliResult {
+    match args {
+        ["generate-key", key_id] => generate_key(key_id),
+        ["sign", key_file, manifest_file] => sign_manifest(key_file, manifest_file, None),
+        ["sign", key_file, manifest_file, build_dir] => {
+            sign_manifest(key_file, manifest_file, Some(build_dir))
+        }
+        _ => Err(CliError::Usage),
+    }
+}
+
+fn generate_key(key_id: &str) -> CliResult {
+    if key_id.is_empty()
+        || !key_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err("key ids may only contain letters, digits and dashes".into());
+    }
+
+    let (key_file, trusted) = update::generate_key(key_id);
+    let path = format!("{}.key", key_id);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options
+        .open(&path)
+        .and_then(|mut file| file.write_all(key_file.as_bytes()))
+        .map_err(|err| format!("failed to write {}: {}", path, err))?;
+
+    eprintln!("Wrote signing key to {}. Keep it offline.", path);
+    println!("{}:{}", trusted.key_id, trusted.public_key);
+
+    Ok(())
+}
+
+fn sign_manifest(key_file: &str, manifest_file: &str, build_dir: Option<&str>) -> CliResult {
+    let key = fs::read_to_string(key_file)
+        .map_err(|err| format!("failed to read {}: {}", key_file, err))?;
+    let manifest = fs::read_to_string(manifest_file)
+        .map_err(|err| format!("failed to read {}: {}", manifest_file, err))?;
+    let manifest = manifest.trim_end();
+
+    if let Some(build_dir) = build_dir {
+        let parsed: update::Manifest = rocket::serde::json::from_str(manifest)?;
+
+        check_file(build_dir, &parsed.file.url, &parsed.file.sha256)?;
+
+        for patch in &parsed.patches {
+            check_file(build_dir, &patch.url, &patch.sha256)?;
+        }
+    }
+
+    let signed = update::sign(&key, manifest)?;
+    println!("{}", rocket::serde::json::to_string(&signed)?);
+
+    Ok(())
+}
+
+/// Checks that the local copy of a file referenced by a manifest matches its hash.
+fn check_file(build_dir: &str, url: &str, expected: &str) -> CliResult {
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    let path = Path::new(build_dir).join(file_name);
+
+    let hash = util::sha256_file(&path)
+        .map_err(|err| format!("failed to hash {}: {}", path.display(), err))?;
+
+    if util::to_hex(&hash) != expected {
+        return Err(format!("{} does not match the manifest's hash", path.display()).into());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use super::{connect, CliError, CliResult};
+use fumohouse_web::db::migrations;
+
+pub fn run(args: &[&str]) -> CliResult {
+    match args {
+        ["status"] => status(),
+        ["run"] => apply(),
+        _ => Err(CliError::Usage),
+    }
+}
+
+fn status() -> CliResult {
+    let c = &mut connect()?;
+    let version = migrations::schema_version(c)?;
+    let pending = migrations::pending(c)?;
+
+    println!("Schema version: {}", version.as_deref().unwrap_or("none"));
+
+    if pending.is_empty() {
+        println!("No pending migrations.");
+    } else {
+        println!("Pending migrations:");
+
+        for version in pending {
+            println!("    {}", version);
+        }
+    }
+
+    Ok(())
+}
+
+fn apply() -> CliResult {
+    let c = &mut connect()?;
+    let applied = migrations::run(c)?;
+
+    for version in &applied {
+        println!("Applied {}", version);
+    }
+
+    println!(
+        "Schema version: {}",
+        migrations::schema_version(c)?.as_deref().unwrap_or("none")
+    );
+
+    Ok(())
+}
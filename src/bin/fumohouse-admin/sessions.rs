@@ -0,0 +1,36 @@
+use super::{connect, users, CliError, CliResult};
+use fumohouse_web::{
+    models::Session,
+    util::{jobs::JobHandler, PurgeExpiredPresence, PurgeExpiredSessions},
+};
+
+pub fn run(args: &[&str]) -> CliResult {
+    match args {
+        ["purge"] => purge(),
+        ["revoke", username] => revoke(username),
+        _ => Err(CliError::Usage),
+    }
+}
+
+/// Runs the same jobs the site runs on a schedule.
+fn purge() -> CliResult {
+    let c = &mut connect()?;
+
+    PurgeExpiredSessions.run(c)?;
+    PurgeExpiredPresence.run(c)?;
+
+    println!("Purged expired sessions and presence.");
+
+    Ok(())
+}
+
+fn revoke(username: &str) -> CliResult {
+    let c = &mut connect()?;
+
+    let user = users::find(c, username)?;
+    let sessions = Session::revoke_all(c, user.id)?;
+
+    println!("Ended {} sessions for {}.", sessions, user.username);
+
+    Ok(())
+}
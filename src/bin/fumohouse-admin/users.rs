@@ -0,0 +1,174 @@
+use super::{connect, CliError, CliResult};
+use argon2::Argon2;
+use diesel::{prelude::*, PgConnection};
+use fumohouse_web::{
+    db::schema::users,
+    models::{NewUser, Role, Session, User},
+    util,
+};
+use rand::{distributions::Alphanumeric, Rng};
+
+const GENERATED_PASSWORD_LENGTH: usize = 20;
+
+pub fn run(args: &[&str]) -> CliResult {
+    match args {
+        ["create", username] => create(username, Role::User),
+        ["create", username, role] => create(username, parse_role(role)?),
+        ["list"] => list(None),
+        ["list", role] => list(Some(parse_role(role)?)),
+        ["disable", username] => set_banned(username, true),
+        ["enable", username] => set_banned(username, false),
+        ["promote", username, role] => promote(username, parse_role(role)?),
+        ["reset-password", username] => reset_password(username),
+        _ => Err(CliError::Usage),
+    }
+}
+
+fn parse_role(role: &str) -> Result<Role, CliError> {
+    role.parse::<Role>().map_err(CliError::Failed)
+}
+
+pub fn find(c: &mut PgConnection, username: &str) -> Result<User, CliError> {
+    match User::find(c, username) {
+        Ok(user) => Ok(user),
+        Err(diesel::result::Error::NotFound) => {
+            Err(format!("no user is named {}", username).into())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Returns a new password and its hash.
+fn generate_password() -> Result<(String, String), CliError> {
+    let password: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(GENERATED_PASSWORD_LENGTH)
+        .map(char::from)
+        .collect();
+
+    let hash = util::hash_password(&Argon2::default(), &password)?;
+
+    Ok((password, hash))
+}
+
+fn create(username: &str, role: Role) -> CliResult {
+    if username.is_empty()
+        || username.len() > util::USERNAME_MAX_LENGTH
+        || !util::is_valid_username(username)
+    {
+        return Err(format!(
+            "usernames must be 1 to {} letters, digits, spaces or []()-_",
+            util::USERNAME_MAX_LENGTH
+        )
+        .into());
+    }
+
+    let c = &mut connect()?;
+
+    if User::find(c, username).is_ok() {
+        return Err(format!("{} is already taken", username).into());
+    }
+
+    let (password, hash) = generate_password()?;
+
+    let user: User = diesel::insert_into(users::table)
+        .values((
+            &NewUser {
+                username,
+                password: &hash,
+            },
+            users::role.eq(role),
+        ))
+        .get_result(c)?;
+
+    eprintln!(
+        "Created {} ({}) with id {}. Their password is:",
+        user.username,
+        user.role.as_str(),
+        user.id
+    );
+    println!("{}", password);
+
+    Ok(())
+}
+
+fn list(min_role: Option<Role>) -> CliResult {
+    let c = &mut connect()?;
+
+    println!(
+        "{:>8}  {:<32}  {:<9}  {:<6}  created",
+        "id", "username", "role", "banned"
+    );
+
+    for user in User::list(c, min_role)? {
+        println!(
+            "{:>8}  {:<32}  {:<9}  {:<6}  {}",
+            user.id,
+            user.username,
+            user.role.as_str(),
+            if user.banned { "yes" } else { "no" },
+            user.created_at.format("%Y-%m-%d")
+        );
+    }
+
+    Ok(())
+}
+
+fn set_banned(username: &str, banned: bool) -> CliResult {
+    let c = &mut connect()?;
+    let user = find(c, username)?;
+
+    diesel::update(users::table.find(user.id))
+        .set(users::banned.eq(banned))
+        .execute(c)?;
+
+    if banned {
+        let sessions = Session::revoke_all(c, user.id)?;
+        println!(
+            "Disabled {} and ended {} sessions.",
+            user.username, sessions
+        );
+    } else {
+        println!("Enabled {}.", user.username);
+    }
+
+    Ok(())
+}
+
+fn promote(username: &str, role: Role) -> CliResult {
+    let c = &mut connect()?;
+    let user = find(c, username)?;
+
+    diesel::update(users::table.find(user.id))
+        .set(users::role.eq(role))
+        .execute(c)?;
+
+    println!(
+        "{} is now {} (was {}).",
+        user.username,
+        role.as_str(),
+        user.role.as_str()
+    );
+
+    Ok(())
+}
+
+fn reset_password(username: &str) -> CliResult {
+    let c = &mut connect()?;
+    let user = find(c, username)?;
+    let (password, hash) = generate_password()?;
+
+    diesel::update(users::table.find(user.id))
+        .set(users::password.eq(hash))
+        .execute(c)?;
+
+    let sessions = Session::revoke_all(c, user.id)?;
+
+    eprintln!(
+        "Reset {}'s password and ended {} sessions. Their new password is:",
+        user.username, sessions
+    );
+    println!("{}", password);
+
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use super::FumohouseDb;
+use diesel::{
+    dsl::sql,
+    migration::RunMigrationsError,
+    sql_types::{BigInt, Bool},
+    Connection, PgConnection, QueryResult, RunQueryDsl,
+};
+use diesel_migrations::MigrationConnection;
+use rocket::{
+    fairing::{self, Fairing, Info, Kind},
+    Build, Rocket,
+};
+use std::{collections::HashSet, env};
+
+// Everything under `migrations/`, built into the binary
+embed_migrations!();
+
+/// Held while migrating, so instances starting together take turns
+const MIGRATION_LOCK: i64 = 0x0066_756d_6f6d_6967; // "fumomig"
+
+/// The schema version, which is the version of the latest migration applied.
+pub fn schema_version(c: &PgConnection) -> QueryResult<Option<String>> {
+    if migrations_table_exists(c)? {
+        c.latest_run_migration_version()
+    } else {
+        Ok(None)
+    }
+}
+
+/// Applies every pending migration in one transaction, returning their versions.
+pub fn run(c: &PgConnection) -> Result<Vec<String>, RunMigrationsError> {
+    let mut output = Vec::new();
+
+    c.transaction(|| {
+        lock(c)?;
+        embedded_migrations::run_with_output(c, &mut output)
+    })?;
+
+    Ok(applied_versions(&output))
+}
+
+/// The versions of every migration built into the binary, oldest first.
+pub const VERSIONS: &[&str] = include!(concat!(env!("OUT_DIR"), "/migration_versions.rs"));
+
+/// The schema version the site expects, which is that of its latest migration.
+pub fn latest_version() -> Option<&'static str> {
+    VERSIONS.last().copied()
+}
+
+/// The versions of the migrations `run` would apply. This only reads which
+/// have been applied, so it never changes the database.
+pub fn pending(c: &PgConnection) -> QueryResult<Vec<String>> {
+    // Until the first migration, diesel hasn't created its table
+    let applied = if migrations_table_exists(c)? {
+        c.previously_run_migration_versions()?
+    } else {
+        HashSet::new()
+    };
+
+    Ok(VERSIONS
+        .iter()
+        .filter(|version| !applied.contains(**version))
+        .map(|version| version.to_string())
+        .collect())
+}
+
+fn migrations_table_exists(c: &PgConnection) -> QueryResult<bool> {
+    diesel::select(sql::<Bool>(
+        "to_regclass('__diesel_schema_migrations') IS NOT NULL",
+    ))
+    .get_result(c)
+}
+
+fn lock(c: &PgConnection) -> QueryResult<()> {
+    diesel::sql_query("SELECT pg_advisory_xact_lock($1)")
+        .bind::<BigInt, _>(MIGRATION_LOCK)
+        .execute(c)?;
+
+    Ok(())
+}
+
+/// Migrations report each one they run as `Running migration <version>`.
+fn applied_versions(output: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter_map(|line| line.strip_prefix("Running migration "))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Applies pending migrations before the site starts. With AUTO_MIGRATE set
+/// to `false`, the site refuses to start instead, so that migrations can be
+/// applied deliberately with `fumohouse-admin migrate run`.
+pub struct Migrations;
+
+#[rocket::async_trait]
+impl Fairing for Migrations {
+    fn info(&self) -> Info {
+        Info {
+            name: "apply database migrations",
+            kind: Kind::Ignite,
+        }
+    }
+
+    async fn on_ignite(&self, rocket: Rocket<Build>) -> fairing::Result {
+        let auto_migrate = env::var("AUTO_MIGRATE")
+            .map(|value| !matches!(value.as_str(), "0" | "false"))
+            .unwrap_or(true);
+
+        let conn = match FumohouseDb::get_one(&rocket).await {
+            Some(conn) => conn,
+            None => {
+                error!("migrations: no database connection");
+                return Err(rocket);
+            }
+        };
+
+        let result = conn
+            .run(move |c| -> Result<_, RunMigrationsError> {
+                let migrations = if auto_migrate { run(c)? } else { pending(c)? };
+                Ok((migrations, schema_version(c)?))
+            })
+            .await;
+
+        let version = match result {
+            Ok((migrations, version)) if migrations.is_empty() => version,
+            Ok((migrations, version)) if auto_migrate => {
+                info!("migrations: applied {}", migrations.join(", "));
+                version
+            }
+            Ok((migrations, _)) => {
+                error!(
+                    "migrations: refusing to start with pending migrations {} \
+                     as AUTO_MIGRATE is off",
+                    migrations.join(", ")
+                );
+                return Err(rocket);
+            }
+            Err(err) => {
+                error!("migrations: failed to migrate: {}", err);
+                return Err(rocket);
+            }
+        };
+
+        info!(
+            "migrations: schema version {}",
+            version.as_deref().unwrap_or("none")
+        );
+
+        Ok(rocket)
+    }
+}
@@ -0,0 +1,86 @@
+use super::release::serialize_hex;
+use crate::db::{lower, schema::contributor_signatures};
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// A signature of the contributor agreement. These are never updated or
+/// deleted, which the database enforces; signing a new version adds a row.
+/// Deleting the signer's account only detaches its signatures.
+#[derive(Queryable, Serialize)]
+pub struct ContributorSignature {
+    pub id: i64,
+    /// `None` once the signer's account has been deleted
+    pub user_id: Option<i64>,
+    pub legal_name: String,
+    pub github_handle: String,
+    pub version: String,
+    #[serde(serialize_with = "serialize_hex")]
+    pub document_sha256: Vec<u8>,
+    pub signed_at: DateTime<Utc>,
+    /// The verified GitHub account. Signatures from before verification only
+    /// have the handle that was entered.
+    pub github_id: Option<i64>,
+}
+
+impl ContributorSignature {
+    pub fn latest_for_user(
+        c: &PgConnection,
+        for_user: i64,
+    ) -> QueryResult<Option<ContributorSignature>> {
+        use crate::db::schema::contributor_signatures::dsl::*;
+
+        contributor_signatures
+            .filter(user_id.eq(for_user))
+            .order((signed_at.desc(), id.desc()))
+            .first(c)
+            .optional()
+    }
+
+    /// GitHub handles are case insensitive.
+    pub fn latest_for_handle(
+        c: &mut PgConnection,
+        handle: &str,
+    ) -> QueryResult<Option<ContributorSignature>> {
+        use crate::db::schema::contributor_signatures::dsl::*;
+
+        contributor_signatures
+            .filter(lower(github_handle).eq(lower(handle)))
+            .order((signed_at.desc(), id.desc()))
+            .first(c)
+            .optional()
+    }
+
+    /// The latest signature made as the GitHub account with the given ID or
+    /// handle by anyone other than `signer`.
+    pub fn latest_by_other(
+        c: &PgConnection,
+        signer: i64,
+        account_id: i64,
+        handle: &str,
+    ) -> QueryResult<Option<ContributorSignature>> {
+        use crate::db::schema::contributor_signatures::dsl::*;
+
+        contributor_signatures
+            .filter(
+                github_id
+                    .eq(account_id)
+                    .or(lower(github_handle).eq(lower(handle))),
+            )
+            .filter(user_id.is_distinct_from(signer))
+            .order((signed_at.desc(), id.desc()))
+            .first(c)
+            .optional()
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "contributor_signatures"]
+pub struct NewContributorSignature<'a> {
+    pub user_id: i64,
+    pub legal_name: &'a str,
+    pub github_handle: &'a str,
+    pub version: &'a str,
+    pub document_sha256: &'a [u8],
+    pub github_id: Option<i64>,
+}
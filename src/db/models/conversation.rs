@@ -0,0 +1,285 @@
+use crate::db::schema::{conversation_members, conversations, direct_conversations, users};
+use chrono::{DateTime, Utc};
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    result::Error as DieselError,
+    sql_types::{BigInt, Nullable, Text},
+    PgConnection,
+};
+use rocket::serde::Serialize;
+use std::collections::HashMap;
+
+/// How many people can be in a group conversation, its creator included
+pub const MAX_GROUP_MEMBERS: usize = 10;
+
+#[derive(Queryable, Serialize, Clone)]
+pub struct Conversation {
+    pub id: i64,
+    pub title: Option<String>,
+    pub is_group: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_message_at: DateTime<Utc>,
+}
+
+/// Someone in a conversation, with how far they have read.
+#[derive(Queryable, Serialize, Clone)]
+pub struct ConversationMember {
+    pub user_id: i64,
+    pub username: String,
+    pub joined_at: DateTime<Utc>,
+    /// The newest message they have seen, or 0 if they haven't seen any
+    pub last_read_id: i64,
+}
+
+/// A conversation as listed in a user's inbox.
+#[derive(Serialize)]
+pub struct ConversationSummary {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    /// Everyone else in the conversation
+    pub members: Vec<String>,
+    pub unread_count: i64,
+    /// The start of the newest message
+    pub preview: Option<String>,
+}
+
+/// The characters of the newest message shown in the inbox
+const PREVIEW_LENGTH: usize = 100;
+
+/// Leaves out messages from anyone the member has blocked.
+const NOT_BLOCKED_SQL: &str = "NOT EXISTS (SELECT 1 FROM user_blocks \
+    WHERE user_blocks.blocker_id = conversation_members.user_id \
+    AND user_blocks.blocked_id = messages.author_id)";
+
+/// Messages in the conversation the member hasn't seen, besides their own.
+fn unread_sql() -> String {
+    format!(
+        "(SELECT COUNT(*) FROM messages \
+        WHERE messages.conversation_id = conversation_members.conversation_id \
+        AND messages.id > conversation_members.last_read_id \
+        AND messages.author_id <> conversation_members.user_id \
+        AND {})",
+        NOT_BLOCKED_SQL
+    )
+}
+
+impl Conversation {
+    /// Only finds the conversation if the user is in it.
+    pub fn find_for_member(
+        c: &mut PgConnection,
+        conversation_id: i64,
+        user_id: i64,
+    ) -> QueryResult<Option<Conversation>> {
+        conversations::table
+            .inner_join(conversation_members::table)
+            .filter(conversations::id.eq(conversation_id))
+            .filter(conversation_members::user_id.eq(user_id))
+            .select(conversations::all_columns)
+            .first(c)
+            .optional()
+    }
+
+    /// The one-to-one conversation between two users, if they have one.
+    pub fn find_direct(c: &PgConnection, a: i64, b: i64) -> QueryResult<Option<Conversation>> {
+        direct_conversations::table
+            .inner_join(conversations::table)
+            .filter(direct_conversations::low_user_id.eq(a.min(b)))
+            .filter(direct_conversations::high_user_id.eq(a.max(b)))
+            .select(conversations::all_columns)
+            .first(c)
+            .optional()
+    }
+
+    /// The one-to-one conversation between two users, started if they don't
+    /// have one yet.
+    pub fn find_or_create_direct(c: &PgConnection, a: i64, b: i64) -> QueryResult<Conversation> {
+        if let Some(conversation) = Conversation::find_direct(c, a, b)? {
+            return Ok(conversation);
+        }
+
+        let created = c.transaction(|| {
+            let conversation = Conversation::create(c, &[a, b], false, None)?;
+
+            let inserted = diesel::insert_into(direct_conversations::table)
+                .values((
+                    direct_conversations::low_user_id.eq(a.min(b)),
+                    direct_conversations::high_user_id.eq(a.max(b)),
+                    direct_conversations::conversation_id.eq(conversation.id),
+                ))
+                .on_conflict_do_nothing()
+                .execute(c)?;
+
+            // Someone else started it first, so theirs is used instead
+            if inserted == 0 {
+                return Err(DieselError::RollbackTransaction);
+            }
+
+            Ok(conversation)
+        });
+
+        match created {
+            Err(DieselError::RollbackTransaction) => {
+                Conversation::find_direct(c, a, b)?.ok_or(DieselError::NotFound)
+            }
+            created => created,
+        }
+    }
+
+    /// Starts a conversation between the given users. Groups can have a title.
+    pub fn create(
+        c: &PgConnection,
+        member_ids: &[i64],
+        is_group: bool,
+        title: Option<&str>,
+    ) -> QueryResult<Conversation> {
+        c.transaction(|| {
+            let conversation = diesel::insert_into(conversations::table)
+                .values((
+                    conversations::title.eq(title),
+                    conversations::is_group.eq(is_group),
+                ))
+                .get_result::<Conversation>(c)?;
+
+            let members: Vec<_> = member_ids
+                .iter()
+                .map(|member_id| {
+                    (
+                        conversation_members::conversation_id.eq(conversation.id),
+                        conversation_members::user_id.eq(*member_id),
+                    )
+                })
+                .collect();
+
+            diesel::insert_into(conversation_members::table)
+                .values(&members)
+                .execute(c)?;
+
+            Ok(conversation)
+        })
+    }
+
+    /// Most recently active first.
+    pub fn for_user(
+        c: &mut PgConnection,
+        user_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<ConversationSummary>> {
+        let rows = conversations::table
+            .inner_join(conversation_members::table)
+            .filter(conversation_members::user_id.eq(user_id))
+            .select((
+                conversations::all_columns,
+                sql::<BigInt>(&unread_sql()),
+                sql::<Nullable<Text>>(&format!(
+                    "(SELECT content FROM messages \
+                    WHERE messages.conversation_id = conversations.id AND {} \
+                    ORDER BY messages.id DESC LIMIT 1)",
+                    NOT_BLOCKED_SQL
+                )),
+            ))
+            .order(conversations::last_message_at.desc())
+            .limit(limit)
+            .offset(offset)
+            .load::<(Conversation, i64, Option<String>)>(c)?;
+
+        let ids: Vec<i64> = rows.iter().map(|(conversation, _, _)| conversation.id).collect();
+
+        let mut members: HashMap<i64, Vec<String>> = HashMap::new();
+
+        for (conversation_id, username) in conversation_members::table
+            .inner_join(users::table)
+            .filter(conversation_members::conversation_id.eq_any(&ids))
+            .filter(conversation_members::user_id.ne(user_id))
+            .select((conversation_members::conversation_id, users::username))
+            .order(users::username)
+            .load::<(i64, String)>(c)?
+        {
+            members.entry(conversation_id).or_default().push(username);
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|(conversation, unread_count, preview)| ConversationSummary {
+                members: members.remove(&conversation.id).unwrap_or_default(),
+                preview: preview.map(|p| p.chars().take(PREVIEW_LENGTH).collect()),
+                conversation,
+                unread_count,
+            })
+            .collect())
+    }
+
+    pub fn count_for_user(c: &mut PgConnection, user_id: i64) -> QueryResult<i64> {
+        conversation_members::table
+            .filter(conversation_members::user_id.eq(user_id))
+            .count()
+            .get_result(c)
+    }
+
+    /// Unread messages across all of the user's conversations.
+    pub fn unread_count(c: &mut PgConnection, user_id: i64) -> QueryResult<i64> {
+        let counts = conversation_members::table
+            .filter(conversation_members::user_id.eq(user_id))
+            .select(sql::<BigInt>(&unread_sql()))
+            .load::<i64>(c)?;
+
+        Ok(counts.into_iter().sum())
+    }
+
+    /// Everyone in the conversation, in the order they joined.
+    pub fn members(c: &PgConnection, conversation_id: i64) -> QueryResult<Vec<ConversationMember>> {
+        conversation_members::table
+            .inner_join(users::table)
+            .filter(conversation_members::conversation_id.eq(conversation_id))
+            .select((
+                conversation_members::user_id,
+                users::username,
+                conversation_members::joined_at,
+                conversation_members::last_read_id,
+            ))
+            .order((conversation_members::joined_at, users::username))
+            .load(c)
+    }
+
+    /// Marks every message up to the given one as seen by the user.
+    pub fn mark_read(
+        c: &mut PgConnection,
+        conversation_id: i64,
+        user_id: i64,
+        message_id: i64,
+    ) -> QueryResult<()> {
+        use crate::db::schema::conversation_members::dsl;
+
+        diesel::update(
+            dsl::conversation_members
+                .find((conversation_id, user_id))
+                .filter(dsl::last_read_id.lt(message_id)),
+        )
+        .set(dsl::last_read_id.eq(message_id))
+        .execute(c)?;
+
+        Ok(())
+    }
+
+    /// Removes the user from a group. The group is deleted once it's empty.
+    pub fn leave(c: &mut PgConnection, conversation_id: i64, user_id: i64) -> QueryResult<()> {
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            diesel::delete(conversation_members::table.find((conversation_id, user_id)))
+                .execute(c)?;
+
+            let remaining: i64 = conversation_members::table
+                .filter(conversation_members::conversation_id.eq(conversation_id))
+                .count()
+                .get_result(c)?;
+
+            if remaining == 0 {
+                diesel::delete(conversations::table.find(conversation_id)).execute(c)?;
+            }
+
+            Ok(())
+        })
+    }
+}
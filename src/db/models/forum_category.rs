@@ -0,0 +1,78 @@
+use crate::db::schema::forum_categories;
+use chrono::{DateTime, Utc};
+use diesel::{dsl::sql, prelude::*, sql_types::BigInt, PgConnection};
+use rocket::serde::Serialize;
+
+#[derive(Queryable, Serialize)]
+pub struct ForumCategory {
+    pub id: i64,
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    pub position: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A category with how much has been posted in it, for the forum index.
+#[derive(Serialize)]
+pub struct CategorySummary {
+    #[serde(flatten)]
+    pub category: ForumCategory,
+    pub thread_count: i64,
+    pub post_count: i64,
+}
+
+impl ForumCategory {
+    pub fn find(c: &mut PgConnection, for_slug: &str) -> QueryResult<Option<ForumCategory>> {
+        use crate::db::schema::forum_categories::dsl::*;
+
+        forum_categories.filter(slug.eq(for_slug)).first(c).optional()
+    }
+
+    pub fn find_by_id(c: &mut PgConnection, category_id: i64) -> QueryResult<ForumCategory> {
+        forum_categories::table.find(category_id).first(c)
+    }
+
+    pub fn create(c: &mut PgConnection, category: &NewForumCategory) -> QueryResult<ForumCategory> {
+        diesel::insert_into(forum_categories::table)
+            .values(category)
+            .get_result(c)
+    }
+
+    /// Every category in order, with its thread and post counts.
+    pub fn summaries(c: &mut PgConnection) -> QueryResult<Vec<CategorySummary>> {
+        let categories: Vec<(ForumCategory, i64, i64)> = forum_categories::table
+            .select((
+                forum_categories::all_columns,
+                sql::<BigInt>(
+                    "(SELECT COUNT(*) FROM forum_threads \
+                     WHERE forum_threads.category_id = forum_categories.id)",
+                ),
+                sql::<BigInt>(
+                    "(SELECT COUNT(*) FROM forum_posts \
+                     INNER JOIN forum_threads ON forum_threads.id = forum_posts.thread_id \
+                     WHERE forum_threads.category_id = forum_categories.id)",
+                ),
+            ))
+            .order((forum_categories::position, forum_categories::name))
+            .load(c)?;
+
+        Ok(categories
+            .into_iter()
+            .map(|(category, thread_count, post_count)| CategorySummary {
+                category,
+                thread_count,
+                post_count,
+            })
+            .collect())
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "forum_categories"]
+pub struct NewForumCategory<'a> {
+    pub slug: &'a str,
+    pub name: &'a str,
+    pub description: &'a str,
+    pub position: i32,
+}
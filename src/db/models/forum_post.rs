@@ -0,0 +1,169 @@
+use super::Role;
+use crate::db::schema::{forum_post_edits, forum_posts, users};
+use chrono::{DateTime, Utc};
+use diesel::{dsl::now, prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+#[derive(Queryable, Serialize)]
+pub struct ForumPost {
+    pub id: i64,
+    pub thread_id: i64,
+    pub author_id: i64,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    /// Rendered when saved. `None` for posts from before that.
+    pub content_html: Option<String>,
+}
+
+/// A post with its author, for showing in a thread.
+#[derive(Queryable, Serialize)]
+pub struct PostInfo {
+    pub id: i64,
+    pub author_id: i64,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub content_html: Option<String>,
+    pub author: String,
+    pub author_role: Role,
+}
+
+/// An earlier version of a post, with who replaced it.
+#[derive(Queryable, Serialize)]
+pub struct PostEdit {
+    pub id: i64,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub editor: String,
+}
+
+impl ForumPost {
+    pub fn find(c: &mut PgConnection, post_id: i64) -> QueryResult<Option<ForumPost>> {
+        forum_posts::table.find(post_id).first(c).optional()
+    }
+
+    /// Oldest first.
+    pub fn for_thread(
+        c: &mut PgConnection,
+        for_thread: i64,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<PostInfo>> {
+        forum_posts::table
+            .inner_join(users::table)
+            .filter(forum_posts::thread_id.eq(for_thread))
+            .select((
+                forum_posts::id,
+                forum_posts::author_id,
+                forum_posts::content,
+                forum_posts::created_at,
+                forum_posts::edited_at,
+                forum_posts::content_html,
+                users::username,
+                users::role,
+            ))
+            .order(forum_posts::id)
+            .limit(limit)
+            .offset(offset)
+            .load(c)
+    }
+
+    pub fn count_for_thread(c: &mut PgConnection, for_thread: i64) -> QueryResult<i64> {
+        use crate::db::schema::forum_posts::dsl::*;
+
+        forum_posts
+            .filter(thread_id.eq(for_thread))
+            .count()
+            .get_result(c)
+    }
+
+    pub fn count_by_author(c: &mut PgConnection, for_author: i64) -> QueryResult<i64> {
+        use crate::db::schema::forum_posts::dsl::*;
+
+        forum_posts
+            .filter(author_id.eq(for_author))
+            .count()
+            .get_result(c)
+    }
+
+    /// The authors of the given posts, without duplicates.
+    pub fn authors(c: &mut PgConnection, post_ids: &[i64]) -> QueryResult<Vec<i64>> {
+        use crate::db::schema::forum_posts::dsl::*;
+
+        forum_posts
+            .filter(id.eq_any(post_ids))
+            .select(author_id)
+            .distinct()
+            .load(c)
+    }
+
+    /// Where the post is in its thread, counting from 1.
+    pub fn position(&self, c: &mut PgConnection) -> QueryResult<i64> {
+        use crate::db::schema::forum_posts::dsl::*;
+
+        forum_posts
+            .filter(thread_id.eq(self.thread_id).and(id.le(self.id)))
+            .count()
+            .get_result(c)
+    }
+
+    /// Replaces the post's content, keeping the old content in its history.
+    /// Returns whether it changed, comparing against the post as it is when
+    /// the edit is saved, rather than when it was loaded.
+    pub fn edit(
+        c: &mut PgConnection,
+        post_id: i64,
+        editor: i64,
+        new_content: &str,
+        new_html: &str,
+    ) -> QueryResult<bool> {
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            // Locked so that concurrent edits each keep the one before
+            let old_content: String = forum_posts::table
+                .find(post_id)
+                .select(forum_posts::content)
+                .for_update()
+                .first(c)?;
+
+            if old_content == new_content {
+                return Ok(false);
+            }
+
+            diesel::insert_into(forum_post_edits::table)
+                .values((
+                    forum_post_edits::post_id.eq(post_id),
+                    forum_post_edits::editor_id.eq(editor),
+                    forum_post_edits::content.eq(&old_content),
+                ))
+                .execute(c)?;
+
+            diesel::update(forum_posts::table.find(post_id))
+                .set((
+                    forum_posts::content.eq(new_content),
+                    forum_posts::content_html.eq(new_html),
+                    forum_posts::edited_at.eq(now),
+                ))
+                .execute(c)?;
+
+            Ok(true)
+        })
+    }
+
+    /// Earlier versions of the post, oldest first.
+    pub fn edits(c: &mut PgConnection, for_post: i64) -> QueryResult<Vec<PostEdit>> {
+        forum_post_edits::table
+            .inner_join(users::table)
+            .filter(forum_post_edits::post_id.eq(for_post))
+            .select((
+                forum_post_edits::id,
+                forum_post_edits::content,
+                forum_post_edits::created_at,
+                users::username,
+            ))
+            .order(forum_post_edits::id)
+            .load(c)
+    }
+}
@@ -0,0 +1,156 @@
+use crate::db::schema::{forum_posts, forum_threads, users};
+use chrono::{DateTime, Utc};
+use diesel::{dsl::now, prelude::*, sql_types::BigInt, PgConnection};
+use rocket::serde::Serialize;
+
+#[derive(Queryable, Serialize)]
+pub struct ForumThread {
+    pub id: i64,
+    pub category_id: i64,
+    pub author_id: i64,
+    pub title: String,
+    pub pinned: bool,
+    pub locked: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_post_at: DateTime<Utc>,
+}
+
+/// A thread with its author and size, for category listings.
+#[derive(Queryable, Serialize)]
+pub struct ThreadInfo {
+    pub id: i64,
+    pub title: String,
+    pub pinned: bool,
+    pub locked: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_post_at: DateTime<Utc>,
+    pub author: String,
+    pub post_count: i64,
+}
+
+impl ForumThread {
+    pub fn find(c: &mut PgConnection, thread_id: i64) -> QueryResult<Option<ForumThread>> {
+        forum_threads::table.find(thread_id).first(c).optional()
+    }
+
+    /// Pinned threads first, then those posted in most recently.
+    pub fn list(
+        c: &mut PgConnection,
+        for_category: i64,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<ThreadInfo>> {
+        forum_threads::table
+            .inner_join(users::table)
+            .filter(forum_threads::category_id.eq(for_category))
+            .select((
+                forum_threads::id,
+                forum_threads::title,
+                forum_threads::pinned,
+                forum_threads::locked,
+                forum_threads::created_at,
+                forum_threads::last_post_at,
+                users::username,
+                diesel::dsl::sql::<BigInt>(
+                    "(SELECT COUNT(*) FROM forum_posts WHERE forum_posts.thread_id = forum_threads.id)",
+                ),
+            ))
+            .order((
+                forum_threads::pinned.desc(),
+                forum_threads::last_post_at.desc(),
+            ))
+            .limit(limit)
+            .offset(offset)
+            .load(c)
+    }
+
+    pub fn count(c: &mut PgConnection, for_category: i64) -> QueryResult<i64> {
+        use crate::db::schema::forum_threads::dsl::*;
+
+        forum_threads
+            .filter(category_id.eq(for_category))
+            .count()
+            .get_result(c)
+    }
+
+    /// Starts a thread with its first post.
+    pub fn create(
+        c: &mut PgConnection,
+        thread: NewForumThread,
+        content: &str,
+        content_html: &str,
+    ) -> QueryResult<ForumThread> {
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            let thread: ForumThread = diesel::insert_into(forum_threads::table)
+                .values(&thread)
+                .get_result(c)?;
+
+            diesel::insert_into(forum_posts::table)
+                .values((
+                    forum_posts::thread_id.eq(thread.id),
+                    forum_posts::author_id.eq(thread.author_id),
+                    forum_posts::content.eq(content),
+                    forum_posts::content_html.eq(content_html),
+                ))
+                .execute(c)?;
+
+            Ok(thread)
+        })
+    }
+
+    /// Adds a post to the end of the thread, bumping it to the top of its
+    /// category.
+    pub fn reply(
+        c: &mut PgConnection,
+        for_thread: i64,
+        author: i64,
+        content: &str,
+        content_html: &str,
+    ) -> QueryResult<i64> {
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            let post_id = diesel::insert_into(forum_posts::table)
+                .values((
+                    forum_posts::thread_id.eq(for_thread),
+                    forum_posts::author_id.eq(author),
+                    forum_posts::content.eq(content),
+                    forum_posts::content_html.eq(content_html),
+                ))
+                .returning(forum_posts::id)
+                .get_result(c)?;
+
+            diesel::update(forum_threads::table.find(for_thread))
+                .set(forum_threads::last_post_at.eq(now))
+                .execute(c)?;
+
+            Ok(post_id)
+        })
+    }
+
+    pub fn set_locked(c: &mut PgConnection, thread_id: i64, value: bool) -> QueryResult<usize> {
+        use crate::db::schema::forum_threads::dsl::*;
+
+        diesel::update(forum_threads.find(thread_id))
+            .set(locked.eq(value))
+            .execute(c)
+    }
+
+    pub fn set_pinned(c: &mut PgConnection, thread_id: i64, value: bool) -> QueryResult<usize> {
+        use crate::db::schema::forum_threads::dsl::*;
+
+        diesel::update(forum_threads.find(thread_id))
+            .set(pinned.eq(value))
+            .execute(c)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "forum_threads"]
+pub struct NewForumThread<'a> {
+    pub category_id: i64,
+    pub author_id: i64,
+    pub title: &'a str,
+}
@@ -0,0 +1,133 @@
+use crate::db::schema::game_servers;
+use chrono::{DateTime, Utc};
+use diesel::{pg::Pg, prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+#[derive(Queryable, Serialize)]
+pub struct GameServer {
+    pub id: i64,
+    pub name: String,
+    pub address: String,
+    pub port: i32,
+    pub version: String,
+    pub max_players: i32,
+    pub player_count: i32,
+    pub region: String,
+    pub tags: Vec<String>,
+    #[serde(skip)]
+    pub token: Vec<u8>,
+    pub online: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_heartbeat: DateTime<Utc>,
+}
+
+#[derive(FromFormField, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Fullness {
+    /// Servers with at least one open slot
+    Available,
+    /// Servers with no players
+    Empty,
+    /// Servers with at least one player
+    Populated,
+}
+
+#[derive(FromFormField, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServerSort {
+    Players,
+    Name,
+    /// Servers in the region given by `near` first, then by player count
+    Ping,
+}
+
+#[derive(FromForm, Serialize)]
+pub struct ServerFilter {
+    pub region: Option<String>,
+    pub version: Option<String>,
+    pub tags: Vec<String>,
+    pub fullness: Option<Fullness>,
+    pub sort: Option<ServerSort>,
+    /// The player's region, for sorting by ping
+    pub near: Option<String>,
+}
+
+impl GameServer {
+    fn filtered(filter: &ServerFilter) -> game_servers::BoxedQuery<'_, Pg> {
+        use crate::db::schema::game_servers::dsl::*;
+
+        let mut query = game_servers.filter(online.eq(true)).into_boxed();
+
+        // Empty fields are sent by the server browser's filter form
+        if let Some(filter_region) = filter.region.as_deref().filter(|r| !r.is_empty()) {
+            query = query.filter(region.eq(filter_region));
+        }
+
+        if let Some(filter_version) = filter.version.as_deref().filter(|v| !v.is_empty()) {
+            query = query.filter(version.eq(filter_version));
+        }
+
+        let filter_tags: Vec<&str> = filter
+            .tags
+            .iter()
+            .map(String::as_str)
+            .filter(|t| !t.is_empty())
+            .collect();
+
+        if !filter_tags.is_empty() {
+            query = query.filter(tags.contains(filter_tags));
+        }
+
+        match filter.fullness {
+            Some(Fullness::Available) => query = query.filter(player_count.lt(max_players)),
+            Some(Fullness::Empty) => query = query.filter(player_count.eq(0)),
+            Some(Fullness::Populated) => query = query.filter(player_count.gt(0)),
+            None => (),
+        }
+
+        query
+    }
+
+    pub fn count(c: &mut PgConnection, filter: &ServerFilter) -> QueryResult<i64> {
+        Self::filtered(filter).count().get_result(c)
+    }
+
+    pub fn list(
+        c: &mut PgConnection,
+        filter: &ServerFilter,
+        offset: i64,
+        limit: i64,
+    ) -> QueryResult<Vec<GameServer>> {
+        use crate::db::schema::game_servers::dsl::*;
+
+        let query = Self::filtered(filter);
+
+        let query = match filter.sort.unwrap_or(ServerSort::Players) {
+            ServerSort::Players => query.order((player_count.desc(), name.asc())),
+            ServerSort::Name => query.order((name.asc(), id.asc())),
+            ServerSort::Ping => match filter.near.as_deref().filter(|n| !n.is_empty()) {
+                Some(near) => {
+                    query.order((region.eq(near).desc(), player_count.desc(), name.asc()))
+                }
+                None => query.order((player_count.desc(), name.asc())),
+            },
+        };
+
+        query.offset(offset).limit(limit).load(c)
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "game_servers"]
+pub struct NewGameServer<'a> {
+    pub name: &'a str,
+    pub address: &'a str,
+    pub port: i32,
+    pub version: &'a str,
+    pub max_players: i32,
+    pub region: &'a str,
+    pub tags: &'a [String],
+    pub token: &'a [u8],
+    pub online: bool,
+    pub last_heartbeat: DateTime<Utc>,
+}
@@ -0,0 +1,70 @@
+use super::{NewUser, User};
+use crate::db::schema::{invites, users};
+use chrono::{DateTime, Utc};
+use diesel::{dsl::now, prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct Invite {
+    pub id: i64,
+    pub code: String,
+    pub note: Option<String>,
+    pub max_uses: i32,
+    pub uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[table_name = "invites"]
+pub struct NewInvite<'a> {
+    pub code: &'a str,
+    pub note: Option<&'a str>,
+    pub max_uses: i32,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Invite {
+    pub fn create(c: &mut PgConnection, invite: &NewInvite) -> QueryResult<Invite> {
+        diesel::insert_into(invites::table)
+            .values(invite)
+            .get_result(c)
+    }
+
+    /// Uses up one of the invite's uses to register a user, returning `None`
+    /// if the invite doesn't exist, has expired or has no uses left.
+    pub fn redeem(c: &PgConnection, code: &str, new_user: &NewUser) -> QueryResult<Option<User>> {
+        c.transaction(|| {
+            let updated = diesel::update(
+                invites::table
+                    .filter(invites::code.eq(code))
+                    .filter(invites::uses.lt(invites::max_uses))
+                    .filter(
+                        invites::expires_at
+                            .is_null()
+                            .or(invites::expires_at.gt(now)),
+                    ),
+            )
+            .set(invites::uses.eq(invites::uses + 1))
+            .execute(c)?;
+
+            if updated == 0 {
+                return Ok(None);
+            }
+
+            diesel::insert_into(users::table)
+                .values(new_user)
+                .get_result(c)
+                .map(Some)
+        })
+    }
+
+    /// Newest first.
+    pub fn list(c: &mut PgConnection) -> QueryResult<Vec<Invite>> {
+        invites::table.order(invites::id.desc()).load(c)
+    }
+
+    pub fn is_usable(&self) -> bool {
+        self.uses < self.max_uses && self.expires_at.is_none_or(|at| at > Utc::now())
+    }
+}
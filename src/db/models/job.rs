@@ -0,0 +1,240 @@
+use crate::db::schema::jobs;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{dsl::now, prelude::*, PgConnection};
+use rocket::serde::{json::Value, Serialize};
+
+text_enum! {
+    #[derive(FromFormField)]
+    pub enum JobStatus {
+        // Waiting for its first or next attempt
+        Pending => "pending",
+        Running => "running",
+        Succeeded => "succeeded",
+        // Out of attempts, or its kind has no handler
+        Dead => "dead",
+    }
+}
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct Job {
+    pub id: i64,
+    pub kind: String,
+    pub payload: Value,
+    pub status: JobStatus,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub repeat_every: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[table_name = "jobs"]
+pub struct NewJob<'a> {
+    pub kind: &'a str,
+    pub payload: &'a Value,
+    pub max_attempts: i32,
+    pub run_at: DateTime<Utc>,
+    pub repeat_every: Option<i32>,
+}
+
+impl Job {
+    pub fn enqueue(c: &mut PgConnection, job: &NewJob) -> QueryResult<i64> {
+        diesel::insert_into(jobs::table)
+            .values(job)
+            .returning(jobs::id)
+            .get_result(c)
+    }
+
+    /// Schedules a recurring job unless a run of it is already waiting.
+    pub fn schedule_recurring(c: &mut PgConnection, job: &NewJob) -> QueryResult<bool> {
+        let inserted = diesel::insert_into(jobs::table)
+            .values(job)
+            .on_conflict_do_nothing()
+            .execute(c)?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Takes up to `limit` jobs of the given kinds which are due, marking
+    /// them as running for `lease`. Jobs left running by a worker that died
+    /// are taken again once their lease is up.
+    pub fn claim_due(
+        c: &mut PgConnection,
+        kinds: &[&str],
+        limit: i64,
+        lease: Duration,
+    ) -> QueryResult<Vec<Job>> {
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            let ids = jobs::table
+                .filter(jobs::kind.eq_any(kinds))
+                .filter(jobs::status.eq_any(vec![JobStatus::Pending, JobStatus::Running]))
+                .filter(jobs::run_at.le(now))
+                .order(jobs::run_at)
+                .limit(limit)
+                .select(jobs::id)
+                .for_update()
+                .skip_locked()
+                .load::<i64>(c)?;
+
+            diesel::update(jobs::table.filter(jobs::id.eq_any(&ids)))
+                .set((
+                    jobs::status.eq(JobStatus::Running),
+                    jobs::attempts.eq(jobs::attempts + 1),
+                    jobs::run_at.eq(Utc::now() + lease),
+                ))
+                .get_results(c)
+        })
+    }
+
+    /// Holds a running job for another `lease`, so that jobs which take a
+    /// while aren't taken by another worker. The attempt is the one the job
+    /// was claimed for. Returns false if the job's lease already ran out and
+    /// it was taken again.
+    pub fn extend_lease(
+        c: &mut PgConnection,
+        job_id: i64,
+        attempt: i32,
+        lease: Duration,
+    ) -> QueryResult<bool> {
+        let updated = diesel::update(
+            jobs::table
+                .find(job_id)
+                .filter(jobs::status.eq(JobStatus::Running))
+                .filter(jobs::attempts.eq(attempt)),
+        )
+        .set(jobs::run_at.eq(Utc::now() + lease))
+        .execute(c)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Finishes a job, or records a failed attempt if there is an error. A
+    /// failed job is tried again at `retry_at`, or dies if there isn't one.
+    /// Recurring jobs are scheduled again once they succeed or die. Returns
+    /// false, changing nothing, if the job was taken again in the meantime.
+    pub fn finish(
+        c: &mut PgConnection,
+        job: &Job,
+        error: Option<&str>,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> QueryResult<bool> {
+        let c: &PgConnection = c;
+
+        let status = match (error, retry_at) {
+            (None, _) => JobStatus::Succeeded,
+            (Some(_), Some(_)) => JobStatus::Pending,
+            (Some(_), None) => JobStatus::Dead,
+        };
+
+        let finished_at = match status {
+            JobStatus::Pending => None,
+            _ => Some(Utc::now()),
+        };
+
+        c.transaction(|| {
+            let updated = diesel::update(
+                jobs::table
+                    .find(job.id)
+                    .filter(jobs::status.eq(JobStatus::Running))
+                    .filter(jobs::attempts.eq(job.attempts)),
+            )
+            .set((
+                jobs::status.eq(status),
+                jobs::run_at.eq(retry_at.unwrap_or_else(Utc::now)),
+                jobs::last_error.eq(error),
+                jobs::finished_at.eq(finished_at),
+            ))
+            .execute(c)?;
+
+            if updated == 0 {
+                return Ok(false);
+            }
+
+            if let (Some(seconds), Some(_)) = (job.repeat_every, finished_at) {
+                diesel::insert_into(jobs::table)
+                    .values(&NewJob {
+                        kind: &job.kind,
+                        payload: &job.payload,
+                        max_attempts: job.max_attempts,
+                        run_at: Utc::now() + Duration::seconds(seconds.into()),
+                        repeat_every: job.repeat_every,
+                    })
+                    .on_conflict_do_nothing()
+                    .execute(c)?;
+            }
+
+            Ok(true)
+        })
+    }
+
+    /// Gives a dead job a fresh set of attempts, starting now.
+    pub fn retry(c: &mut PgConnection, job_id: i64) -> QueryResult<bool> {
+        let updated = diesel::update(
+            jobs::table
+                .find(job_id)
+                .filter(jobs::status.eq(JobStatus::Dead)),
+        )
+        .set((
+            jobs::status.eq(JobStatus::Pending),
+            jobs::attempts.eq(0),
+            jobs::run_at.eq(now),
+            jobs::finished_at.eq(None::<DateTime<Utc>>),
+        ))
+        .execute(c);
+
+        // A recurring job can't be retried while its next run is waiting
+        match updated {
+            Ok(updated) => Ok(updated > 0),
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _,
+            )) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Newest first, optionally only those with the given status.
+    pub fn list(
+        c: &mut PgConnection,
+        status: Option<JobStatus>,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<Job>> {
+        let mut query = jobs::table.into_boxed();
+
+        if let Some(status) = status {
+            query = query.filter(jobs::status.eq(status));
+        }
+
+        query
+            .order(jobs::id.desc())
+            .limit(limit)
+            .offset(offset)
+            .load(c)
+    }
+
+    pub fn count(c: &mut PgConnection, status: Option<JobStatus>) -> QueryResult<i64> {
+        let mut query = jobs::table.into_boxed();
+
+        if let Some(status) = status {
+            query = query.filter(jobs::status.eq(status));
+        }
+
+        query.count().get_result(c)
+    }
+
+    /// Deletes jobs which succeeded before the given time.
+    pub fn prune(c: &mut PgConnection, before: DateTime<Utc>) -> QueryResult<usize> {
+        diesel::delete(
+            jobs::table
+                .filter(jobs::status.eq(JobStatus::Succeeded))
+                .filter(jobs::finished_at.lt(before)),
+        )
+        .execute(c)
+    }
+}
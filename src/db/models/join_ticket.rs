@@ -0,0 +1,22 @@
+use crate::db::schema::join_tickets;
+use chrono::{DateTime, Utc};
+
+#[derive(Queryable)]
+pub struct JoinTicket {
+    pub id: i64,
+    pub user_id: i64,
+    pub server_id: i64,
+    pub ticket: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[table_name = "join_tickets"]
+pub struct NewJoinTicket<'a> {
+    pub user_id: i64,
+    pub server_id: i64,
+    pub ticket: &'a [u8],
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,62 @@
+use crate::db::schema::login_attempts;
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+
+/// A login which hasn't succeeded (yet).
+#[derive(Queryable)]
+pub struct LoginAttempt {
+    pub id: i64,
+    pub ip_address: Option<String>,
+    pub username: String,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl LoginAttempt {
+    pub fn record(c: &mut PgConnection, attempt: &NewLoginAttempt) -> QueryResult<usize> {
+        diesel::insert_into(login_attempts::table)
+            .values(attempt)
+            .execute(c)
+    }
+
+    pub fn count_by_ip_since(
+        c: &mut PgConnection,
+        ip: &str,
+        since: DateTime<Utc>,
+    ) -> QueryResult<i64> {
+        login_attempts::table
+            .filter(login_attempts::ip_address.eq(ip))
+            .filter(login_attempts::attempted_at.gt(since))
+            .count()
+            .get_result(c)
+    }
+
+    pub fn count_by_username_since(
+        c: &mut PgConnection,
+        username: &str,
+        since: DateTime<Utc>,
+    ) -> QueryResult<i64> {
+        login_attempts::table
+            .filter(login_attempts::username.eq(username))
+            .filter(login_attempts::attempted_at.gt(since))
+            .count()
+            .get_result(c)
+    }
+
+    /// Forgets attempts on an account once someone logs into it.
+    pub fn clear(c: &mut PgConnection, username: &str) -> QueryResult<usize> {
+        diesel::delete(login_attempts::table.filter(login_attempts::username.eq(username)))
+            .execute(c)
+    }
+
+    pub fn purge_before(c: &mut PgConnection, before: DateTime<Utc>) -> QueryResult<usize> {
+        diesel::delete(login_attempts::table.filter(login_attempts::attempted_at.lt(before)))
+            .execute(c)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "login_attempts"]
+pub struct NewLoginAttempt<'a> {
+    pub ip_address: Option<&'a str>,
+    pub username: &'a str,
+}
@@ -0,0 +1,147 @@
+use crate::db::schema::{conversation_members, conversations, messages, users};
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+#[derive(Queryable, Serialize)]
+pub struct Message {
+    pub id: i64,
+    pub conversation_id: i64,
+    pub author_id: i64,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A message with its author, for showing in a conversation.
+#[derive(Queryable, Serialize, Clone)]
+pub struct MessageInfo {
+    pub id: i64,
+    pub conversation_id: i64,
+    pub author_id: i64,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+    pub author: String,
+}
+
+/// Which messages of a conversation to load.
+#[derive(Clone, Copy)]
+pub enum MessageRange {
+    /// The newest messages
+    Latest,
+    /// The newest messages older than the given one
+    Before(i64),
+    /// The oldest messages newer than the given one
+    After(i64),
+}
+
+impl Message {
+    pub fn find(c: &mut PgConnection, message_id: i64) -> QueryResult<Option<MessageInfo>> {
+        messages::table
+            .inner_join(users::table)
+            .filter(messages::id.eq(message_id))
+            .select((
+                messages::id,
+                messages::conversation_id,
+                messages::author_id,
+                messages::content,
+                messages::created_at,
+                users::username,
+            ))
+            .first(c)
+            .optional()
+    }
+
+    /// Adds a message to a conversation, which its author has then read.
+    pub fn send(
+        c: &PgConnection,
+        conversation_id: i64,
+        author_id: i64,
+        content: &str,
+    ) -> QueryResult<Message> {
+        c.transaction(|| {
+            let message = diesel::insert_into(messages::table)
+                .values((
+                    messages::conversation_id.eq(conversation_id),
+                    messages::author_id.eq(author_id),
+                    messages::content.eq(content),
+                ))
+                .get_result::<Message>(c)?;
+
+            diesel::update(conversations::table.find(conversation_id))
+                .set(conversations::last_message_at.eq(message.created_at))
+                .execute(c)?;
+
+            diesel::update(conversation_members::table.find((conversation_id, author_id)))
+                .set(conversation_members::last_read_id.eq(message.id))
+                .execute(c)?;
+
+            Ok(message)
+        })
+    }
+
+    /// Up to `limit` messages in the range, oldest first.
+    pub fn for_conversation(
+        c: &mut PgConnection,
+        for_conversation: i64,
+        range: MessageRange,
+        limit: i64,
+    ) -> QueryResult<Vec<MessageInfo>> {
+        let query = messages::table
+            .inner_join(users::table)
+            .filter(messages::conversation_id.eq(for_conversation))
+            .select((
+                messages::id,
+                messages::conversation_id,
+                messages::author_id,
+                messages::content,
+                messages::created_at,
+                users::username,
+            ))
+            .limit(limit)
+            .into_boxed();
+
+        let mut messages: Vec<MessageInfo> = match range {
+            MessageRange::Latest => query.order(messages::id.desc()).load(c)?,
+            MessageRange::Before(id) => query
+                .filter(messages::id.lt(id))
+                .order(messages::id.desc())
+                .load(c)?,
+            MessageRange::After(id) => {
+                return query
+                    .filter(messages::id.gt(id))
+                    .order(messages::id)
+                    .load(c)
+            }
+        };
+
+        messages.reverse();
+        Ok(messages)
+    }
+
+    /// The newest message in the conversation, or 0 if there are none.
+    pub fn latest_id(c: &mut PgConnection, for_conversation: i64) -> QueryResult<i64> {
+        use crate::db::schema::messages::dsl::*;
+
+        let latest = messages
+            .filter(conversation_id.eq(for_conversation))
+            .select(diesel::dsl::max(id))
+            .first::<Option<i64>>(c)?;
+
+        Ok(latest.unwrap_or(0))
+    }
+
+    /// How many messages the user has sent since the given time.
+    pub fn count_since(
+        c: &PgConnection,
+        for_author: i64,
+        since: DateTime<Utc>,
+    ) -> QueryResult<i64> {
+        use crate::db::schema::messages::dsl::*;
+
+        messages
+            .filter(author_id.eq(for_author))
+            .filter(created_at.gt(since))
+            .count()
+            .get_result(c)
+    }
+}
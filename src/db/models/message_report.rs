@@ -0,0 +1,124 @@
+use super::MessageInfo;
+use crate::db::schema::{message_reports, users};
+use chrono::{DateTime, Utc};
+use diesel::{dsl::now, prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// An open report, with the message it is about as it was when reported, for
+/// moderators.
+#[derive(Queryable, Serialize)]
+pub struct ReportInfo {
+    pub id: i64,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub reporter_id: i64,
+    /// None once the message has been deleted
+    pub message_id: Option<i64>,
+    pub conversation_id: i64,
+    pub author_id: Option<i64>,
+    pub content: String,
+    pub sent_at: DateTime<Utc>,
+    pub author: String,
+}
+
+/// A report along with who made it.
+#[derive(Queryable, Serialize)]
+pub struct OpenReport {
+    #[serde(flatten)]
+    pub report: ReportInfo,
+    pub reporter: String,
+}
+
+pub struct MessageReport;
+
+impl MessageReport {
+    /// Keeps a copy of the message, so the report outlives it. Returns false
+    /// if the user has already reported the message.
+    pub fn create(
+        c: &mut PgConnection,
+        message: &MessageInfo,
+        reporter_id: i64,
+        reason: &str,
+    ) -> QueryResult<bool> {
+        let inserted = diesel::insert_into(message_reports::table)
+            .values((
+                message_reports::message_id.eq(message.id),
+                message_reports::reporter_id.eq(reporter_id),
+                message_reports::reason.eq(reason),
+                message_reports::conversation_id.eq(message.conversation_id),
+                message_reports::author_id.eq(message.author_id),
+                message_reports::author.eq(&message.author),
+                message_reports::content.eq(&message.content),
+                message_reports::sent_at.eq(message.created_at),
+            ))
+            .on_conflict_do_nothing()
+            .execute(c)?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Reports nobody has dealt with yet, oldest first.
+    pub fn open(c: &mut PgConnection, limit: i64, offset: i64) -> QueryResult<Vec<OpenReport>> {
+        message_reports::table
+            .inner_join(users::table)
+            .filter(message_reports::resolved_at.is_null())
+            .select((
+                (
+                    message_reports::id,
+                    message_reports::reason,
+                    message_reports::created_at,
+                    message_reports::reporter_id,
+                    message_reports::message_id,
+                    message_reports::conversation_id,
+                    message_reports::author_id,
+                    message_reports::content,
+                    message_reports::sent_at,
+                    message_reports::author,
+                ),
+                users::username,
+            ))
+            .order(message_reports::id)
+            .limit(limit)
+            .offset(offset)
+            .load(c)
+    }
+
+    pub fn count_open(c: &mut PgConnection) -> QueryResult<i64> {
+        message_reports::table
+            .filter(message_reports::resolved_at.is_null())
+            .count()
+            .get_result(c)
+    }
+
+    /// Resolves every open report of the report's message, since they are
+    /// usually about the same thing. Reports of deleted messages are resolved
+    /// one at a time.
+    pub fn resolve(c: &mut PgConnection, report_id: i64, moderator: i64) -> QueryResult<usize> {
+        let message_id = match message_reports::table
+            .find(report_id)
+            .select(message_reports::message_id)
+            .first::<Option<i64>>(c)
+            .optional()?
+        {
+            Some(message_id) => message_id,
+            None => return Ok(0),
+        };
+
+        let open = message_reports::table.filter(message_reports::resolved_at.is_null());
+        let resolved = (
+            message_reports::resolved_at.eq(now),
+            message_reports::resolved_by.eq(moderator),
+        );
+
+        match message_id {
+            Some(message_id) => {
+                diesel::update(open.filter(message_reports::message_id.eq(message_id)))
+                    .set(resolved)
+                    .execute(c)
+            }
+            None => diesel::update(open.filter(message_reports::id.eq(report_id)))
+                .set(resolved)
+                .execute(c),
+        }
+    }
+}
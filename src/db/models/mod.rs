@@ -1,5 +1,55 @@
+mod contributor_signature;
+mod conversation;
+mod forum_category;
+mod forum_post;
+mod forum_thread;
+mod game_server;
+mod invite;
+mod job;
+mod join_ticket;
+mod login_attempt;
+mod message;
+mod message_report;
+mod notification;
+mod outbox_email;
+mod policy_acceptance;
+mod presence;
+mod release;
+mod release_patch;
+mod search_document;
+mod update_manifest;
 mod user;
+mod user_block;
+mod wiki_page;
+mod wiki_revision;
 mod session;
 
-pub use user::{NewUser, User};
+pub use contributor_signature::{ContributorSignature, NewContributorSignature};
+pub use conversation::{
+    Conversation, ConversationMember, ConversationSummary, MAX_GROUP_MEMBERS,
+};
+pub use forum_category::{CategorySummary, ForumCategory, NewForumCategory};
+pub use forum_post::{ForumPost, PostEdit, PostInfo};
+pub use forum_thread::{ForumThread, NewForumThread, ThreadInfo};
+pub use game_server::{Fullness, GameServer, NewGameServer, ServerFilter, ServerSort};
+pub use invite::{Invite, NewInvite};
+pub use job::{Job, JobStatus, NewJob};
+pub use join_ticket::{JoinTicket, NewJoinTicket};
+pub use login_attempt::{LoginAttempt, NewLoginAttempt};
+pub use message::{Message, MessageInfo, MessageRange};
+pub use message_report::{MessageReport, OpenReport, ReportInfo};
+pub use notification::{
+    Notification, NotificationInfo, NotificationKind, NotificationPayload, NotificationPreference,
+};
+pub use outbox_email::{EmailStatus, NewOutboxEmail, OutboxEmail};
+pub use policy_acceptance::{NewPolicyAcceptance, PolicyAcceptance};
+pub use presence::{Presence, PresenceInfo, PresenceServer, PresenceStatus, PRESENCE_EXPIRY};
+pub use release::{Channel, NewRelease, Platform, Release};
+pub use release_patch::{NewReleasePatch, ReleasePatch};
+pub use search_document::{DocumentKind, NewSearchDocument, SearchDocument, SearchResult};
+pub use update_manifest::{NewUpdateManifest, UpdateManifest};
+pub use user::{NewUser, Role, User};
+pub use user_block::{BlockedUser, UserBlock};
+pub use wiki_page::{WikiEdit, WikiPage, WikiSave};
+pub use wiki_revision::{NewWikiRevision, RevisionInfo, WikiRevision};
 pub use session::{NewSession, Session};
@@ -0,0 +1,319 @@
+use crate::db::schema::{notification_preferences, notifications, users};
+use chrono::{DateTime, Utc};
+use diesel::{dsl::now, prelude::*, result::Error as DieselError, PgConnection};
+use rocket::serde::{
+    json::{self, Value},
+    Deserialize, Serialize,
+};
+
+text_enum! {
+    pub enum NotificationKind {
+        /// Someone replied to a thread the user started
+        ForumReply => "forum_reply",
+        /// Someone quoted one of the user's forum posts
+        ForumQuote => "forum_quote",
+        /// Someone edited a wiki page the user has edited before
+        WikiEdit => "wiki_edit",
+    }
+}
+
+impl NotificationKind {
+    /// Shown next to the kind's checkbox in the user's preferences.
+    pub fn description(&self) -> &'static str {
+        match self {
+            NotificationKind::ForumReply => "Replies to threads I started",
+            NotificationKind::ForumQuote => "Quotes of my forum posts",
+            NotificationKind::WikiEdit => "Edits to wiki pages I've edited",
+        }
+    }
+}
+
+/// What a notification is about. The variant is stored in the `kind`
+/// column and its fields in `payload`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum NotificationPayload {
+    ForumReply {
+        thread_id: i64,
+        thread_title: String,
+        post_id: i64,
+        author: String,
+    },
+    ForumQuote {
+        thread_id: i64,
+        thread_title: String,
+        post_id: i64,
+        author: String,
+    },
+    WikiEdit {
+        slug: String,
+        title: String,
+        revision_id: i64,
+        author: String,
+    },
+}
+
+impl NotificationPayload {
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            NotificationPayload::ForumReply { .. } => NotificationKind::ForumReply,
+            NotificationPayload::ForumQuote { .. } => NotificationKind::ForumQuote,
+            NotificationPayload::WikiEdit { .. } => NotificationKind::WikiEdit,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            NotificationPayload::ForumReply {
+                thread_title,
+                author,
+                ..
+            } => format!("{} replied to your thread “{}”", author, thread_title),
+            NotificationPayload::ForumQuote {
+                thread_title,
+                author,
+                ..
+            } => format!("{} quoted your post in “{}”", author, thread_title),
+            NotificationPayload::WikiEdit { title, author, .. } => {
+                format!("{} edited the wiki page “{}”", author, title)
+            }
+        }
+    }
+
+    /// Where the notification leads to.
+    pub fn url(&self) -> String {
+        match self {
+            NotificationPayload::ForumReply { post_id, .. }
+            | NotificationPayload::ForumQuote { post_id, .. } => {
+                format!("/forums/posts/{}", post_id)
+            }
+            NotificationPayload::WikiEdit {
+                slug, revision_id, ..
+            } => format!("/wiki/{}/revisions/{}", slug, revision_id),
+        }
+    }
+
+    fn from_row(kind: NotificationKind, payload: Value) -> Result<Self, json::serde_json::Error> {
+        json::from_value(json::serde_json::json!({
+            "kind": kind.as_str(),
+            "payload": payload,
+        }))
+    }
+
+    fn to_row(&self) -> Result<(NotificationKind, Value), json::serde_json::Error> {
+        let mut value = json::to_value(self)?;
+        Ok((self.kind(), value["payload"].take()))
+    }
+}
+
+#[derive(Queryable)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: i64,
+    pub kind: NotificationKind,
+    pub payload: Value,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+/// A notification as shown to its user, on the site or in the game client.
+#[derive(Serialize, Clone, Debug)]
+pub struct NotificationInfo {
+    pub id: i64,
+    #[serde(flatten)]
+    pub payload: NotificationPayload,
+    pub message: String,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+    pub read: bool,
+}
+
+impl TryFrom<Notification> for NotificationInfo {
+    type Error = DieselError;
+
+    fn try_from(notification: Notification) -> Result<Self, Self::Error> {
+        let payload = NotificationPayload::from_row(notification.kind, notification.payload)
+            .map_err(|err| DieselError::DeserializationError(Box::new(err)))?;
+
+        Ok(NotificationInfo {
+            id: notification.id,
+            message: payload.message(),
+            url: payload.url(),
+            payload,
+            created_at: notification.created_at,
+            read: notification.read_at.is_some(),
+        })
+    }
+}
+
+fn to_info(rows: Vec<Notification>) -> QueryResult<Vec<NotificationInfo>> {
+    rows.into_iter().map(NotificationInfo::try_from).collect()
+}
+
+impl Notification {
+    /// Notifies a user, unless they have turned off notifications of this
+    /// kind. Returns the notification if one was sent.
+    pub fn send(
+        c: &mut PgConnection,
+        to: i64,
+        payload: &NotificationPayload,
+    ) -> QueryResult<Option<NotificationInfo>> {
+        let (kind, payload) = payload
+            .to_row()
+            .map_err(|err| DieselError::SerializationError(Box::new(err)))?;
+
+        if !NotificationPreference::enabled(c, to, kind)? {
+            return Ok(None);
+        }
+
+        diesel::insert_into(notifications::table)
+            .values((
+                notifications::user_id.eq(to),
+                notifications::kind.eq(kind),
+                notifications::payload.eq(payload),
+            ))
+            .get_result::<Notification>(c)
+            .and_then(NotificationInfo::try_from)
+            .map(Some)
+    }
+
+    /// Newest first.
+    pub fn for_user(
+        c: &mut PgConnection,
+        user_id: i64,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<NotificationInfo>> {
+        let rows = notifications::table
+            .filter(notifications::user_id.eq(user_id))
+            .order(notifications::id.desc())
+            .limit(limit)
+            .offset(offset)
+            .load(c)?;
+
+        to_info(rows)
+    }
+
+    /// Notifications newer than `after_id`, newest first. Used by clients
+    /// which poll for new notifications.
+    pub fn since(
+        c: &mut PgConnection,
+        user_id: i64,
+        after_id: i64,
+        limit: i64,
+    ) -> QueryResult<Vec<NotificationInfo>> {
+        let rows = notifications::table
+            .filter(notifications::user_id.eq(user_id))
+            .filter(notifications::id.gt(after_id))
+            .order(notifications::id.desc())
+            .limit(limit)
+            .load(c)?;
+
+        to_info(rows)
+    }
+
+    pub fn find(
+        c: &mut PgConnection,
+        user_id: i64,
+        notification_id: i64,
+    ) -> QueryResult<Option<NotificationInfo>> {
+        notifications::table
+            .filter(notifications::user_id.eq(user_id))
+            .find(notification_id)
+            .first::<Notification>(c)
+            .optional()?
+            .map(NotificationInfo::try_from)
+            .transpose()
+    }
+
+    pub fn count_for_user(c: &mut PgConnection, user_id: i64) -> QueryResult<i64> {
+        notifications::table
+            .filter(notifications::user_id.eq(user_id))
+            .count()
+            .get_result(c)
+    }
+
+    /// Read from the count the database keeps on the user.
+    pub fn unread_count(c: &mut PgConnection, user_id: i64) -> QueryResult<i64> {
+        users::table
+            .find(user_id)
+            .select(users::unread_notifications)
+            .get_result(c)
+    }
+
+    pub fn mark_read(c: &mut PgConnection, user_id: i64, ids: &[i64]) -> QueryResult<usize> {
+        diesel::update(
+            notifications::table
+                .filter(notifications::user_id.eq(user_id))
+                .filter(notifications::id.eq_any(ids))
+                .filter(notifications::read_at.is_null()),
+        )
+        .set(notifications::read_at.eq(now))
+        .execute(c)
+    }
+
+    pub fn mark_all_read(c: &mut PgConnection, user_id: i64) -> QueryResult<usize> {
+        diesel::update(
+            notifications::table
+                .filter(notifications::user_id.eq(user_id))
+                .filter(notifications::read_at.is_null()),
+        )
+        .set(notifications::read_at.eq(now))
+        .execute(c)
+    }
+}
+
+pub struct NotificationPreference;
+
+impl NotificationPreference {
+    /// Every kind is enabled until the user turns it off.
+    pub fn enabled(
+        c: &mut PgConnection,
+        for_user: i64,
+        of_kind: NotificationKind,
+    ) -> QueryResult<bool> {
+        use crate::db::schema::notification_preferences::dsl::*;
+
+        let preference = notification_preferences
+            .find((for_user, of_kind))
+            .select(enabled)
+            .first::<bool>(c)
+            .optional()?;
+
+        Ok(preference.unwrap_or(true))
+    }
+
+    /// The kinds the user has turned off.
+    pub fn disabled(c: &mut PgConnection, for_user: i64) -> QueryResult<Vec<NotificationKind>> {
+        use crate::db::schema::notification_preferences::dsl::*;
+
+        notification_preferences
+            .filter(user_id.eq(for_user))
+            .filter(enabled.eq(false))
+            .select(kind)
+            .load(c)
+    }
+
+    pub fn set(
+        c: &mut PgConnection,
+        for_user: i64,
+        of_kind: NotificationKind,
+        value: bool,
+    ) -> QueryResult<()> {
+        diesel::insert_into(notification_preferences::table)
+            .values((
+                notification_preferences::user_id.eq(for_user),
+                notification_preferences::kind.eq(of_kind),
+                notification_preferences::enabled.eq(value),
+            ))
+            .on_conflict((
+                notification_preferences::user_id,
+                notification_preferences::kind,
+            ))
+            .do_update()
+            .set(notification_preferences::enabled.eq(value))
+            .execute(c)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::db::schema::email_outbox;
+use chrono::{DateTime, Duration, Utc};
+use diesel::{dsl::now, prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+text_enum! {
+    pub enum EmailStatus {
+        /// Waiting for its first or next attempt
+        Pending => "pending",
+        Sent => "sent",
+        /// Out of attempts
+        Failed => "failed",
+    }
+}
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct OutboxEmail {
+    pub id: i64,
+    pub recipient: String,
+    pub subject: String,
+    pub template: String,
+    pub html_body: String,
+    pub text_body: String,
+    pub status: EmailStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
+
+/// A rendered email, ready for the outbox.
+#[derive(Insertable)]
+#[table_name = "email_outbox"]
+pub struct NewOutboxEmail {
+    pub recipient: String,
+    pub subject: String,
+    pub template: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+impl OutboxEmail {
+    pub fn queue(c: &mut PgConnection, email: &NewOutboxEmail) -> QueryResult<i64> {
+        diesel::insert_into(email_outbox::table)
+            .values(email)
+            .returning(email_outbox::id)
+            .get_result(c)
+    }
+
+    /// Takes up to `limit` emails which are due, and holds them for `lease`
+    /// so other workers leave them alone while they're being sent. Emails
+    /// held by a worker that died are picked up again once the lease is up.
+    pub fn claim_due(
+        c: &mut PgConnection,
+        limit: i64,
+        lease: Duration,
+    ) -> QueryResult<Vec<OutboxEmail>> {
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            let due = email_outbox::table
+                .filter(email_outbox::status.eq(EmailStatus::Pending))
+                .filter(email_outbox::next_attempt_at.le(now))
+                .order(email_outbox::next_attempt_at)
+                .limit(limit)
+                .for_update()
+                .skip_locked()
+                .load::<OutboxEmail>(c)?;
+
+            let ids: Vec<i64> = due.iter().map(|email| email.id).collect();
+
+            diesel::update(email_outbox::table.filter(email_outbox::id.eq_any(&ids)))
+                .set(email_outbox::next_attempt_at.eq(Utc::now() + lease))
+                .execute(c)?;
+
+            Ok(due)
+        })
+    }
+
+    pub fn mark_sent(c: &mut PgConnection, email_id: i64) -> QueryResult<()> {
+        diesel::update(email_outbox::table.find(email_id))
+            .set((
+                email_outbox::status.eq(EmailStatus::Sent),
+                email_outbox::attempts.eq(email_outbox::attempts + 1),
+                email_outbox::sent_at.eq(now),
+                email_outbox::last_error.eq(None::<String>),
+            ))
+            .execute(c)?;
+
+        Ok(())
+    }
+
+    /// Records a failed attempt. The email is tried again at `retry_at`, or
+    /// given up on if there isn't one.
+    pub fn mark_attempt_failed(
+        c: &mut PgConnection,
+        email_id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> QueryResult<()> {
+        let status = match retry_at {
+            Some(_) => EmailStatus::Pending,
+            None => EmailStatus::Failed,
+        };
+
+        diesel::update(email_outbox::table.find(email_id))
+            .set((
+                email_outbox::status.eq(status),
+                email_outbox::attempts.eq(email_outbox::attempts + 1),
+                email_outbox::next_attempt_at.eq(retry_at.unwrap_or_else(Utc::now)),
+                email_outbox::last_error.eq(error),
+            ))
+            .execute(c)?;
+
+        Ok(())
+    }
+
+    /// Gives a failed email a fresh set of attempts, starting now.
+    pub fn retry(c: &mut PgConnection, email_id: i64) -> QueryResult<bool> {
+        let updated = diesel::update(
+            email_outbox::table
+                .find(email_id)
+                .filter(email_outbox::status.eq(EmailStatus::Failed)),
+        )
+        .set((
+            email_outbox::status.eq(EmailStatus::Pending),
+            email_outbox::attempts.eq(0),
+            email_outbox::next_attempt_at.eq(now),
+        ))
+        .execute(c)?;
+
+        Ok(updated > 0)
+    }
+
+    /// Newest first.
+    pub fn recent(c: &mut PgConnection, limit: i64) -> QueryResult<Vec<OutboxEmail>> {
+        email_outbox::table
+            .order(email_outbox::id.desc())
+            .limit(limit)
+            .load(c)
+    }
+
+    pub fn count_by_status(c: &mut PgConnection) -> QueryResult<Vec<(EmailStatus, i64)>> {
+        EmailStatus::ALL
+            .iter()
+            .map(|&status| {
+                let count = email_outbox::table
+                    .filter(email_outbox::status.eq(status))
+                    .count()
+                    .get_result(c)?;
+
+                Ok((status, count))
+            })
+            .collect()
+    }
+}
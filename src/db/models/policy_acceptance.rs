@@ -0,0 +1,41 @@
+use crate::db::schema::policy_acceptances;
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// A user's agreement to one version of a policy document.
+#[derive(Queryable, Serialize)]
+pub struct PolicyAcceptance {
+    pub id: i64,
+    pub user_id: i64,
+    pub policy: String,
+    pub version: String,
+    pub accepted_at: DateTime<Utc>,
+}
+
+impl PolicyAcceptance {
+    pub fn for_user(c: &mut PgConnection, for_user: i64) -> QueryResult<Vec<PolicyAcceptance>> {
+        use crate::db::schema::policy_acceptances::dsl::*;
+
+        policy_acceptances
+            .filter(user_id.eq(for_user))
+            .order((accepted_at.desc(), id.desc()))
+            .load(c)
+    }
+
+    /// Records acceptances, ignoring versions which were already accepted.
+    pub fn accept(c: &PgConnection, acceptances: &[NewPolicyAcceptance]) -> QueryResult<usize> {
+        diesel::insert_into(policy_acceptances::table)
+            .values(acceptances)
+            .on_conflict_do_nothing()
+            .execute(c)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "policy_acceptances"]
+pub struct NewPolicyAcceptance<'a> {
+    pub user_id: i64,
+    pub policy: &'a str,
+    pub version: &'a str,
+}
@@ -0,0 +1,202 @@
+use crate::db::{
+    models::User,
+    schema::{game_servers, presences, users},
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+pub const PRESENCE_EXPIRY: i64 = 120; // seconds
+
+#[derive(Queryable)]
+pub struct Presence {
+    pub user_id: i64,
+    pub server_id: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[table_name = "presences"]
+struct NewPresence {
+    user_id: i64,
+    server_id: Option<i64>,
+    updated_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PresenceStatus {
+    Online,
+    InGame,
+    Offline,
+    /// The user has chosen not to share their presence
+    Hidden,
+}
+
+#[derive(Serialize)]
+pub struct PresenceServer {
+    pub id: i64,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct PresenceInfo {
+    pub status: PresenceStatus,
+    pub server: Option<PresenceServer>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+}
+
+impl Presence {
+    fn seen(c: &PgConnection, user_id: i64, now: DateTime<Utc>) -> QueryResult<()> {
+        diesel::update(users::table.find(user_id))
+            .set(users::last_seen_at.eq(now))
+            .execute(c)?;
+
+        Ok(())
+    }
+
+    /// Marks a user as online without changing which server they are in.
+    pub fn touch(c: &mut PgConnection, user_id: i64) -> QueryResult<()> {
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(PRESENCE_EXPIRY);
+
+        let c = &*c;
+
+        c.transaction(|| {
+            diesel::insert_into(presences::table)
+                .values(&NewPresence {
+                    user_id,
+                    server_id: None,
+                    updated_at: now,
+                    expires_at,
+                })
+                .on_conflict(presences::user_id)
+                .do_update()
+                .set((
+                    presences::updated_at.eq(now),
+                    presences::expires_at.eq(expires_at),
+                ))
+                .execute(c)?;
+
+            Self::seen(c, user_id, now)
+        })
+    }
+
+    pub fn join_server(c: &PgConnection, user_id: i64, server_id: i64) -> QueryResult<()> {
+        let now = Utc::now();
+        let expires_at = now + Duration::seconds(PRESENCE_EXPIRY);
+
+        c.transaction(|| {
+            diesel::insert_into(presences::table)
+                .values(&NewPresence {
+                    user_id,
+                    server_id: Some(server_id),
+                    updated_at: now,
+                    expires_at,
+                })
+                .on_conflict(presences::user_id)
+                .do_update()
+                .set((
+                    presences::server_id.eq(server_id),
+                    presences::updated_at.eq(now),
+                    presences::expires_at.eq(expires_at),
+                ))
+                .execute(c)?;
+
+            Self::seen(c, user_id, now)
+        })
+    }
+
+    /// Only clears the server if the user has not since moved to another one.
+    pub fn leave_server(c: &PgConnection, user_id: i64, server_id: i64) -> QueryResult<()> {
+        diesel::update(
+            presences::table.filter(
+                presences::user_id
+                    .eq(user_id)
+                    .and(presences::server_id.eq(server_id)),
+            ),
+        )
+        .set(presences::server_id.eq(None::<i64>))
+        .execute(c)?;
+
+        Ok(())
+    }
+
+    /// Keeps the presence of everyone in a server alive, called on heartbeat.
+    pub fn refresh_server(c: &mut PgConnection, server_id: i64) -> QueryResult<()> {
+        let now = Utc::now();
+
+        diesel::update(presences::table.filter(presences::server_id.eq(server_id)))
+            .set((
+                presences::updated_at.eq(now),
+                presences::expires_at.eq(now + Duration::seconds(PRESENCE_EXPIRY)),
+            ))
+            .execute(c)?;
+
+        let in_server = presences::table
+            .filter(presences::server_id.eq(server_id))
+            .select(presences::user_id);
+
+        diesel::update(users::table.filter(users::id.eq_any(in_server)))
+            .set(users::last_seen_at.eq(now))
+            .execute(c)?;
+
+        Ok(())
+    }
+
+    pub fn clear_server(c: &mut PgConnection, server_id: i64) -> QueryResult<()> {
+        diesel::update(presences::table.filter(presences::server_id.eq(server_id)))
+            .set(presences::server_id.eq(None::<i64>))
+            .execute(c)?;
+
+        Ok(())
+    }
+
+    pub fn purge_expired(c: &mut PgConnection) -> QueryResult<usize> {
+        diesel::delete(presences::table.filter(presences::expires_at.lt(Utc::now()))).execute(c)
+    }
+
+    /// Presence of `user` as seen by the user with ID `viewer_id`,
+    /// respecting the user's privacy settings.
+    pub fn info(
+        c: &PgConnection,
+        user: &User,
+        viewer_id: Option<i64>,
+    ) -> QueryResult<PresenceInfo> {
+        if !user.show_presence && viewer_id != Some(user.id) {
+            return Ok(PresenceInfo {
+                status: PresenceStatus::Hidden,
+                server: None,
+                last_seen_at: None,
+            });
+        }
+
+        let presence = presences::table
+            .find(user.id)
+            .filter(presences::expires_at.gt(Utc::now()))
+            .left_join(game_servers::table)
+            .select((
+                presences::server_id,
+                (game_servers::id, game_servers::name).nullable(),
+            ))
+            .first::<(Option<i64>, Option<(i64, String)>)>(c)
+            .optional()?;
+
+        let (status, server) = match presence {
+            Some((_, Some((id, name)))) => (
+                PresenceStatus::InGame,
+                Some(PresenceServer { id, name }),
+            ),
+            Some(_) => (PresenceStatus::Online, None),
+            None => (PresenceStatus::Offline, None),
+        };
+
+        Ok(PresenceInfo {
+            status,
+            server,
+            last_seen_at: user.last_seen_at,
+        })
+    }
+}
@@ -0,0 +1,200 @@
+use super::{Role, User};
+use crate::{db::schema::releases, util};
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::{Serialize, Serializer};
+
+text_enum! {
+    #[derive(FromFormField)]
+    pub enum Channel {
+        Stable => "stable",
+        Beta => "beta",
+        Nightly => "nightly",
+    }
+}
+
+text_enum! {
+    #[derive(FromFormField)]
+    pub enum Platform {
+        Windows => "windows",
+        MacOs => "macos",
+        Linux => "linux",
+    }
+}
+
+impl Channel {
+    /// Whether a visitor may see builds and release notes in this channel.
+    pub fn visible_to(&self, user: Option<&User>) -> bool {
+        match self {
+            Channel::Stable => true,
+            // Anyone can opt into beta from their account settings
+            Channel::Beta => user.is_some_and(|u| u.beta_opt_in || u.has_role(Role::Tester)),
+            Channel::Nightly => user.is_some_and(|u| u.has_role(Role::Tester)),
+        }
+    }
+
+    pub fn visible(user: Option<&User>) -> Vec<Channel> {
+        Channel::ALL
+            .iter()
+            .copied()
+            .filter(|c| c.visible_to(user))
+            .collect()
+    }
+}
+
+impl Platform {
+    pub fn from_user_agent(user_agent: &str) -> Option<Platform> {
+        // Mobile devices also claim to be "like Mac OS X" or Linux
+        if user_agent.contains("Android")
+            || user_agent.contains("iPhone")
+            || user_agent.contains("iPad")
+        {
+            return None;
+        }
+
+        if user_agent.contains("Windows") {
+            Some(Platform::Windows)
+        } else if user_agent.contains("Macintosh") || user_agent.contains("Mac OS X") {
+            Some(Platform::MacOs)
+        } else if user_agent.contains("Linux") || user_agent.contains("X11") {
+            Some(Platform::Linux)
+        } else {
+            None
+        }
+    }
+}
+
+pub(super) fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&util::to_hex(bytes))
+}
+
+#[derive(Queryable, Serialize)]
+pub struct Release {
+    pub id: i64,
+    pub version: String,
+    pub channel: Channel,
+    pub platform: Platform,
+    pub file_url: String,
+    pub size: i64,
+    #[serde(serialize_with = "serialize_hex")]
+    pub sha256: Vec<u8>,
+    pub signature: Option<String>,
+    pub release_date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Release {
+    pub fn all(c: &mut PgConnection) -> QueryResult<Vec<Release>> {
+        use crate::db::schema::releases::dsl::*;
+
+        releases.order((release_date.desc(), id.desc())).load(c)
+    }
+
+    /// The newest release for each platform in a channel.
+    pub fn latest(c: &mut PgConnection, for_channel: Channel) -> QueryResult<Vec<Release>> {
+        use crate::db::schema::releases::dsl::*;
+
+        let mut latest: Vec<Release> = Vec::new();
+
+        for release in releases
+            .filter(channel.eq(for_channel))
+            .order((release_date.desc(), id.desc()))
+            .load::<Release>(c)?
+        {
+            if !latest.iter().any(|r| r.platform == release.platform) {
+                latest.push(release);
+            }
+        }
+
+        latest.sort_by_key(|r| r.platform);
+
+        Ok(latest)
+    }
+
+    /// The channel of the release or patch stored at `url`, if any.
+    pub fn channel_for_file(c: &mut PgConnection, url: &str) -> QueryResult<Option<Channel>> {
+        use crate::db::schema::{release_patches, releases::dsl::*};
+
+        let release_channel = releases
+            .filter(file_url.eq(url))
+            .select(channel)
+            .first::<Channel>(c)
+            .optional()?;
+
+        if release_channel.is_some() {
+            return Ok(release_channel);
+        }
+
+        release_patches::table
+            .inner_join(releases)
+            .filter(release_patches::file_url.eq(url))
+            .select(channel)
+            .first::<Channel>(c)
+            .optional()
+    }
+
+    pub fn find(
+        c: &mut PgConnection,
+        find_version: &str,
+        find_channel: Channel,
+        find_platform: Platform,
+    ) -> QueryResult<Release> {
+        use crate::db::schema::releases::dsl::*;
+
+        releases
+            .filter(version.eq(find_version))
+            .filter(channel.eq(find_channel))
+            .filter(platform.eq(find_platform))
+            .first(c)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "releases"]
+pub struct NewRelease<'a> {
+    pub version: &'a str,
+    pub channel: Channel,
+    pub platform: Platform,
+    pub file_url: &'a str,
+    pub size: i64,
+    pub sha256: &'a [u8],
+    pub signature: Option<&'a str>,
+    pub release_date: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(role: Role, beta_opt_in: bool) -> User {
+        User {
+            id: 1,
+            username: "cirno".to_string(),
+            password: String::new(),
+            created_at: Utc::now(),
+            banned: false,
+            last_seen_at: None,
+            show_presence: true,
+            role,
+            beta_opt_in,
+            unread_notifications: 0,
+        }
+    }
+
+    #[test]
+    fn channels_are_gated_by_role_or_opt_in() {
+        let plain = user(Role::User, false);
+        let opted_in = user(Role::User, true);
+        let tester = user(Role::Tester, false);
+        let admin = user(Role::Admin, false);
+
+        assert_eq!(Channel::visible(None), [Channel::Stable]);
+        assert_eq!(Channel::visible(Some(&plain)), [Channel::Stable]);
+        assert_eq!(
+            Channel::visible(Some(&opted_in)),
+            [Channel::Stable, Channel::Beta]
+        );
+        assert_eq!(Channel::visible(Some(&tester)), Channel::ALL);
+        assert_eq!(Channel::visible(Some(&admin)), Channel::ALL);
+    }
+}
@@ -0,0 +1,48 @@
+use super::{release::serialize_hex, Release};
+use crate::db::schema::release_patches;
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// A delta which upgrades an install of `from_version` to its release.
+#[derive(Queryable, Serialize)]
+pub struct ReleasePatch {
+    pub id: i64,
+    pub release_id: i64,
+    pub from_version: String,
+    pub file_url: String,
+    pub size: i64,
+    #[serde(serialize_with = "serialize_hex")]
+    pub sha256: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ReleasePatch {
+    pub fn all(c: &mut PgConnection) -> QueryResult<Vec<(ReleasePatch, Release)>> {
+        use crate::db::schema::{release_patches::dsl::*, releases};
+
+        release_patches
+            .inner_join(releases::table)
+            .order((created_at.desc(), id.desc()))
+            .load(c)
+    }
+
+    pub fn for_release(c: &mut PgConnection, for_release: i64) -> QueryResult<Vec<ReleasePatch>> {
+        use crate::db::schema::release_patches::dsl::*;
+
+        release_patches
+            .filter(release_id.eq(for_release))
+            .order(from_version.asc())
+            .load(c)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "release_patches"]
+pub struct NewReleasePatch<'a> {
+    pub release_id: i64,
+    pub from_version: &'a str,
+    pub file_url: &'a str,
+    pub size: i64,
+    pub sha256: &'a [u8],
+}
@@ -0,0 +1,160 @@
+use super::Channel;
+use crate::db::schema::search_documents;
+use diesel::{
+    prelude::*,
+    sql_types::{Array, BigInt, Text},
+    PgConnection,
+};
+use rocket::serde::Serialize;
+
+text_enum! {
+    pub enum DocumentKind {
+        Page => "page",
+        News => "news",
+        Changelog => "changelog",
+        Wiki => "wiki",
+    }
+}
+
+/// Options for `ts_headline`. Matches are wrapped in private use characters,
+/// which are swapped for tags once the rest of the snippet has been escaped.
+const HEADLINE_OPTIONS: &str =
+    "StartSel=\u{E000}, StopSel=\u{E001}, MaxFragments=2, MaxWords=25, MinWords=10, FragmentDelimiter=\" … \"";
+
+/// Matches `query` against the search index, limited to documents
+/// without a channel or in one of `channels`.
+const SEARCH_FROM: &str = "
+    FROM search_documents, websearch_to_tsquery('english', $1) query
+    WHERE search @@ query AND (channel IS NULL OR channel = ANY($2))";
+
+/// A page, news post or set of release notes in the search index.
+#[derive(Queryable)]
+pub struct SearchDocument {
+    pub id: i64,
+    pub kind: DocumentKind,
+    pub url: String,
+    pub title: String,
+    pub headings: String,
+    pub body: String,
+    pub channel: Option<Channel>,
+}
+
+#[derive(QueryableByName, Serialize)]
+pub struct SearchResult {
+    #[sql_type = "Text"]
+    pub kind: DocumentKind,
+    #[sql_type = "Text"]
+    pub url: String,
+    #[sql_type = "Text"]
+    pub title: String,
+    /// Escaped HTML, with matches wrapped in `<mark>`
+    #[sql_type = "Text"]
+    pub snippet: String,
+}
+
+#[derive(QueryableByName)]
+struct SearchCount {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+impl SearchDocument {
+    /// Replaces every document of the given kinds, in one transaction so
+    /// searches never see a half built index.
+    pub fn replace_kinds(
+        c: &mut PgConnection,
+        kinds: &[DocumentKind],
+        documents: &[NewSearchDocument],
+    ) -> QueryResult<()> {
+        use crate::db::schema::search_documents::dsl::*;
+
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            // Another instance of the site may be reindexing at the same time
+            diesel::sql_query("LOCK TABLE search_documents IN SHARE ROW EXCLUSIVE MODE")
+                .execute(c)?;
+            diesel::delete(search_documents.filter(kind.eq_any(kinds))).execute(c)?;
+            diesel::insert_into(search_documents)
+                .values(documents)
+                .execute(c)?;
+
+            Ok(())
+        })
+    }
+
+    /// Adds a document, or replaces the one already at its URL.
+    pub fn upsert(c: &mut PgConnection, document: &NewSearchDocument) -> QueryResult<()> {
+        use crate::db::schema::search_documents::dsl::*;
+
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            diesel::delete(search_documents.filter(url.eq(&document.url))).execute(c)?;
+            diesel::insert_into(search_documents)
+                .values(document)
+                .execute(c)?;
+
+            Ok(())
+        })
+    }
+
+    /// Best matches first.
+    pub fn search(
+        c: &mut PgConnection,
+        query: &str,
+        channels: &[Channel],
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<SearchResult>> {
+        let sql = format!(
+            "SELECT kind, url, title, ts_headline('english', body, query, $3) AS snippet
+            {}
+            ORDER BY ts_rank(search, query) DESC, title
+            LIMIT $4 OFFSET $5",
+            SEARCH_FROM
+        );
+
+        let results: Vec<SearchResult> = diesel::sql_query(sql)
+            .bind::<Text, _>(query)
+            .bind::<Array<Text>, _>(channel_names(channels))
+            .bind::<Text, _>(HEADLINE_OPTIONS)
+            .bind::<BigInt, _>(limit)
+            .bind::<BigInt, _>(offset)
+            .load(c)?;
+
+        Ok(results
+            .into_iter()
+            .map(|result| SearchResult {
+                snippet: crate::util::escape_html(&result.snippet)
+                    .replace('\u{E000}', "<mark>")
+                    .replace('\u{E001}', "</mark>"),
+                ..result
+            })
+            .collect())
+    }
+
+    pub fn count(c: &mut PgConnection, query: &str, channels: &[Channel]) -> QueryResult<i64> {
+        let result: SearchCount = diesel::sql_query(format!("SELECT COUNT(*) AS count {}", SEARCH_FROM))
+            .bind::<Text, _>(query)
+            .bind::<Array<Text>, _>(channel_names(channels))
+            .get_result(c)?;
+
+        Ok(result.count)
+    }
+}
+
+fn channel_names(channels: &[Channel]) -> Vec<&'static str> {
+    channels.iter().map(Channel::as_str).collect()
+}
+
+#[derive(Insertable, Clone)]
+#[table_name = "search_documents"]
+pub struct NewSearchDocument {
+    pub kind: DocumentKind,
+    pub url: String,
+    pub title: String,
+    pub headings: String,
+    pub body: String,
+    pub channel: Option<Channel>,
+}
@@ -1,5 +1,6 @@
 use crate::db::schema::sessions;
 use chrono::{DateTime, Utc, Duration};
+use diesel::{prelude::*, PgConnection};
 
 #[derive(Queryable)]
 pub struct Session {
@@ -12,6 +13,11 @@ pub struct Session {
 }
 
 impl Session {
+    /// Signs a user out everywhere, returning how many sessions they had.
+    pub fn revoke_all(c: &mut PgConnection, for_user: i64) -> QueryResult<usize> {
+        diesel::delete(sessions::table.filter(sessions::user_id.eq(for_user))).execute(c)
+    }
+
     pub fn since_last_modify(&self) -> Duration {
         Utc::now() - self.last_modify()
     }
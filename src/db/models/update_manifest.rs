@@ -0,0 +1,44 @@
+use super::{Channel, Platform};
+use crate::db::schema::update_manifests;
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+
+/// A signed update manifest, stored exactly as it was signed.
+#[derive(Queryable)]
+pub struct UpdateManifest {
+    pub id: i64,
+    pub channel: Channel,
+    pub platform: Platform,
+    pub key_id: String,
+    pub manifest: String,
+    pub signature: String,
+    pub issued_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl UpdateManifest {
+    pub fn latest(
+        c: &mut PgConnection,
+        for_channel: Channel,
+        for_platform: Platform,
+    ) -> QueryResult<Option<UpdateManifest>> {
+        use crate::db::schema::update_manifests::dsl::*;
+
+        update_manifests
+            .filter(channel.eq(for_channel).and(platform.eq(for_platform)))
+            .order((issued_at.desc(), id.desc()))
+            .first(c)
+            .optional()
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "update_manifests"]
+pub struct NewUpdateManifest<'a> {
+    pub channel: Channel,
+    pub platform: Platform,
+    pub key_id: &'a str,
+    pub manifest: &'a str,
+    pub signature: &'a str,
+    pub issued_at: DateTime<Utc>,
+}
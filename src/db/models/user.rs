@@ -4,12 +4,30 @@ use chrono::{DateTime, Utc};
 use diesel::{prelude::*, result::Error, PgConnection};
 use rocket::serde::Serialize;
 
-#[derive(Queryable, Serialize)]
+text_enum! {
+    /// Roles are ordered; each includes the permissions of those before it.
+    pub enum Role {
+        User => "user",
+        /// Has access to every release channel
+        Tester => "tester",
+        Moderator => "moderator",
+        Admin => "admin",
+    }
+}
+
+#[derive(Queryable, Serialize, Clone)]
 pub struct User {
     pub id: i64,
     pub username: String,
     pub password: String,
     pub created_at: DateTime<Utc>,
+    pub banned: bool,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub show_presence: bool,
+    pub role: Role,
+    pub beta_opt_in: bool,
+    /// Kept up to date by the database. Shown as a badge in the nav.
+    pub unread_notifications: i64,
 }
 
 impl User {
@@ -21,6 +39,44 @@ impl User {
             .first(c)
     }
 
+    pub fn find_by_id(c: &PgConnection, user_id: i64) -> Result<User, Error> {
+        use crate::db::schema::users::dsl::users;
+
+        users.find(user_id).first(c)
+    }
+
+    /// Holds the user's row until the transaction ends, so that checks made
+    /// for them run one at a time. Rows referring to the user can still be
+    /// added meanwhile.
+    pub fn lock(c: &PgConnection, user_id: i64) -> Result<(), Error> {
+        use crate::db::schema::users::dsl::{id, users};
+
+        users
+            .find(user_id)
+            .select(id)
+            .for_no_key_update()
+            .execute(c)?;
+        Ok(())
+    }
+
+    /// Oldest first, optionally only those with at least the given role.
+    pub fn list(c: &mut PgConnection, min_role: Option<Role>) -> Result<Vec<User>, Error> {
+        use crate::db::schema::users::dsl::{id, role, users};
+
+        let mut query = users.into_boxed();
+
+        if let Some(min_role) = min_role {
+            let roles: Vec<Role> = Role::ALL.iter().copied().filter(|r| *r >= min_role).collect();
+            query = query.filter(role.eq_any(roles));
+        }
+
+        query.order(id).load(c)
+    }
+
+    pub fn has_role(&self, role: Role) -> bool {
+        self.role >= role
+    }
+
     pub fn verify_password(&self, argon: &Argon2, password: &str) -> Result<(), ArgonError> {
         let db_hash = PasswordHash::new(&self.password)?;
 
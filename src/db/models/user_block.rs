@@ -0,0 +1,74 @@
+use crate::db::schema::{user_blocks, users};
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// Someone the user has blocked, for listing their blocks.
+#[derive(Queryable, Serialize)]
+pub struct BlockedUser {
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+pub struct UserBlock;
+
+impl UserBlock {
+    pub fn block(c: &mut PgConnection, blocker: i64, blocked: i64) -> QueryResult<()> {
+        diesel::insert_into(user_blocks::table)
+            .values((
+                user_blocks::blocker_id.eq(blocker),
+                user_blocks::blocked_id.eq(blocked),
+            ))
+            .on_conflict_do_nothing()
+            .execute(c)?;
+
+        Ok(())
+    }
+
+    pub fn unblock(c: &mut PgConnection, blocker: i64, blocked: i64) -> QueryResult<()> {
+        diesel::delete(user_blocks::table.find((blocker, blocked))).execute(c)?;
+        Ok(())
+    }
+
+    pub fn has_blocked(c: &PgConnection, blocker: i64, blocked: i64) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(
+            user_blocks::table.find((blocker, blocked)),
+        ))
+        .get_result(c)
+    }
+
+    /// Whether the user has blocked, or been blocked by, any of the others.
+    pub fn any_between(c: &PgConnection, user: i64, others: &[i64]) -> QueryResult<bool> {
+        use crate::db::schema::user_blocks::dsl::*;
+
+        diesel::select(diesel::dsl::exists(
+            user_blocks.filter(
+                blocker_id
+                    .eq(user)
+                    .and(blocked_id.eq_any(others))
+                    .or(blocked_id.eq(user).and(blocker_id.eq_any(others))),
+            ),
+        ))
+        .get_result(c)
+    }
+
+    /// The IDs of everyone the user has blocked.
+    pub fn blocked_by(c: &mut PgConnection, blocker: i64) -> QueryResult<Vec<i64>> {
+        use crate::db::schema::user_blocks::dsl::*;
+
+        user_blocks
+            .filter(blocker_id.eq(blocker))
+            .select(blocked_id)
+            .load(c)
+    }
+
+    /// Everyone the user has blocked, most recent first.
+    pub fn list(c: &mut PgConnection, blocker: i64) -> QueryResult<Vec<BlockedUser>> {
+        user_blocks::table
+            .inner_join(users::table.on(users::id.eq(user_blocks::blocked_id)))
+            .filter(user_blocks::blocker_id.eq(blocker))
+            .select((users::username, user_blocks::created_at))
+            .order(user_blocks::created_at.desc())
+            .load(c)
+    }
+}
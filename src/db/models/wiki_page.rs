@@ -0,0 +1,159 @@
+use super::{NewWikiRevision, Role, User, WikiRevision};
+use crate::db::schema::wiki_revisions;
+use chrono::{DateTime, Utc};
+use diesel::{dsl::now, prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// A wiki page. Its content lives in its revisions, the latest of which is
+/// the current version; the title is copied here for listings.
+#[derive(Queryable, Serialize)]
+pub struct WikiPage {
+    pub id: i64,
+    pub slug: String,
+    pub title: String,
+    pub protection: Role,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A change to a page, to be saved as its next revision.
+pub struct WikiEdit<'a> {
+    pub author_id: i64,
+    /// Checked against the page's protection once it is locked
+    pub author_role: Role,
+    pub title: &'a str,
+    pub content: &'a str,
+    pub summary: &'a str,
+    /// The earlier revision being restored, if this is a revert
+    pub reverted_from: Option<i64>,
+}
+
+pub enum WikiSave {
+    Saved(WikiRevision),
+    /// Someone else saved a revision first
+    Conflict,
+    /// The revision is the same as the latest one
+    Unchanged,
+    /// The page was protected above the author before it was locked
+    Forbidden,
+}
+
+impl WikiPage {
+    pub fn find(c: &mut PgConnection, for_slug: &str) -> QueryResult<Option<WikiPage>> {
+        use crate::db::schema::wiki_pages::dsl::*;
+
+        wiki_pages.filter(slug.eq(for_slug)).first(c).optional()
+    }
+
+    /// Sorted by title.
+    pub fn all(c: &mut PgConnection) -> QueryResult<Vec<WikiPage>> {
+        use crate::db::schema::wiki_pages::dsl::*;
+
+        wiki_pages.order((title, slug)).load(c)
+    }
+
+    /// Whether a user may edit the page or revert it.
+    pub fn editable_by(&self, user: Option<&User>) -> bool {
+        user.is_some_and(|u| !u.banned && u.has_role(self.protection))
+    }
+
+    pub fn latest_revision(&self, c: &mut PgConnection) -> QueryResult<WikiRevision> {
+        use crate::db::schema::wiki_revisions::dsl::*;
+
+        wiki_revisions
+            .filter(page_id.eq(self.id))
+            .order(id.desc())
+            .first(c)
+    }
+
+    /// Adds a revision to the page at `for_slug`, creating the page if it
+    /// doesn't exist yet. `base_revision` is the latest revision the author
+    /// saw, and nothing is saved if another has been added since.
+    pub fn save(
+        c: &mut PgConnection,
+        for_slug: &str,
+        edit: WikiEdit,
+        base_revision: Option<i64>,
+    ) -> QueryResult<WikiSave> {
+        use crate::db::schema::wiki_pages::dsl::*;
+
+        let c: &PgConnection = c;
+
+        c.transaction(|| {
+            // Locking the page keeps concurrent edits from both passing the
+            // check, and protection from changing until the edit is saved
+            let locked: Option<(i64, Role)> = wiki_pages
+                .filter(slug.eq(for_slug))
+                .select((id, protection))
+                .for_update()
+                .first(c)
+                .optional()?;
+
+            if locked.is_some_and(|(_, level)| edit.author_role < level) {
+                return Ok(WikiSave::Forbidden);
+            }
+
+            let existing = locked.map(|(existing, _)| existing);
+
+            let latest: Option<WikiRevision> = match existing {
+                Some(existing) => Some(
+                    wiki_revisions::table
+                        .filter(wiki_revisions::page_id.eq(existing))
+                        .order(wiki_revisions::id.desc())
+                        .first(c)?,
+                ),
+                None => None,
+            };
+
+            if latest.as_ref().map(|r| r.id) != base_revision {
+                return Ok(WikiSave::Conflict);
+            }
+
+            if latest.is_some_and(|r| r.title == edit.title && r.content == edit.content) {
+                return Ok(WikiSave::Unchanged);
+            }
+
+            let page_id: i64 = match existing {
+                Some(existing) => diesel::update(wiki_pages.find(existing))
+                    .set((title.eq(edit.title), updated_at.eq(now)))
+                    .returning(id)
+                    .get_result(c)?,
+                // There's no row to lock yet, so whoever creates the page
+                // first wins and anyone else conflicts
+                None => {
+                    let created = diesel::insert_into(wiki_pages)
+                        .values((slug.eq(for_slug), title.eq(edit.title)))
+                        .on_conflict_do_nothing()
+                        .returning(id)
+                        .get_result(c)
+                        .optional()?;
+
+                    match created {
+                        Some(created) => created,
+                        None => return Ok(WikiSave::Conflict),
+                    }
+                }
+            };
+
+            diesel::insert_into(wiki_revisions::table)
+                .values(NewWikiRevision {
+                    page_id,
+                    author_id: edit.author_id,
+                    title: edit.title,
+                    content: edit.content,
+                    summary: edit.summary,
+                    reverted_from: edit.reverted_from,
+                })
+                .get_result(c)
+                .map(WikiSave::Saved)
+        })
+    }
+
+    pub fn set_protection(c: &mut PgConnection, page_id: i64, role: Role) -> QueryResult<usize> {
+        use crate::db::schema::wiki_pages::dsl::*;
+
+        diesel::update(wiki_pages.find(page_id))
+            .set(protection.eq(role))
+            .execute(c)
+    }
+}
@@ -0,0 +1,139 @@
+use crate::db::schema::{users, wiki_pages, wiki_revisions};
+use chrono::{DateTime, Utc};
+use diesel::{prelude::*, PgConnection};
+use rocket::serde::Serialize;
+
+/// A version of a wiki page. Revisions are never changed; reverting a page
+/// adds a new revision with the old content.
+#[derive(Queryable, Serialize)]
+pub struct WikiRevision {
+    pub id: i64,
+    pub page_id: i64,
+    pub author_id: i64,
+    pub title: String,
+    pub content: String,
+    pub summary: String,
+    pub reverted_from: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A revision without its content, for page histories and recent changes.
+#[derive(Queryable, Serialize)]
+pub struct RevisionInfo {
+    pub id: i64,
+    pub slug: String,
+    pub title: String,
+    pub summary: String,
+    pub reverted_from: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub author: String,
+}
+
+type RevisionInfoColumns = (
+    wiki_revisions::id,
+    wiki_pages::slug,
+    wiki_revisions::title,
+    wiki_revisions::summary,
+    wiki_revisions::reverted_from,
+    wiki_revisions::created_at,
+    users::username,
+);
+
+const REVISION_INFO_COLUMNS: RevisionInfoColumns = (
+    wiki_revisions::id,
+    wiki_pages::slug,
+    wiki_revisions::title,
+    wiki_revisions::summary,
+    wiki_revisions::reverted_from,
+    wiki_revisions::created_at,
+    users::username,
+);
+
+impl WikiRevision {
+    pub fn find(
+        c: &mut PgConnection,
+        for_page: i64,
+        revision_id: i64,
+    ) -> QueryResult<Option<WikiRevision>> {
+        use crate::db::schema::wiki_revisions::dsl::*;
+
+        wiki_revisions
+            .filter(page_id.eq(for_page))
+            .find(revision_id)
+            .first(c)
+            .optional()
+    }
+
+    /// The revision before this one, if it isn't the first.
+    pub fn previous(&self, c: &mut PgConnection) -> QueryResult<Option<WikiRevision>> {
+        use crate::db::schema::wiki_revisions::dsl::*;
+
+        wiki_revisions
+            .filter(page_id.eq(self.page_id).and(id.lt(self.id)))
+            .order(id.desc())
+            .first(c)
+            .optional()
+    }
+
+    /// Newest first.
+    pub fn history(
+        c: &mut PgConnection,
+        for_page: i64,
+        limit: i64,
+        offset: i64,
+    ) -> QueryResult<Vec<RevisionInfo>> {
+        wiki_revisions::table
+            .inner_join(wiki_pages::table)
+            .inner_join(users::table)
+            .filter(wiki_revisions::page_id.eq(for_page))
+            .select(REVISION_INFO_COLUMNS)
+            .order(wiki_revisions::id.desc())
+            .limit(limit)
+            .offset(offset)
+            .load(c)
+    }
+
+    pub fn count_for_page(c: &mut PgConnection, for_page: i64) -> QueryResult<i64> {
+        use crate::db::schema::wiki_revisions::dsl::*;
+
+        wiki_revisions.filter(page_id.eq(for_page)).count().get_result(c)
+    }
+
+    /// Everyone who has edited a page.
+    pub fn editors(c: &mut PgConnection, for_page: i64) -> QueryResult<Vec<i64>> {
+        use crate::db::schema::wiki_revisions::dsl::*;
+
+        wiki_revisions
+            .filter(page_id.eq(for_page))
+            .select(author_id)
+            .distinct()
+            .load(c)
+    }
+
+    /// Revisions of every page, newest first.
+    pub fn recent(c: &mut PgConnection, limit: i64, offset: i64) -> QueryResult<Vec<RevisionInfo>> {
+        wiki_revisions::table
+            .inner_join(wiki_pages::table)
+            .inner_join(users::table)
+            .select(REVISION_INFO_COLUMNS)
+            .order(wiki_revisions::id.desc())
+            .limit(limit)
+            .offset(offset)
+            .load(c)
+    }
+
+    pub fn count(c: &mut PgConnection) -> QueryResult<i64> {
+        wiki_revisions::table.count().get_result(c)
+    }
+}
+
+#[derive(Insertable)]
+#[table_name = "wiki_revisions"]
+pub struct NewWikiRevision<'a> {
+    pub page_id: i64,
+    pub author_id: i64,
+    pub title: &'a str,
+    pub content: &'a str,
+    pub summary: &'a str,
+    pub reverted_from: Option<i64>,
+}
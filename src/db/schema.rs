@@ -1,3 +1,278 @@
+table! {
+    contributor_signatures (id) {
+        id -> Int8,
+        user_id -> Nullable<Int8>,
+        legal_name -> Varchar,
+        github_handle -> Varchar,
+        version -> Varchar,
+        document_sha256 -> Bytea,
+        signed_at -> Timestamptz,
+        github_id -> Nullable<Int8>,
+    }
+}
+
+table! {
+    conversation_members (conversation_id, user_id) {
+        conversation_id -> Int8,
+        user_id -> Int8,
+        joined_at -> Timestamptz,
+        last_read_id -> Int8,
+    }
+}
+
+table! {
+    conversations (id) {
+        id -> Int8,
+        title -> Nullable<Varchar>,
+        is_group -> Bool,
+        created_at -> Timestamptz,
+        last_message_at -> Timestamptz,
+    }
+}
+
+table! {
+    direct_conversations (low_user_id, high_user_id) {
+        low_user_id -> Int8,
+        high_user_id -> Int8,
+        conversation_id -> Int8,
+    }
+}
+
+table! {
+    email_outbox (id) {
+        id -> Int8,
+        recipient -> Varchar,
+        subject -> Varchar,
+        template -> Varchar,
+        html_body -> Text,
+        text_body -> Text,
+        status -> Varchar,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        sent_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    forum_categories (id) {
+        id -> Int8,
+        slug -> Varchar,
+        name -> Varchar,
+        description -> Varchar,
+        position -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    forum_post_edits (id) {
+        id -> Int8,
+        post_id -> Int8,
+        editor_id -> Int8,
+        content -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    forum_posts (id) {
+        id -> Int8,
+        thread_id -> Int8,
+        author_id -> Int8,
+        content -> Text,
+        created_at -> Timestamptz,
+        edited_at -> Nullable<Timestamptz>,
+        content_html -> Nullable<Text>,
+    }
+}
+
+table! {
+    forum_threads (id) {
+        id -> Int8,
+        category_id -> Int8,
+        author_id -> Int8,
+        title -> Varchar,
+        pinned -> Bool,
+        locked -> Bool,
+        created_at -> Timestamptz,
+        last_post_at -> Timestamptz,
+    }
+}
+
+table! {
+    game_servers (id) {
+        id -> Int8,
+        name -> Varchar,
+        address -> Varchar,
+        port -> Int4,
+        version -> Varchar,
+        max_players -> Int4,
+        player_count -> Int4,
+        region -> Varchar,
+        tags -> Array<Text>,
+        token -> Bytea,
+        online -> Bool,
+        created_at -> Timestamptz,
+        last_heartbeat -> Timestamptz,
+    }
+}
+
+table! {
+    invites (id) {
+        id -> Int8,
+        code -> Varchar,
+        note -> Nullable<Text>,
+        max_uses -> Int4,
+        uses -> Int4,
+        expires_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    jobs (id) {
+        id -> Int8,
+        kind -> Varchar,
+        payload -> Jsonb,
+        status -> Varchar,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        run_at -> Timestamptz,
+        repeat_every -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    join_tickets (id) {
+        id -> Int8,
+        user_id -> Int8,
+        server_id -> Int8,
+        ticket -> Bytea,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        used_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    login_attempts (id) {
+        id -> Int8,
+        ip_address -> Nullable<Varchar>,
+        username -> Text,
+        attempted_at -> Timestamptz,
+    }
+}
+
+table! {
+    message_reports (id) {
+        id -> Int8,
+        message_id -> Nullable<Int8>,
+        reporter_id -> Int8,
+        reason -> Varchar,
+        created_at -> Timestamptz,
+        resolved_at -> Nullable<Timestamptz>,
+        resolved_by -> Nullable<Int8>,
+        conversation_id -> Int8,
+        author_id -> Nullable<Int8>,
+        author -> Varchar,
+        content -> Text,
+        sent_at -> Timestamptz,
+    }
+}
+
+table! {
+    messages (id) {
+        id -> Int8,
+        conversation_id -> Int8,
+        author_id -> Int8,
+        content -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    notification_preferences (user_id, kind) {
+        user_id -> Int8,
+        kind -> Varchar,
+        enabled -> Bool,
+    }
+}
+
+table! {
+    notifications (id) {
+        id -> Int8,
+        user_id -> Int8,
+        kind -> Varchar,
+        payload -> Jsonb,
+        created_at -> Timestamptz,
+        read_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    policy_acceptances (id) {
+        id -> Int8,
+        user_id -> Int8,
+        policy -> Varchar,
+        version -> Varchar,
+        accepted_at -> Timestamptz,
+    }
+}
+
+table! {
+    presences (user_id) {
+        user_id -> Int8,
+        server_id -> Nullable<Int8>,
+        updated_at -> Timestamptz,
+        expires_at -> Timestamptz,
+    }
+}
+
+table! {
+    release_patches (id) {
+        id -> Int8,
+        release_id -> Int8,
+        from_version -> Varchar,
+        file_url -> Text,
+        size -> Int8,
+        sha256 -> Bytea,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    releases (id) {
+        id -> Int8,
+        version -> Varchar,
+        channel -> Varchar,
+        platform -> Varchar,
+        file_url -> Text,
+        size -> Int8,
+        sha256 -> Bytea,
+        signature -> Nullable<Text>,
+        release_date -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    // `search` is a generated tsvector column, only used through raw SQL
+    search_documents (id) {
+        id -> Int8,
+        kind -> Varchar,
+        url -> Varchar,
+        title -> Text,
+        headings -> Text,
+        body -> Text,
+        channel -> Nullable<Varchar>,
+    }
+}
+
 table! {
     sessions (id) {
         id -> Int8,
@@ -9,18 +284,120 @@ table! {
     }
 }
 
+table! {
+    update_manifests (id) {
+        id -> Int8,
+        channel -> Varchar,
+        platform -> Varchar,
+        key_id -> Varchar,
+        manifest -> Text,
+        signature -> Text,
+        issued_at -> Timestamptz,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    user_blocks (blocker_id, blocked_id) {
+        blocker_id -> Int8,
+        blocked_id -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     users (id) {
         id -> Int8,
         username -> Varchar,
         password -> Text,
         created_at -> Timestamptz,
+        banned -> Bool,
+        last_seen_at -> Nullable<Timestamptz>,
+        show_presence -> Bool,
+        role -> Varchar,
+        beta_opt_in -> Bool,
+        unread_notifications -> Int8,
+    }
+}
+
+table! {
+    wiki_pages (id) {
+        id -> Int8,
+        slug -> Varchar,
+        title -> Varchar,
+        protection -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+    }
+}
+
+table! {
+    wiki_revisions (id) {
+        id -> Int8,
+        page_id -> Int8,
+        author_id -> Int8,
+        title -> Varchar,
+        content -> Text,
+        summary -> Varchar,
+        reverted_from -> Nullable<Int8>,
+        created_at -> Timestamptz,
     }
 }
 
+joinable!(contributor_signatures -> users (user_id));
+joinable!(conversation_members -> conversations (conversation_id));
+joinable!(conversation_members -> users (user_id));
+joinable!(direct_conversations -> conversations (conversation_id));
+joinable!(forum_post_edits -> forum_posts (post_id));
+joinable!(forum_post_edits -> users (editor_id));
+joinable!(forum_posts -> forum_threads (thread_id));
+joinable!(forum_posts -> users (author_id));
+joinable!(forum_threads -> forum_categories (category_id));
+joinable!(forum_threads -> users (author_id));
+joinable!(join_tickets -> game_servers (server_id));
+joinable!(join_tickets -> users (user_id));
+joinable!(message_reports -> messages (message_id));
+joinable!(message_reports -> users (reporter_id));
+joinable!(messages -> conversations (conversation_id));
+joinable!(messages -> users (author_id));
+joinable!(notification_preferences -> users (user_id));
+joinable!(notifications -> users (user_id));
+joinable!(policy_acceptances -> users (user_id));
+joinable!(presences -> game_servers (server_id));
+joinable!(presences -> users (user_id));
+joinable!(release_patches -> releases (release_id));
 joinable!(sessions -> users (user_id));
+joinable!(wiki_revisions -> users (author_id));
+joinable!(wiki_revisions -> wiki_pages (page_id));
 
 allow_tables_to_appear_in_same_query!(
+    contributor_signatures,
+    conversation_members,
+    conversations,
+    direct_conversations,
+    email_outbox,
+    forum_categories,
+    forum_post_edits,
+    forum_posts,
+    forum_threads,
+    game_servers,
+    invites,
+    jobs,
+    join_tickets,
+    login_attempts,
+    message_reports,
+    messages,
+    notification_preferences,
+    notifications,
+    policy_acceptances,
+    presences,
+    release_patches,
+    releases,
+    search_documents,
     sessions,
+    update_manifests,
+    user_blocks,
     users,
+    wiki_pages,
+    wiki_revisions,
 );
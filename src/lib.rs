@@ -0,0 +1,130 @@
+// Managed state is built from the environment, which `Default` would hide
+#![allow(clippy::new_without_default)]
+
+#[macro_use]
+extern crate rocket;
+#[macro_use]
+extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
+#[macro_use]
+extern crate log;
+
+use argon2::Argon2;
+use rocket::figment::{
+    util::map,
+    value::{Map, Value},
+};
+use rocket::data::{Limits, ToByteUnit};
+use rocket::fs::FileServer;
+use rocket::{Build, Rocket};
+use rocket_dyn_templates::Template;
+use std::{env, path::PathBuf};
+
+pub mod db;
+mod routes;
+pub mod util;
+
+pub use db::models;
+use chrono::Duration;
+use util::{
+    jobs::PruneJobs, GameServerUtils, JobRegistry, JobWorker, LiveListener, PurgeExpiredPresence,
+    PurgeExpiredSessions, PurgeLoginAttempts, SearchIndexer, SendEmails, MAIL_POLL, SESSION_PURGE,
+};
+
+/// Builds the site, ready to launch.
+pub fn rocket() -> Rocket<Build> {
+    let is_debug = cfg!(debug_assertions);
+    // Tests build the site more than once, with their own logger
+    if let Err(err) = util::setup_logging(is_debug) {
+        eprintln!("warning: failed to set up logging: {}", err);
+    }
+
+    if is_debug {
+        info!("Loading environment variables from .env.");
+        if let Err(err) = dotenvy::dotenv() {
+            warn!("Failed to load .env: {}", err);
+        }
+    }
+
+    let database_url =
+        env::var("DATABASE_URL").expect("The DATABASE_URL environment variable is not set.");
+
+    let db: Map<_, Value> = map! {
+        "url" => database_url.into(),
+        "pool_size" => 10.into(),
+    };
+
+    // Builds uploaded through the admin release forms. Other forms keep
+    // the default limits.
+    let limits = Limits::default().limit(util::UPLOAD_LIMIT, 1.gibibytes());
+
+    let figment = rocket::Config::figment()
+        .merge(("databases", map!["fumohouse_db" => db]))
+        .merge(("limits", limits));
+
+    let pages = util::markdown::PageCache::load().expect("Failed to load markdown pages.");
+    let changelog = util::Changelog::load(&pages).expect("Failed to index the changelog.");
+    let news = util::News::load(&pages).expect("Failed to index news posts.");
+
+    let template_dir = figment
+        .extract_inner::<PathBuf>("template_dir")
+        .unwrap_or_else(|_| "templates".into());
+    let mailer = util::Mailer::load(&template_dir).expect("Failed to set up email.");
+
+    let jobs = JobRegistry::default()
+        .recurring(PurgeExpiredSessions, Duration::minutes(SESSION_PURGE))
+        .recurring(PurgeExpiredPresence, Duration::minutes(SESSION_PURGE))
+        .recurring(PurgeLoginAttempts, Duration::minutes(SESSION_PURGE))
+        .recurring(PruneJobs, Duration::days(1))
+        .recurring(SendEmails, Duration::seconds(MAIL_POLL));
+
+    rocket::custom(figment)
+        .attach(db::FumohouseDb::fairing())
+        .attach(db::migrations::Migrations)
+        .attach(Template::fairing())
+        .attach(GameServerUtils)
+        .attach(LiveListener)
+        .attach(SearchIndexer)
+        .attach(JobWorker::new(jobs))
+        .manage(util::CaptchaVerifier::new())
+        .manage(util::GameServerConfig::new())
+        .manage(util::GithubOAuth::new())
+        .manage(util::InviteConfig::new())
+        .manage(util::UpdateKeys::new())
+        .manage(pages)
+        .manage(changelog)
+        .manage(news)
+        .manage(mailer)
+        .manage(util::LiveEvents::default())
+        .manage(Argon2::default())
+        .register("/", routes::pages::catchers())
+        .register("/api", routes::api::catchers())
+        .mount("/", FileServer::from("static/"))
+        .mount("/", routes::pages::routes())
+        .mount("/account", routes::account::routes())
+        .mount("/admin", routes::admin::routes())
+        .mount("/auth", routes::auth::routes())
+        .mount("/changelog", routes::changelog::routes())
+        .mount("/download", routes::download::routes())
+        .mount("/forums", routes::forums::routes())
+        .mount("/messages", routes::messages::routes())
+        .mount("/moderation", routes::moderation::routes())
+        .mount("/news", routes::news::routes())
+        .mount("/notifications", routes::notifications::routes())
+        .mount("/releases", routes::releases::routes())
+        .mount("/search", routes::search::routes())
+        .mount("/servers", routes::servers::routes())
+        .mount("/users", routes::users::routes())
+        .mount("/wiki", routes::wiki::routes())
+        .mount("/api/v1/auth", routes::api::auth::routes())
+        .mount("/api/v1/contributors", routes::api::contributors::routes())
+        .mount("/api/v1/health", routes::api::health::routes())
+        .mount("/api/v1/live", routes::api::live::routes())
+        .mount("/api/v1/messages", routes::api::messages::routes())
+        .mount("/api/v1/notifications", routes::api::notifications::routes())
+        .mount("/api/v1/presence", routes::api::presence::routes())
+        .mount("/api/v1/releases", routes::api::releases::routes())
+        .mount("/api/v1/servers", routes::api::servers::routes())
+        .mount("/api/v1/update", routes::api::update::routes())
+}
@@ -2,24 +2,45 @@ use std::collections::HashMap;
 
 use super::{BaseData, DefaultContext};
 use crate::{
-    db::FumohouseDb,
-    util::{self, CsrfToken, CsrfVerify, SiteMessages, UserSession},
+    db::{
+        models::{ContributorSignature, NewContributorSignature},
+        FumohouseDb,
+    },
+    util::{
+        self,
+        markdown::PageCache,
+        policy::{Policy, CONTRIBUTOR_AGREEMENT},
+        CsrfToken, CsrfVerify, GithubAccount, GithubOAuth, SiteMessages, UserSession,
+    },
 };
 use argon2::Argon2;
 use diesel::{prelude::*, result::Error as DieselError};
 use rocket::{
     form::{name::NameView, Context, Contextual, Form, FromForm, Options, ValueField},
+    http::{CookieJar, Status},
     response::Redirect,
+    serde::Serialize,
     Route, State,
 };
 use rocket_dyn_templates::Template;
 
 pub fn routes() -> Vec<Route> {
-    routes![edit_get, edit_post]
+    routes![
+        edit_get,
+        edit_post,
+        contributor_get,
+        contributor_post,
+        contributor_github,
+        contributor_github_callback
+    ]
 }
 
 #[get("/edit")]
-fn edit_get(csrf: CsrfToken, user_session: UserSession) -> Result<Template, Redirect> {
+fn edit_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    pages: &State<PageCache>,
+) -> Result<Template, Redirect> {
     if !user_session.user.is_some() {
         return Err(Redirect::to(uri!("/auth/login")));
     }
@@ -27,10 +48,7 @@ fn edit_get(csrf: CsrfToken, user_session: UserSession) -> Result<Template, Redi
     Ok(Template::render(
         "account/edit",
         DefaultContext {
-            base: BaseData {
-                user: user_session.user,
-                csrf_token: &csrf.token,
-            },
+            base: BaseData::new(user_session.user, &csrf.token, pages),
             captcha_site_key: None,
             form_context: Some(&Context::default()),
         },
@@ -65,6 +83,16 @@ where
     result.unwrap()
 }
 
+#[derive(FromForm)]
+struct PrivacySettings {
+    show_presence: bool,
+}
+
+#[derive(FromForm)]
+struct BetaSettings {
+    beta_opt_in: bool,
+}
+
 enum EditResult<'a> {
     Success(Context<'a>),
     SessionInvalidated,
@@ -73,7 +101,7 @@ enum EditResult<'a> {
 async fn handle_edit<'a>(
     conn: &FumohouseDb,
     argon: &Argon2<'_>,
-    user_session: &UserSession,
+    user_session: &mut UserSession,
     body: &'a HashMap<String, String>,
 ) -> Option<EditResult<'a>> {
     use crate::db::schema::{sessions, users};
@@ -146,6 +174,68 @@ async fn handle_edit<'a>(
 
             Some(EditResult::Success(result.context))
         }
+        "privacy" => {
+            let user = user_session.user.as_mut().unwrap();
+            let mut result = parse::<PrivacySettings>(body);
+
+            if let Some(ref form_data) = result.value {
+                let user_id = user.id;
+                let show_presence = form_data.show_presence;
+
+                let update_result = conn
+                    .run(move |c| {
+                        diesel::update(users::table.filter(users::id.eq(user_id)))
+                            .set(users::show_presence.eq(show_presence))
+                            .execute(c)
+                    })
+                    .await;
+
+                match update_result {
+                    Ok(_) => user.show_presence = show_presence,
+                    Err(err) => {
+                        result.context.push_error(SiteMessages::GenericError.into());
+                        error!("account edit: privacy update failed: {}", err);
+                    }
+                }
+            }
+
+            Some(Success(result.context))
+        }
+        "beta" => {
+            let user = user_session.user.as_mut().unwrap();
+            let mut result = parse::<BetaSettings>(body);
+
+            if let Some(ref form_data) = result.value {
+                let user_id = user.id;
+                let beta_opt_in = form_data.beta_opt_in;
+
+                let update_result = conn
+                    .run(move |c| {
+                        diesel::update(users::table.filter(users::id.eq(user_id)))
+                            .set(users::beta_opt_in.eq(beta_opt_in))
+                            .execute(c)
+                    })
+                    .await;
+
+                match update_result {
+                    Ok(_) => {
+                        user.beta_opt_in = beta_opt_in;
+
+                        info!(
+                            "account edit: {} opted {} beta",
+                            user.username,
+                            if beta_opt_in { "into" } else { "out of" }
+                        );
+                    }
+                    Err(err) => {
+                        result.context.push_error(SiteMessages::GenericError.into());
+                        error!("account edit: beta update failed: {}", err);
+                    }
+                }
+            }
+
+            Some(Success(result.context))
+        }
         _ => None,
     }
 }
@@ -153,16 +243,17 @@ async fn handle_edit<'a>(
 #[post("/edit", data = "<form>")]
 async fn edit_post<'a>(
     csrf: CsrfVerify,
-    user_session: UserSession,
+    mut user_session: UserSession,
     form: Form<HashMap<String, String>>,
     conn: FumohouseDb,
     argon: &State<Argon2<'_>>,
+    pages: &State<PageCache>,
 ) -> Result<Template, Redirect> {
     if !user_session.user.is_some() {
         return Err(Redirect::to(uri!("/auth/login")));
     }
 
-    let result = handle_edit(&conn, argon, &user_session, &form).await;
+    let result = handle_edit(&conn, argon, &mut user_session, &form).await;
     let mut context = None;
 
     if let Some(edit_result) = result {
@@ -175,12 +266,270 @@ async fn edit_post<'a>(
     Ok(Template::render(
         "account/edit",
         DefaultContext {
-            base: BaseData {
-                user: user_session.user,
-                csrf_token: csrf.new_token(),
-            },
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
             captcha_site_key: None,
             form_context: context.as_ref(),
         },
     ))
 }
+
+#[derive(Serialize)]
+struct ContributorContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: Option<&'a Context<'b>>,
+    agreement: &'a Policy,
+    signature: Option<ContributorSignature>,
+    /// Whether the latest signature is of the current version
+    is_current: bool,
+    /// The GitHub account the user just verified, which they would sign as
+    github: Option<GithubAccount>,
+    github_enabled: bool,
+}
+
+#[derive(FromForm)]
+struct ContributorForm<'a> {
+    #[field(validate = len(1..=128))]
+    legal_name: &'a str,
+    agree: bool,
+}
+
+/// The user's latest signature, and whether it is of the current version.
+async fn signature_status(
+    conn: &FumohouseDb,
+    user_id: i64,
+    agreement: &Policy,
+) -> Result<(Option<ContributorSignature>, bool), Status> {
+    let signature = conn
+        .run(move |c| ContributorSignature::latest_for_user(c, user_id))
+        .await
+        .map_err(|err| {
+            error!("contributor: failed to find signature: {}", err);
+            Status::InternalServerError
+        })?;
+
+    let is_current = signature
+        .as_ref()
+        .is_some_and(|s| util::to_hex(&s.document_sha256) == agreement.sha256);
+
+    Ok((signature, is_current))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn contributor_template(
+    user_session: UserSession,
+    csrf_token: &str,
+    agreement: &Policy,
+    (signature, is_current): (Option<ContributorSignature>, bool),
+    github: Option<GithubAccount>,
+    github_enabled: bool,
+    form_context: &Context<'_>,
+    pages: &PageCache,
+) -> Template {
+    Template::render(
+        "account/contributor",
+        ContributorContext {
+            base: BaseData::new(user_session.user, csrf_token, pages),
+            form_context: Some(form_context),
+            agreement,
+            signature,
+            is_current,
+            github,
+            github_enabled,
+        },
+    )
+}
+
+#[get("/contributor")]
+async fn contributor_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+    github: &State<GithubOAuth>,
+    cookies: &CookieJar<'_>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let agreement = pages
+        .policy(CONTRIBUTOR_AGREEMENT)
+        .ok_or(Err(Status::NotFound))?;
+
+    let status = signature_status(&conn, user_id, &agreement)
+        .await
+        .map_err(Err)?;
+
+    Ok(contributor_template(
+        user_session,
+        &csrf.token,
+        &agreement,
+        status,
+        GithubOAuth::verified_account(cookies, user_id),
+        github.enabled(),
+        &Context::default(),
+        pages,
+    ))
+}
+
+#[get("/contributor/github")]
+fn contributor_github(
+    user_session: UserSession,
+    github: &State<GithubOAuth>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Status> {
+    if user_session.user.is_none() {
+        return Ok(Redirect::to(uri!("/auth/login")));
+    }
+
+    github
+        .authorize_url(cookies)
+        .map(Redirect::to)
+        .ok_or(Status::NotFound)
+}
+
+#[get("/contributor/github/callback?<code>&<state>")]
+async fn contributor_github_callback(
+    user_session: UserSession,
+    code: &str,
+    state: &str,
+    github: &State<GithubOAuth>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Status> {
+    let user = match user_session.user {
+        Some(ref user) => user,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    match github.verify(cookies, user.id, code, state).await {
+        Ok(account) => {
+            info!(
+                "contributor: {} verified GitHub account {} ({})",
+                user.username, account.login, account.id
+            );
+
+            Ok(Redirect::to(uri!("/account/contributor")))
+        }
+        Err(err) => {
+            warn!("contributor: failed to verify GitHub account: {}", err);
+            Err(Status::BadRequest)
+        }
+    }
+}
+
+async fn handle_sign(
+    conn: &FumohouseDb,
+    user_id: i64,
+    agreement: &Policy,
+    github: Option<GithubAccount>,
+    form_data: &ContributorForm<'_>,
+) -> Result<(), SiteMessages> {
+    if !form_data.agree {
+        return Err(SiteMessages::AgreementNotAccepted);
+    }
+
+    let github = github.ok_or(SiteMessages::GithubNotVerified)?;
+    let legal_name = form_data.legal_name.trim().to_string();
+    let version = agreement.version.clone();
+    let document_sha256 = util::from_hex(&agreement.sha256).ok_or(SiteMessages::GenericError)?;
+
+    let result = conn
+        .run(move |c| {
+            let c: &PgConnection = c;
+
+            c.transaction(|| {
+                // Signers are checked and added one at a time, so two users
+                // can't both claim the same GitHub account
+                diesel::sql_query("LOCK TABLE contributor_signatures IN SHARE ROW EXCLUSIVE MODE")
+                    .execute(c)?;
+
+                let signature = ContributorSignature::latest_for_user(c, user_id)?;
+
+                if signature.is_some_and(|s| s.document_sha256 == document_sha256) {
+                    return Ok(Err(SiteMessages::AgreementAlreadySigned));
+                }
+
+                let existing =
+                    ContributorSignature::latest_by_other(c, user_id, github.id, &github.login)?;
+
+                if existing.is_some() {
+                    return Ok(Err(SiteMessages::GithubHandleInUse));
+                }
+
+                diesel::insert_into(crate::db::schema::contributor_signatures::table)
+                    .values(NewContributorSignature {
+                        user_id,
+                        legal_name: &legal_name,
+                        github_handle: &github.login,
+                        version: &version,
+                        document_sha256: &document_sha256,
+                        github_id: Some(github.id),
+                    })
+                    .execute(c)?;
+
+                Ok::<_, DieselError>(Ok(()))
+            })
+        })
+        .await;
+
+    result.unwrap_or_else(|err| {
+        error!("contributor: failed to record signature: {}", err);
+        Err(SiteMessages::GenericError)
+    })
+}
+
+#[post("/contributor", data = "<form>")]
+async fn contributor_post<'a>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'a, ContributorForm<'a>>>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+    github: &State<GithubOAuth>,
+    cookies: &CookieJar<'_>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let user = match user_session.user {
+        Some(ref user) => user,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+    let user_id = user.id;
+
+    let agreement = pages
+        .policy(CONTRIBUTOR_AGREEMENT)
+        .ok_or(Err(Status::NotFound))?;
+
+    let account = GithubOAuth::verified_account(cookies, user_id);
+
+    if let Some(ref form_data) = form.value {
+        match handle_sign(&conn, user.id, &agreement, account.clone(), form_data).await {
+            Ok(()) => {
+                info!(
+                    "contributor: {} signed version {} of the agreement as {}",
+                    user.username,
+                    agreement.version,
+                    account.map(|a| a.login).unwrap_or_default()
+                );
+
+                GithubOAuth::forget_account(cookies);
+                return Ok(Redirect::to(uri!("/account/contributor")));
+            }
+            Err(message) => form.context.push_error(message.into()),
+        }
+    }
+
+    let status = signature_status(&conn, user_id, &agreement)
+        .await
+        .map_err(Err)?;
+
+    Err(Ok(contributor_template(
+        user_session,
+        csrf.new_token(),
+        &agreement,
+        status,
+        account,
+        github.enabled(),
+        &form.context,
+        pages,
+    )))
+}
@@ -0,0 +1,981 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{
+            Channel, EmailStatus, Job, JobStatus, NewRelease, NewReleasePatch, NewUpdateManifest,
+            OutboxEmail, Platform, Release, ReleasePatch, Role, UpdateManifest,
+        },
+        FumohouseDb,
+    },
+    util::{
+        self,
+        jobs::{self, JobError, PruneJobs},
+        mail::MailError,
+        markdown::PageCache,
+        update::{Manifest, SignedManifest, UpdateError},
+        AdminUpload, CsrfToken, CsrfVerify, Mailer, Pagination, SiteMessages, UpdateKeys,
+        UploadedFile, UserSession,
+    },
+};
+use chrono::{DateTime, NaiveDate, SubsecRound, TimeZone, Utc};
+use diesel::{
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
+use rocket::{
+    form::{Context, Contextual, Error, Form},
+    http::Status,
+    response::Redirect,
+    serde::{json::{self, Json}, Serialize},
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+use std::path::Path;
+
+pub const RELEASES_DIR: &str = "releases";
+
+/// How many of the latest emails the outbox page lists
+const RECENT_EMAILS: i64 = 50;
+
+const JOBS_PER_PAGE: i64 = 50;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        releases_get,
+        releases_post,
+        patches_get,
+        patches_post,
+        manifests_get,
+        manifests_post,
+        unsigned_manifest,
+        email_get,
+        email_post,
+        email_retry,
+        jobs_get,
+        job_retry,
+        jobs_prune,
+    ]
+}
+
+fn valid_version_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+' | '_')
+}
+
+#[derive(Serialize)]
+struct ReleasesContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    releases: Vec<Release>,
+    channels: &'static [Channel],
+    platforms: &'static [Platform],
+}
+
+async fn list_releases(conn: &FumohouseDb) -> Result<Vec<Release>, Status> {
+    conn.run(Release::all).await.map_err(|err| {
+        error!("admin: failed to list releases: {}", err);
+        Status::InternalServerError
+    })
+}
+
+#[get("/releases")]
+async fn releases_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let releases = list_releases(&conn).await?;
+
+    Ok(Template::render(
+        "admin/releases",
+        ReleasesContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            releases,
+            channels: Channel::ALL,
+            platforms: Platform::ALL,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct ReleaseForm<'r> {
+    #[field(validate = len(1..=32))]
+    #[field(validate = with(|v| v.chars().all(valid_version_char), "Version contains invalid characters."))]
+    version: &'r str,
+    channel: Channel,
+    platform: Platform,
+    file_url: &'r str,
+    size: Option<i64>,
+    sha256: &'r str,
+    signature: &'r str,
+    release_date: &'r str,
+}
+
+/// Stores an uploaded file under `name`, returning its URL, size and hash.
+async fn store_upload(
+    file: UploadedFile,
+    name: String,
+) -> Result<(String, i64, Vec<u8>), std::io::Error> {
+    let file_name = match file.extension {
+        Some(ref extension) => format!("{}.{}", name, extension),
+        None => name,
+    };
+
+    let path = Path::new(RELEASES_DIR).join(&file_name);
+    let (size, hash) = (file.size, file.sha256.clone());
+
+    rocket::tokio::fs::create_dir_all(RELEASES_DIR).await?;
+    file.persist_to(&path).await?;
+
+    Ok((format!("/{}/{}", RELEASES_DIR, file_name), size, hash))
+}
+
+/// Determines the URL, size and hash of a file which is either uploaded
+/// (and stored under `name`) or hosted elsewhere.
+async fn resolve_file(
+    upload: Option<UploadedFile>,
+    name: String,
+    file_url: &str,
+    size: Option<i64>,
+    sha256: &str,
+    errors: &mut Vec<Error<'_>>,
+) -> Option<(String, i64, Vec<u8>)> {
+    if let Some(file) = upload {
+        return match store_upload(file, name).await {
+            Ok(stored) => Some(stored),
+            Err(err) => {
+                errors.push(SiteMessages::GenericError.into());
+                error!("admin: failed to store upload: {}", err);
+                None
+            }
+        };
+    }
+
+    let size = match size {
+        Some(size) if !file_url.is_empty() && !sha256.is_empty() => size,
+        _ => {
+            errors.push(SiteMessages::ReleaseFileMissing.into());
+            return None;
+        }
+    };
+
+    match util::from_hex(sha256).filter(|h| h.len() == 32) {
+        Some(hash) => Some((file_url.to_string(), size, hash)),
+        None => {
+            errors.push(SiteMessages::ReleaseHashInvalid.into());
+            None
+        }
+    }
+}
+
+async fn handle_release<'r>(
+    conn: &FumohouseDb,
+    form_data: &ReleaseForm<'r>,
+    upload: Option<UploadedFile>,
+    errors: &mut Vec<Error<'_>>,
+) -> Option<Release> {
+    use crate::db::schema::releases;
+
+    let release_date = match NaiveDate::parse_from_str(form_data.release_date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+    {
+        Some(date) => Utc.from_utc_datetime(&date),
+        None => {
+            errors.push(SiteMessages::ReleaseDateInvalid.into());
+            return None;
+        }
+    };
+
+    let name = format!(
+        "fumohouse-{}-{}-{}",
+        form_data.version,
+        form_data.channel.as_str(),
+        form_data.platform.as_str()
+    );
+
+    let (file_url, size, hash) = resolve_file(
+        upload,
+        name,
+        form_data.file_url,
+        form_data.size,
+        form_data.sha256,
+        errors,
+    )
+    .await?;
+
+    let version = form_data.version.to_string();
+    let channel = form_data.channel;
+    let platform = form_data.platform;
+    let signature = Some(form_data.signature.to_string()).filter(|s| !s.is_empty());
+
+    let result = conn
+        .run(move |c| {
+            diesel::insert_into(releases::table)
+                .values(&NewRelease {
+                    version: &version,
+                    channel,
+                    platform,
+                    file_url: &file_url,
+                    size,
+                    sha256: &hash,
+                    signature: signature.as_deref(),
+                    release_date,
+                })
+                .get_result::<Release>(c)
+        })
+        .await;
+
+    match result {
+        Ok(release) => Some(release),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            errors.push(SiteMessages::ReleaseExists.into());
+            None
+        }
+        Err(err) => {
+            errors.push(SiteMessages::GenericError.into());
+            error!("admin: failed to register release: {}", err);
+            None
+        }
+    }
+}
+
+/// Only admins get as far as the upload being read.
+#[post("/releases", data = "<upload>")]
+async fn releases_post(
+    csrf: CsrfVerify,
+    mut upload: AdminUpload,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let file = upload.file.take();
+    let user_session = std::mem::take(&mut upload.user_session);
+    let mut form = Form::<Contextual<ReleaseForm>>::parse_iter(upload.fields())
+        .map_err(|_| Err(Status::BadRequest))?;
+
+    let mut errors = Vec::new();
+
+    if let Some(ref form_data) = form.value {
+        if let Some(release) = handle_release(&conn, form_data, file, &mut errors).await {
+            info!(
+                "admin: {} registered release {} ({}, {})",
+                user_session.user.as_ref().unwrap().username,
+                release.version,
+                release.channel.as_str(),
+                release.platform.as_str()
+            );
+
+            return Ok(Redirect::to(uri!("/admin/releases")));
+        }
+    }
+
+    let releases = list_releases(&conn).await.map_err(Err)?;
+
+    form.context.push_errors(errors);
+
+    Err(Ok(Template::render(
+        "admin/releases",
+        ReleasesContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            releases,
+            channels: Channel::ALL,
+            platforms: Platform::ALL,
+        },
+    )))
+}
+
+#[derive(Serialize)]
+struct PatchListing {
+    patch: ReleasePatch,
+    release: Release,
+}
+
+#[derive(Serialize)]
+struct PatchesContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    patches: Vec<PatchListing>,
+    channels: &'static [Channel],
+    platforms: &'static [Platform],
+}
+
+async fn list_patches(conn: &FumohouseDb) -> Result<Vec<PatchListing>, Status> {
+    match conn.run(ReleasePatch::all).await {
+        Ok(patches) => Ok(patches
+            .into_iter()
+            .map(|(patch, release)| PatchListing { patch, release })
+            .collect()),
+        Err(err) => {
+            error!("admin: failed to list patches: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[get("/patches")]
+async fn patches_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let patches = list_patches(&conn).await?;
+
+    Ok(Template::render(
+        "admin/patches",
+        PatchesContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            patches,
+            channels: Channel::ALL,
+            platforms: Platform::ALL,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct PatchForm<'r> {
+    /// Version the patch upgrades to
+    version: &'r str,
+    channel: Channel,
+    platform: Platform,
+    #[field(validate = len(1..=32))]
+    #[field(validate = with(|v| v.chars().all(valid_version_char), "Version contains invalid characters."))]
+    from_version: &'r str,
+    file_url: &'r str,
+    size: Option<i64>,
+    sha256: &'r str,
+}
+
+async fn handle_patch<'r>(
+    conn: &FumohouseDb,
+    form_data: &PatchForm<'r>,
+    upload: Option<UploadedFile>,
+    errors: &mut Vec<Error<'_>>,
+) -> Option<(ReleasePatch, Release)> {
+    use crate::db::schema::release_patches;
+
+    let version = form_data.version.to_string();
+    let channel = form_data.channel;
+    let platform = form_data.platform;
+
+    let release = match conn
+        .run(move |c| Release::find(c, &version, channel, platform))
+        .await
+    {
+        Ok(release) => release,
+        Err(DieselError::NotFound) => {
+            errors.push(SiteMessages::ReleaseNotFound.into());
+            return None;
+        }
+        Err(err) => {
+            errors.push(SiteMessages::GenericError.into());
+            error!("admin: failed to retrieve release: {}", err);
+            return None;
+        }
+    };
+
+    let name = format!(
+        "fumohouse-{}-{}-{}-from-{}",
+        release.version,
+        release.channel.as_str(),
+        release.platform.as_str(),
+        form_data.from_version
+    );
+
+    let (file_url, size, hash) = resolve_file(
+        upload,
+        name,
+        form_data.file_url,
+        form_data.size,
+        form_data.sha256,
+        errors,
+    )
+    .await?;
+
+    let release_id = release.id;
+    let from_version = form_data.from_version.to_string();
+
+    let result = conn
+        .run(move |c| {
+            diesel::insert_into(release_patches::table)
+                .values(&NewReleasePatch {
+                    release_id,
+                    from_version: &from_version,
+                    file_url: &file_url,
+                    size,
+                    sha256: &hash,
+                })
+                .get_result::<ReleasePatch>(c)
+        })
+        .await;
+
+    match result {
+        Ok(patch) => Some((patch, release)),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+            errors.push(SiteMessages::PatchExists.into());
+            None
+        }
+        Err(err) => {
+            errors.push(SiteMessages::GenericError.into());
+            error!("admin: failed to register patch: {}", err);
+            None
+        }
+    }
+}
+
+/// Only admins get as far as the upload being read.
+#[post("/patches", data = "<upload>")]
+async fn patches_post(
+    csrf: CsrfVerify,
+    mut upload: AdminUpload,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let file = upload.file.take();
+    let user_session = std::mem::take(&mut upload.user_session);
+    let mut form = Form::<Contextual<PatchForm>>::parse_iter(upload.fields())
+        .map_err(|_| Err(Status::BadRequest))?;
+
+    let mut errors = Vec::new();
+
+    if let Some(ref form_data) = form.value {
+        if let Some((patch, release)) = handle_patch(&conn, form_data, file, &mut errors).await {
+            info!(
+                "admin: {} registered patch {} -> {} ({}, {})",
+                user_session.user.as_ref().unwrap().username,
+                patch.from_version,
+                release.version,
+                release.channel.as_str(),
+                release.platform.as_str()
+            );
+
+            return Ok(Redirect::to(uri!("/admin/patches")));
+        }
+    }
+
+    let patches = list_patches(&conn).await.map_err(Err)?;
+
+    form.context.push_errors(errors);
+
+    Err(Ok(Template::render(
+        "admin/patches",
+        PatchesContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            patches,
+            channels: Channel::ALL,
+            platforms: Platform::ALL,
+        },
+    )))
+}
+
+#[derive(Serialize)]
+struct PublishedManifest {
+    version: String,
+    key_id: String,
+    issued_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct ManifestTarget {
+    channel: Channel,
+    platform: Platform,
+    latest_version: Option<String>,
+    published: Option<PublishedManifest>,
+}
+
+#[derive(Serialize)]
+struct ManifestsContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    targets: Vec<ManifestTarget>,
+}
+
+async fn list_manifest_targets(conn: &FumohouseDb) -> Result<Vec<ManifestTarget>, Status> {
+    let result = conn
+        .run(|c| {
+            let mut targets = Vec::new();
+
+            for &channel in Channel::ALL {
+                let latest = Release::latest(c, channel)?;
+
+                for &platform in Platform::ALL {
+                    let latest_version = latest
+                        .iter()
+                        .find(|r| r.platform == platform)
+                        .map(|r| r.version.clone());
+
+                    let published = UpdateManifest::latest(c, channel, platform)?;
+
+                    targets.push((channel, platform, latest_version, published));
+                }
+            }
+
+            QueryResult::Ok(targets)
+        })
+        .await;
+
+    let targets = result.map_err(|err| {
+        error!("admin: failed to list manifests: {}", err);
+        Status::InternalServerError
+    })?;
+
+    Ok(targets
+        .into_iter()
+        .map(|(channel, platform, latest_version, published)| ManifestTarget {
+            channel,
+            platform,
+            latest_version,
+            // Published manifests were verified when they were uploaded
+            published: published.and_then(|m| {
+                let body = json::from_str::<Manifest>(&m.manifest).ok()?;
+
+                Some(PublishedManifest {
+                    version: body.version,
+                    key_id: m.key_id,
+                    issued_at: m.issued_at,
+                })
+            }),
+        })
+        .collect())
+}
+
+#[get("/manifests")]
+async fn manifests_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let targets = list_manifest_targets(&conn).await?;
+
+    Ok(Template::render(
+        "admin/manifests",
+        ManifestsContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            targets,
+        },
+    ))
+}
+
+/// An unsigned manifest for the latest release, to be signed offline
+/// with `fumohouse-admin manifest sign`.
+#[get("/manifests/<channel>/<platform>")]
+async fn unsigned_manifest(
+    channel: Channel,
+    platform: Platform,
+    user_session: UserSession,
+    update_keys: &State<UpdateKeys>,
+    conn: FumohouseDb,
+) -> Result<Json<Manifest>, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let result = conn
+        .run(move |c| {
+            let release = Release::latest(c, channel)?
+                .into_iter()
+                .find(|r| r.platform == platform);
+
+            match release {
+                Some(release) => {
+                    let patches = ReleasePatch::for_release(c, release.id)?;
+                    Ok(Some((release, patches)))
+                }
+                None => QueryResult::Ok(None),
+            }
+        })
+        .await;
+
+    match result {
+        Ok(Some((release, patches))) => Ok(Json(Manifest::new(&release, &patches, update_keys))),
+        Ok(None) => Err(Status::NotFound),
+        Err(err) => {
+            error!("admin: failed to build manifest: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(FromForm)]
+struct ManifestForm<'r> {
+    signed_manifest: &'r str,
+}
+
+async fn handle_manifest(
+    conn: &FumohouseDb,
+    update_keys: &UpdateKeys,
+    form_data: &ManifestForm<'_>,
+    errors: &mut Vec<Error<'_>>,
+) -> Option<Manifest> {
+    use crate::db::schema::update_manifests;
+
+    let signed = match json::from_str::<SignedManifest>(form_data.signed_manifest) {
+        Ok(signed) => signed,
+        Err(_) => {
+            errors.push(SiteMessages::ManifestInvalid.into());
+            return None;
+        }
+    };
+
+    let manifest = match update_keys.verify(&signed) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            errors.push(
+                match err {
+                    UpdateError::UnknownKey(_) => SiteMessages::ManifestKeyUnknown,
+                    UpdateError::BadSignature => SiteMessages::ManifestSignatureInvalid,
+                    _ => SiteMessages::ManifestInvalid,
+                }
+                .into(),
+            );
+            return None;
+        }
+    };
+
+    let channel = manifest.channel;
+    let platform = manifest.platform;
+    // Postgres stores microseconds, so compare at that precision
+    let issued_at = manifest.issued_at.trunc_subsecs(6);
+
+    let result = conn
+        .run(move |c| {
+            // Launchers refuse manifests older than one they've seen,
+            // so publishing one would strand them on the current version
+            if let Some(current) = UpdateManifest::latest(c, channel, platform)? {
+                if current.issued_at >= issued_at {
+                    return Ok(false);
+                }
+            }
+
+            diesel::insert_into(update_manifests::table)
+                .values(&NewUpdateManifest {
+                    channel,
+                    platform,
+                    key_id: &signed.key_id,
+                    manifest: &signed.manifest,
+                    signature: &signed.signature,
+                    issued_at,
+                })
+                .execute(c)?;
+
+            QueryResult::Ok(true)
+        })
+        .await;
+
+    match result {
+        Ok(true) => Some(manifest),
+        Ok(false) => {
+            errors.push(SiteMessages::ManifestOutdated.into());
+            None
+        }
+        Err(err) => {
+            errors.push(SiteMessages::GenericError.into());
+            error!("admin: failed to publish manifest: {}", err);
+            None
+        }
+    }
+}
+
+#[post("/manifests", data = "<form>")]
+async fn manifests_post<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, ManifestForm<'r>>>,
+    update_keys: &State<UpdateKeys>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Err(Status::Forbidden));
+    }
+
+    let mut errors = Vec::new();
+
+    if let Some(ref form_data) = form.value {
+        if let Some(manifest) = handle_manifest(&conn, update_keys, form_data, &mut errors).await {
+            info!(
+                "admin: {} published manifest for {} ({}, {})",
+                user_session.user.as_ref().unwrap().username,
+                manifest.version,
+                manifest.channel.as_str(),
+                manifest.platform.as_str()
+            );
+
+            return Ok(Redirect::to(uri!("/admin/manifests")));
+        }
+    }
+
+    let targets = list_manifest_targets(&conn).await.map_err(Err)?;
+
+    form.context.push_errors(errors);
+
+    Err(Ok(Template::render(
+        "admin/manifests",
+        ManifestsContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            targets,
+        },
+    )))
+}
+
+#[derive(Serialize)]
+struct EmailContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    counts: Vec<(EmailStatus, i64)>,
+    emails: Vec<OutboxEmail>,
+}
+
+async fn list_emails(
+    conn: &FumohouseDb,
+) -> Result<(Vec<(EmailStatus, i64)>, Vec<OutboxEmail>), Status> {
+    conn.run(|c| {
+        Ok((
+            OutboxEmail::count_by_status(c)?,
+            OutboxEmail::recent(c, RECENT_EMAILS)?,
+        ))
+    })
+    .await
+    .map_err(|err: DieselError| {
+        error!("admin: failed to list emails: {}", err);
+        Status::InternalServerError
+    })
+}
+
+#[get("/email")]
+async fn email_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let (counts, emails) = list_emails(&conn).await?;
+
+    Ok(Template::render(
+        "admin/email",
+        EmailContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            counts,
+            emails,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct TestEmailForm<'r> {
+    #[field(validate = len(1..=320))]
+    recipient: &'r str,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct TestEmail<'a> {
+    sender: &'a str,
+}
+
+/// Queues a test email, to check that email is set up properly.
+#[post("/email", data = "<form>")]
+async fn email_post<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, TestEmailForm<'r>>>,
+    mailer: &State<Mailer>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Err(Status::Forbidden));
+    }
+
+    let mut errors: Vec<Error> = Vec::new();
+
+    if let Some(ref form_data) = form.value {
+        let sender = &user_session.user.as_ref().unwrap().username;
+        let email = mailer.render(
+            form_data.recipient.trim(),
+            "Fumohouse test email",
+            "test",
+            &TestEmail { sender },
+        );
+
+        match email {
+            Ok(email) => match conn.run(move |c| OutboxEmail::queue(c, &email)).await {
+                Ok(id) => {
+                    info!("admin: {} queued test email {}", sender, id);
+                    return Ok(Redirect::to(uri!("/admin/email")));
+                }
+                Err(err) => {
+                    error!("admin: failed to queue test email: {}", err);
+                    errors.push(SiteMessages::GenericError.into());
+                }
+            },
+            Err(MailError::Address(_)) => errors.push(SiteMessages::EmailAddressInvalid.into()),
+            Err(err) => {
+                error!("admin: failed to render test email: {}", err);
+                errors.push(SiteMessages::GenericError.into());
+            }
+        }
+    }
+
+    let (counts, emails) = list_emails(&conn).await.map_err(Err)?;
+
+    form.context.push_errors(errors);
+
+    Err(Ok(Template::render(
+        "admin/email",
+        EmailContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            counts,
+            emails,
+        },
+    )))
+}
+
+/// Gives a failed email another full set of attempts.
+#[post("/email/<email_id>/retry")]
+async fn email_retry(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    email_id: i64,
+) -> Result<Redirect, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let retried = conn
+        .run(move |c| OutboxEmail::retry(c, email_id))
+        .await
+        .map_err(|err| {
+            error!("admin: failed to retry email: {}", err);
+            Status::InternalServerError
+        })?;
+
+    if !retried {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Redirect::to(uri!("/admin/email")))
+}
+
+#[derive(Serialize)]
+struct JobsContext<'a> {
+    base: BaseData<'a>,
+    jobs: Vec<Job>,
+    statuses: &'static [JobStatus],
+    status: Option<JobStatus>,
+    pagination: Pagination,
+}
+
+/// Every job, newest first, optionally only those with one status.
+#[get("/jobs?<status>&<page>")]
+async fn jobs_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    status: Option<JobStatus>,
+    page: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let (jobs, pagination) = conn
+        .run(move |c| {
+            let count = Job::count(c, status)?;
+            let pagination = Pagination::new(page, JOBS_PER_PAGE, count);
+            let jobs = Job::list(c, status, pagination.limit(), pagination.offset())?;
+
+            QueryResult::Ok((jobs, pagination))
+        })
+        .await
+        .map_err(|err| {
+            error!("admin: failed to list jobs: {}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Template::render(
+        "admin/jobs",
+        JobsContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            jobs,
+            statuses: JobStatus::ALL,
+            status,
+            pagination,
+        },
+    ))
+}
+
+/// Gives a dead job another full set of attempts.
+#[post("/jobs/<job_id>/retry")]
+async fn job_retry(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    job_id: i64,
+) -> Result<Redirect, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    let retried = conn
+        .run(move |c| Job::retry(c, job_id))
+        .await
+        .map_err(|err| {
+            error!("admin: failed to retry job: {}", err);
+            Status::InternalServerError
+        })?;
+
+    if !retried {
+        return Err(Status::NotFound);
+    }
+
+    Ok(Redirect::to(uri!("/admin/jobs")))
+}
+
+/// Prunes finished jobs now rather than waiting for the daily run.
+#[post("/jobs/prune")]
+async fn jobs_prune(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+) -> Result<Redirect, Status> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Status::Forbidden);
+    }
+
+    conn.run(|c| jobs::enqueue(c, &PruneJobs, None))
+        .await
+        .map_err(|err: JobError| {
+            error!("admin: failed to queue job: {}", err);
+            Status::InternalServerError
+        })?;
+
+    Ok(Redirect::to(uri!("/admin/jobs")))
+}
@@ -0,0 +1,99 @@
+use crate::{
+    db::FumohouseDb,
+    util::{self, markdown::PageCache, ApiSession, LoginError, Policy, SessionUtils},
+};
+use argon2::Argon2;
+use chrono::{DateTime, Utc};
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    Route, State,
+};
+use std::net::IpAddr;
+
+pub fn routes() -> Vec<Route> {
+    routes![login, logout]
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct LoginRequest {
+    username: String,
+    password: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct LoginResponse {
+    user_id: i64,
+    username: String,
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// Responds with 403 if the user has yet to accept new versions of the
+/// required policies, which they can only do by logging in on the site.
+#[post("/login", data = "<body>")]
+async fn login(
+    body: Json<LoginRequest>,
+    argon: &State<Argon2<'_>>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+    ip: Option<IpAddr>,
+) -> Result<Json<LoginResponse>, Status> {
+    let user = match util::authenticate(&conn, argon, ip, &body.username, &body.password).await {
+        Ok(user) => user,
+        Err(LoginError::InvalidCredentials) => return Err(Status::Unauthorized),
+        Err(LoginError::Throttled) => return Err(Status::TooManyRequests),
+        Err(LoginError::Database(err)) => {
+            error!("api login: failed to check credentials: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let policies = pages.required_policies();
+    let user_id = user.id;
+
+    match conn
+        .run(move |c| Policy::outstanding(c, user_id, &policies))
+        .await
+    {
+        Ok(outstanding) if !outstanding.is_empty() => return Err(Status::Forbidden),
+        Ok(_) => (),
+        Err(err) => {
+            error!("api login: failed to check policy acceptance: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    }
+
+    match SessionUtils::begin_api_session(&user, &conn).await {
+        Ok((token, session)) => {
+            info!("api login: new login: {}", user.username);
+
+            Ok(Json(LoginResponse {
+                user_id: user.id,
+                username: user.username,
+                token,
+                expires_at: session.expires_at,
+            }))
+        }
+        Err(err) => {
+            error!("api login: failed to start user session: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[post("/logout")]
+async fn logout(api_session: ApiSession, conn: FumohouseDb) -> Status {
+    match SessionUtils::end_api_session(&conn, &api_session.session).await {
+        Ok(_) => {
+            info!("api logout: {} logged out", api_session.user.username);
+            Status::NoContent
+        }
+        Err(err) => {
+            error!("api logout: failed to end user session: {}", err);
+            Status::InternalServerError
+        }
+    }
+}
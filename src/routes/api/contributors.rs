@@ -0,0 +1,61 @@
+use crate::{
+    db::{models::ContributorSignature, FumohouseDb},
+    util::{markdown::PageCache, policy::CONTRIBUTOR_AGREEMENT},
+};
+use chrono::{DateTime, Utc};
+use rocket::{
+    http::Status,
+    serde::{json::Json, Serialize},
+    Route, State,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![status]
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct SignatureStatus {
+    github_handle: String,
+    signed: bool,
+    /// Whether the latest signature is of the current version of the agreement
+    current: bool,
+    version: Option<String>,
+    signed_at: Option<DateTime<Utc>>,
+    current_version: String,
+}
+
+/// Queried by the GitHub bot to check whether a pull request's author
+/// has signed the contributor agreement. Never exposes legal names.
+#[get("/<github_handle>")]
+async fn status(
+    github_handle: String,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Json<SignatureStatus>, Status> {
+    let agreement = pages
+        .policy(CONTRIBUTOR_AGREEMENT)
+        .ok_or(Status::NotFound)?;
+
+    let handle = github_handle.clone();
+    let signature = conn
+        .run(move |c| ContributorSignature::latest_for_handle(c, &handle))
+        .await
+        .map_err(|err| {
+            error!("contributors: failed to find signature: {}", err);
+            Status::InternalServerError
+        })?;
+
+    let current = signature
+        .as_ref()
+        .is_some_and(|s| crate::util::to_hex(&s.document_sha256) == agreement.sha256);
+
+    Ok(Json(SignatureStatus {
+        github_handle,
+        signed: signature.is_some(),
+        current,
+        version: signature.as_ref().map(|s| s.version.clone()),
+        signed_at: signature.map(|s| s.signed_at),
+        current_version: agreement.version,
+    }))
+}
@@ -0,0 +1,50 @@
+use crate::db::{migrations, FumohouseDb};
+use rocket::{
+    http::Status,
+    serde::{json::Json, Serialize},
+    Route,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![health]
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Health {
+    /// `ok`, `database_unavailable` or `schema_outdated`
+    status: &'static str,
+    /// The version of the latest migration applied to the database
+    schema_version: Option<String>,
+    /// The version of the latest migration built into the site
+    expected_schema_version: Option<&'static str>,
+}
+
+/// For load balancers and monitoring. Responds with 503 if the database
+/// can't be reached or its schema is older than the site expects.
+#[get("/")]
+async fn health(conn: Option<FumohouseDb>) -> (Status, Json<Health>) {
+    let version = match conn {
+        Some(conn) => conn.run(|c| migrations::schema_version(c)).await.ok(),
+        None => None,
+    };
+
+    let expected = migrations::latest_version();
+
+    let (status, description) = match version {
+        None => (Status::ServiceUnavailable, "database_unavailable"),
+        Some(ref version) if version.as_deref() < expected => {
+            (Status::ServiceUnavailable, "schema_outdated")
+        }
+        Some(_) => (Status::Ok, "ok"),
+    };
+
+    (
+        status,
+        Json(Health {
+            status: description,
+            schema_version: version.flatten(),
+            expected_schema_version: expected,
+        }),
+    )
+}
@@ -0,0 +1,83 @@
+use crate::{
+    db::{models::Notification, FumohouseDb},
+    util::{LiveEvents, Topic, UserSession},
+};
+use rocket::{
+    http::Status,
+    response::stream::{Event, EventStream},
+    serde::Serialize,
+    tokio::{select, sync::broadcast::error::RecvError},
+    Route, Shutdown, State,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![live]
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Unread {
+    unread_count: i64,
+}
+
+/// Server-sent events for the given topics, or every topic if none are given.
+/// Each event is named after its topic. Subscribing to notifications starts
+/// the stream with an `unread` event holding the unread count.
+///
+/// Works with both session cookies and bearer tokens, since it only reads.
+#[get("/?<topics>")]
+async fn live(
+    user_session: UserSession,
+    conn: FumohouseDb,
+    events: &State<LiveEvents>,
+    topics: Vec<Topic>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    let user_id = user_session.user.ok_or(Status::Unauthorized)?.id;
+
+    let topics = if topics.is_empty() {
+        Topic::ALL.to_vec()
+    } else {
+        topics
+    };
+
+    // Subscribed before counting, so nothing sent in between is missed
+    let mut receiver = events.subscribe();
+
+    let unread_count = if topics.contains(&Topic::Notifications) {
+        let count = conn
+            .run(move |c| Notification::unread_count(c, user_id))
+            .await
+            .map_err(|err| {
+                error!("live: failed to count notifications: {}", err);
+                Status::InternalServerError
+            })?;
+
+        Some(count)
+    } else {
+        None
+    };
+
+    Ok(EventStream! {
+        if let Some(unread_count) = unread_count {
+            yield Event::json(&Unread { unread_count }).event("unread");
+        }
+
+        loop {
+            let event = select! {
+                event = receiver.recv() => event,
+                _ = &mut shutdown => break,
+            };
+
+            match event {
+                Ok(event) if topics.contains(&event.topic) && event.visible_to(user_id) => {
+                    yield Event::json(&event.data).event(event.topic.as_str());
+                }
+                Ok(_) => {}
+                // Clients should catch up through the other endpoints
+                Err(RecvError::Lagged(_)) => yield Event::empty().event("lagged"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
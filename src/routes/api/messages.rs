@@ -0,0 +1,396 @@
+use crate::{
+    db::{
+        models::{
+            BlockedUser, Conversation, ConversationMember, ConversationSummary, Message,
+            MessageRange, MessageReport, User, UserBlock,
+        },
+        FumohouseDb,
+    },
+    util::{
+        messaging::{self, MessagingError, RenderedMessage, MAX_MESSAGE_LENGTH},
+        ApiSession, Pagination,
+    },
+};
+use diesel::{result::Error as DieselError, OptionalExtension};
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    Route,
+};
+
+const CONVERSATIONS_PER_PAGE: i64 = 25;
+const MAX_MESSAGES: i64 = 100;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        list,
+        start,
+        conversation,
+        send,
+        read,
+        leave,
+        report,
+        blocks,
+        block,
+        unblock
+    ]
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("messages: database error: {}", err);
+    Status::InternalServerError
+}
+
+fn messaging_error(err: MessagingError) -> Status {
+    match err {
+        MessagingError::Banned | MessagingError::Blocked => Status::Forbidden,
+        MessagingError::RateLimited => Status::TooManyRequests,
+        MessagingError::UnknownUser(_)
+        | MessagingError::NoRecipients
+        | MessagingError::TooManyMembers => Status::UnprocessableEntity,
+        MessagingError::Database(err) => db_error(err),
+    }
+}
+
+fn valid_content(content: &str) -> bool {
+    !content.trim().is_empty() && content.len() <= MAX_MESSAGE_LENGTH
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ConversationList {
+    unread_count: i64,
+    /// Most recently active first
+    conversations: Vec<ConversationSummary>,
+    pagination: Pagination,
+}
+
+#[get("/?<page>")]
+async fn list(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    page: Option<i64>,
+) -> Result<Json<ConversationList>, Status> {
+    let user_id = api_session.user.id;
+
+    let list = conn
+        .run(move |c| {
+            let count = Conversation::count_for_user(c, user_id)?;
+            let pagination = Pagination::new(page, CONVERSATIONS_PER_PAGE, count);
+
+            Ok(ConversationList {
+                unread_count: Conversation::unread_count(c, user_id)?,
+                conversations: Conversation::for_user(
+                    c,
+                    user_id,
+                    pagination.limit(),
+                    pagination.offset(),
+                )?,
+                pagination,
+            })
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(list))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct StartRequest {
+    /// Usernames. More than one starts a group.
+    to: Vec<String>,
+    /// Only used by groups
+    title: Option<String>,
+    content: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct StartResponse {
+    conversation: Conversation,
+    message: RenderedMessage,
+}
+
+/// Messaging a single user carries on the conversation they already have.
+#[post("/", data = "<body>")]
+async fn start(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    body: Json<StartRequest>,
+) -> Result<Json<StartResponse>, Status> {
+    let body = body.into_inner();
+
+    let title_valid = body.title.as_ref().is_none_or(|t| t.len() <= 64);
+
+    if !valid_content(&body.content) || !title_valid {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let user = api_session.user;
+
+    let (conversation, message) = conn
+        .run(move |c| {
+            messaging::start(c, &user, &body.to, body.title.as_deref(), &body.content)
+        })
+        .await
+        .map_err(messaging_error)?;
+
+    Ok(Json(StartResponse {
+        conversation,
+        message: RenderedMessage::new(message, &[]),
+    }))
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ConversationResponse {
+    conversation: Conversation,
+    /// Each member's `last_read_id` is their read receipt
+    members: Vec<ConversationMember>,
+    /// Oldest first
+    messages: Vec<RenderedMessage>,
+}
+
+/// The newest messages, those before `before` when paging back, or those
+/// after `after` when catching up. Viewing doesn't mark anything as read.
+#[get("/<conversation_id>?<before>&<after>")]
+async fn conversation(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    before: Option<i64>,
+    after: Option<i64>,
+) -> Result<Json<ConversationResponse>, Status> {
+    let user_id = api_session.user.id;
+
+    let range = match (before, after) {
+        (Some(_), Some(_)) => return Err(Status::UnprocessableEntity),
+        (Some(id), None) => MessageRange::Before(id),
+        (None, Some(id)) => MessageRange::After(id),
+        (None, None) => MessageRange::Latest,
+    };
+
+    let response = conn
+        .run(move |c| {
+            let conversation = match Conversation::find_for_member(c, conversation_id, user_id)? {
+                Some(conversation) => conversation,
+                None => return Ok(None),
+            };
+
+            let blocked = UserBlock::blocked_by(c, user_id)?;
+            let messages = Message::for_conversation(c, conversation_id, range, MAX_MESSAGES)?
+                .into_iter()
+                .map(|message| RenderedMessage::new(message, &blocked))
+                .collect();
+
+            Ok(Some(ConversationResponse {
+                members: Conversation::members(c, conversation_id)?,
+                conversation,
+                messages,
+            }))
+        })
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct SendRequest {
+    content: String,
+}
+
+#[post("/<conversation_id>", data = "<body>")]
+async fn send(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    body: Json<SendRequest>,
+) -> Result<Json<RenderedMessage>, Status> {
+    let content = body.into_inner().content;
+
+    if !valid_content(&content) {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let user = api_session.user;
+
+    let message = conn
+        .run(move |c| {
+            let conversation = match Conversation::find_for_member(c, conversation_id, user.id)? {
+                Some(conversation) => conversation,
+                None => return Ok(None),
+            };
+
+            messaging::send(c, &conversation, &user, &content).map(Some)
+        })
+        .await
+        .map_err(messaging_error)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(RenderedMessage::new(message, &[])))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ReadRequest {
+    /// Leaving this out marks every message as read
+    message_id: Option<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ReadResponse {
+    /// Across every conversation
+    unread_count: i64,
+}
+
+#[post("/<conversation_id>/read", data = "<body>")]
+async fn read(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    body: Json<ReadRequest>,
+) -> Result<Json<ReadResponse>, Status> {
+    let user_id = api_session.user.id;
+    let message_id = body.into_inner().message_id;
+
+    let unread_count = conn
+        .run(move |c| {
+            if Conversation::find_for_member(c, conversation_id, user_id)?.is_none() {
+                return Ok(None);
+            }
+
+            // Later messages would never count as unread if this went past them
+            let latest = Message::latest_id(c, conversation_id)?;
+            let message_id = message_id.map_or(latest, |id| id.min(latest));
+
+            Conversation::mark_read(c, conversation_id, user_id, message_id)?;
+            Conversation::unread_count(c, user_id).map(Some)
+        })
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Json(ReadResponse { unread_count }))
+}
+
+/// Only groups can be left.
+#[post("/<conversation_id>/leave")]
+async fn leave(api_session: ApiSession, conn: FumohouseDb, conversation_id: i64) -> Status {
+    let user_id = api_session.user.id;
+
+    let result = conn
+        .run(move |c| match Conversation::find_for_member(c, conversation_id, user_id)? {
+            Some(conversation) if conversation.is_group => {
+                Conversation::leave(c, conversation_id, user_id).map(|_| Status::NoContent)
+            }
+            Some(_) => Ok(Status::UnprocessableEntity),
+            None => Ok(Status::NotFound),
+        })
+        .await;
+
+    result.unwrap_or_else(db_error)
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ReportRequest {
+    reason: String,
+}
+
+/// Reports someone else's message to the moderators. Reporting the same
+/// message twice is a conflict.
+#[post("/<conversation_id>/report/<message_id>", data = "<body>")]
+async fn report(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    message_id: i64,
+    body: Json<ReportRequest>,
+) -> Status {
+    let user_id = api_session.user.id;
+    let reason = body.into_inner().reason.trim().to_string();
+
+    if reason.is_empty() || reason.len() > 512 {
+        return Status::UnprocessableEntity;
+    }
+
+    let result = conn
+        .run(move |c| {
+            if Conversation::find_for_member(c, conversation_id, user_id)?.is_none() {
+                return Ok(Status::NotFound);
+            }
+
+            match Message::find(c, message_id)? {
+                Some(message)
+                    if message.conversation_id == conversation_id
+                        && message.author_id != user_id =>
+                {
+                    match MessageReport::create(c, &message, user_id, &reason)? {
+                        true => Ok(Status::NoContent),
+                        false => Ok(Status::Conflict),
+                    }
+                }
+                _ => Ok(Status::NotFound),
+            }
+        })
+        .await;
+
+    result.unwrap_or_else(db_error)
+}
+
+#[get("/blocks")]
+async fn blocks(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+) -> Result<Json<Vec<BlockedUser>>, Status> {
+    let user_id = api_session.user.id;
+
+    conn.run(move |c| UserBlock::list(c, user_id))
+        .await
+        .map(Json)
+        .map_err(db_error)
+}
+
+async fn set_blocked(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    username: String,
+    blocked: bool,
+) -> Status {
+    let blocker_id = api_session.user.id;
+
+    let result = conn
+        .run(move |c| {
+            let user = match User::find(c, &username).optional()? {
+                Some(user) if user.id != blocker_id => user,
+                Some(_) => return Ok(Status::UnprocessableEntity),
+                None => return Ok(Status::NotFound),
+            };
+
+            if blocked {
+                UserBlock::block(c, blocker_id, user.id)?;
+            } else {
+                UserBlock::unblock(c, blocker_id, user.id)?;
+            }
+
+            Ok(Status::NoContent)
+        })
+        .await;
+
+    result.unwrap_or_else(db_error)
+}
+
+#[put("/blocks/<username>")]
+async fn block(api_session: ApiSession, conn: FumohouseDb, username: String) -> Status {
+    set_blocked(api_session, conn, username, true).await
+}
+
+#[delete("/blocks/<username>")]
+async fn unblock(api_session: ApiSession, conn: FumohouseDb, username: String) -> Status {
+    set_blocked(api_session, conn, username, false).await
+}
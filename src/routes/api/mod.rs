@@ -0,0 +1,44 @@
+use rocket::{
+    http::Status,
+    serde::{json::Json, Serialize},
+    Catcher, Request,
+};
+
+pub mod auth;
+pub mod contributors;
+pub mod health;
+pub mod live;
+pub mod messages;
+pub mod notifications;
+pub mod presence;
+pub mod releases;
+pub mod servers;
+pub mod update;
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ApiErrorBody {
+    code: u16,
+    reason: &'static str,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ApiError {
+    error: ApiErrorBody,
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![not_found]
+}
+
+/// Keeps API 404s as JSON instead of the site's not found page.
+#[catch(404)]
+fn not_found(status: Status, _request: &Request<'_>) -> Json<ApiError> {
+    Json(ApiError {
+        error: ApiErrorBody {
+            code: status.code,
+            reason: status.reason_lossy(),
+        },
+    })
+}
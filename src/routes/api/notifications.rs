@@ -0,0 +1,140 @@
+use crate::{
+    db::{
+        models::{Notification, NotificationInfo},
+        FumohouseDb,
+    },
+    util::{ApiSession, LiveEvents, Topic},
+};
+use diesel::result::Error as DieselError;
+use rocket::{
+    http::Status,
+    response::stream::{Event, EventStream},
+    serde::{json::Json, Deserialize, Serialize},
+    tokio::{select, sync::broadcast::error::RecvError},
+    Route, Shutdown, State,
+};
+
+const MAX_NOTIFICATIONS: i64 = 50;
+
+pub fn routes() -> Vec<Route> {
+    routes![list, read, stream]
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("notifications: database error: {}", err);
+    Status::InternalServerError
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct NotificationList {
+    unread_count: i64,
+    /// Newest first
+    notifications: Vec<NotificationInfo>,
+}
+
+/// Polled by the game client. Passing the id of the newest notification the
+/// client has seen as `after` only returns newer ones.
+#[get("/?<after>")]
+async fn list(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    after: Option<i64>,
+) -> Result<Json<NotificationList>, Status> {
+    let user_id = api_session.user.id;
+
+    let list = conn
+        .run(move |c| {
+            let notifications = match after {
+                Some(after) => Notification::since(c, user_id, after, MAX_NOTIFICATIONS)?,
+                None => Notification::for_user(c, user_id, MAX_NOTIFICATIONS, 0)?,
+            };
+
+            Ok(NotificationList {
+                unread_count: Notification::unread_count(c, user_id)?,
+                notifications,
+            })
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(list))
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct ReadRequest {
+    /// Leaving this out marks every notification as read
+    ids: Option<Vec<i64>>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ReadResponse {
+    unread_count: i64,
+}
+
+#[post("/read", data = "<body>")]
+async fn read(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    body: Json<ReadRequest>,
+) -> Result<Json<ReadResponse>, Status> {
+    let user_id = api_session.user.id;
+    let ids = body.into_inner().ids;
+
+    let unread_count = conn
+        .run(move |c| {
+            match ids {
+                Some(ids) => Notification::mark_read(c, user_id, &ids)?,
+                None => Notification::mark_all_read(c, user_id)?,
+            };
+
+            Notification::unread_count(c, user_id)
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Json(ReadResponse { unread_count }))
+}
+
+/// Kept for clients from before `/api/v1/live`: the notifications topic of
+/// the live stream, with each event named `notification`. Starts with an
+/// `unread` event holding the unread count.
+#[get("/stream")]
+async fn stream(
+    api_session: ApiSession,
+    conn: FumohouseDb,
+    events: &State<LiveEvents>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![], Status> {
+    let user_id = api_session.user.id;
+
+    // Subscribed before counting, so nothing sent in between is missed
+    let mut receiver = events.subscribe();
+
+    let unread_count = conn
+        .run(move |c| Notification::unread_count(c, user_id))
+        .await
+        .map_err(db_error)?;
+
+    Ok(EventStream! {
+        yield Event::json(&ReadResponse { unread_count }).event("unread");
+
+        loop {
+            let event = select! {
+                event = receiver.recv() => event,
+                _ = &mut shutdown => break,
+            };
+
+            match event {
+                Ok(event) if event.topic == Topic::Notifications && event.visible_to(user_id) => {
+                    yield Event::json(&event.data).event("notification");
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(_)) => yield Event::empty().event("lagged"),
+                Err(RecvError::Closed) => break,
+            }
+        }
+    })
+}
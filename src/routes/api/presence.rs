@@ -0,0 +1,53 @@
+use crate::{
+    db::{
+        models::{Presence, PresenceInfo, User},
+        FumohouseDb,
+    },
+    util::{ApiSession, UserSession},
+};
+use diesel::result::Error as DieselError;
+use rocket::{http::Status, serde::json::Json, Route};
+
+pub fn routes() -> Vec<Route> {
+    routes![update, get]
+}
+
+/// Called periodically by the game client to mark the user as online.
+/// Which server the user is in is reported by the server itself.
+#[post("/")]
+async fn update(api_session: ApiSession, conn: FumohouseDb) -> Status {
+    let user_id = api_session.user.id;
+
+    match conn.run(move |c| Presence::touch(c, user_id)).await {
+        Ok(_) => Status::NoContent,
+        Err(err) => {
+            error!("presence: update failed: {}", err);
+            Status::InternalServerError
+        }
+    }
+}
+
+#[get("/<username>")]
+async fn get(
+    username: String,
+    user_session: UserSession,
+    conn: FumohouseDb,
+) -> Result<Json<PresenceInfo>, Status> {
+    let viewer_id = user_session.user.map(|u| u.id);
+
+    let result = conn
+        .run(move |c| {
+            let user = User::find(c, &username)?;
+            Presence::info(c, &user, viewer_id)
+        })
+        .await;
+
+    match result {
+        Ok(info) => Ok(Json(info)),
+        Err(DieselError::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("presence: lookup failed: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{
+    db::{
+        models::{Channel, Platform, Release},
+        FumohouseDb,
+    },
+    util::UserSession,
+};
+use rocket::{
+    http::Status,
+    serde::{json::Json, Serialize},
+    Route,
+};
+
+pub fn routes() -> Vec<Route> {
+    routes![latest]
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct Manifest {
+    channel: Channel,
+    releases: Vec<Release>,
+}
+
+/// Polled by the launcher to find the newest build in a channel.
+#[get("/<channel>/latest?<platform>")]
+async fn latest(
+    channel: Channel,
+    platform: Option<Platform>,
+    user_session: UserSession,
+    conn: FumohouseDb,
+) -> Result<Json<Manifest>, Status> {
+    if !channel.visible_to(user_session.user.as_ref()) {
+        return Err(Status::NotFound);
+    }
+
+    match conn.run(move |c| Release::latest(c, channel)).await {
+        Ok(mut releases) => {
+            if let Some(platform) = platform {
+                releases.retain(|r| r.platform == platform);
+            }
+
+            Ok(Json(Manifest { channel, releases }))
+        }
+        Err(err) => {
+            error!("releases: failed to build manifest: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use crate::{
+    db::{
+        models::{
+            GameServer, NewGameServer, NewJoinTicket, Presence, PresenceInfo, PresenceStatus,
+            ServerFilter, User,
+        },
+        FumohouseDb,
+    },
+    util::{
+        game_server::{HEARTBEAT_INTERVAL, JOIN_TICKET_EXPIRY},
+        ApiSession, GameServerAuth, GameServerUtils, LiveEvent, Pagination, RegistrationAuth,
+        Topic,
+    },
+};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
+use rocket::{
+    http::Status,
+    serde::{json::Json, Deserialize, Serialize},
+    Route,
+};
+
+const MAX_TAGS: usize = 16;
+pub const SERVERS_PER_PAGE: i64 = 25;
+
+pub fn routes() -> Vec<Route> {
+    routes![list, register, heartbeat, deregister, presence, join, verify]
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct ServerList {
+    servers: Vec<GameServer>,
+    pagination: Pagination,
+}
+
+#[get("/?<page>&<filter..>")]
+async fn list(
+    page: Option<i64>,
+    filter: ServerFilter,
+    conn: FumohouseDb,
+) -> Result<Json<ServerList>, Status> {
+    let result = conn
+        .run(move |c| {
+            let total = GameServer::count(c, &filter)?;
+            let pagination = Pagination::new(page, SERVERS_PER_PAGE, total);
+            let servers =
+                GameServer::list(c, &filter, pagination.offset(), pagination.limit())?;
+
+            Ok::<_, DieselError>(ServerList {
+                servers,
+                pagination,
+            })
+        })
+        .await;
+
+    match result {
+        Ok(list) => Ok(Json(list)),
+        Err(err) => {
+            error!("game servers: listing failed: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct RegisterRequest {
+    name: String,
+    address: String,
+    port: u16,
+    version: String,
+    max_players: u16,
+    region: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl RegisterRequest {
+    fn is_valid(&self) -> bool {
+        (1..=64).contains(&self.name.len())
+            && (1..=255).contains(&self.address.len())
+            && (1..=32).contains(&self.version.len())
+            && (1..=32).contains(&self.region.len())
+            && self.max_players > 0
+            && self.tags.len() <= MAX_TAGS
+            && self.tags.iter().all(|t| (1..=32).contains(&t.len()))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct RegisterResponse {
+    id: i64,
+    token: String,
+    heartbeat_interval: u64,
+}
+
+#[post("/register", data = "<body>")]
+async fn register(
+    _auth: RegistrationAuth,
+    body: Json<RegisterRequest>,
+    conn: FumohouseDb,
+) -> Result<Json<RegisterResponse>, Status> {
+    use crate::db::schema::game_servers;
+
+    if !body.is_valid() {
+        return Err(Status::UnprocessableEntity);
+    }
+
+    let (token, hash) = GameServerUtils::new_token();
+    let body = body.into_inner();
+
+    let result = conn
+        .run(move |c| {
+            let new_server = NewGameServer {
+                name: &body.name,
+                address: &body.address,
+                port: body.port.into(),
+                version: &body.version,
+                max_players: body.max_players.into(),
+                region: &body.region,
+                tags: &body.tags,
+                token: &hash,
+                online: true,
+                last_heartbeat: Utc::now(),
+            };
+
+            // Servers re-register on every startup, so an existing entry
+            // for the same address is taken over and issued a new token.
+            let server = diesel::insert_into(game_servers::table)
+                .values(&new_server)
+                .on_conflict((game_servers::address, game_servers::port))
+                .do_update()
+                .set(&new_server)
+                .get_result::<GameServer>(c)?;
+
+            LiveEvent::new(Topic::Servers, None, &server).publish(c);
+            Ok::<_, DieselError>(server)
+        })
+        .await;
+
+    match result {
+        Ok(server) => {
+            info!(
+                "game servers: registered {} ({}:{})",
+                server.name, server.address, server.port
+            );
+
+            Ok(Json(RegisterResponse {
+                id: server.id,
+                token,
+                heartbeat_interval: HEARTBEAT_INTERVAL,
+            }))
+        }
+        Err(err) => {
+            error!("game servers: registration failed: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct HeartbeatRequest {
+    player_count: u16,
+}
+
+#[post("/heartbeat", data = "<body>")]
+async fn heartbeat(
+    auth: GameServerAuth,
+    body: Json<HeartbeatRequest>,
+    conn: FumohouseDb,
+) -> Status {
+    use crate::db::schema::game_servers::dsl::*;
+
+    let server = auth.server;
+    let count = i32::from(body.player_count);
+
+    if count > server.max_players {
+        return Status::UnprocessableEntity;
+    }
+
+    let result = conn
+        .run(move |c| {
+            let updated = diesel::update(game_servers.filter(id.eq(server.id)))
+                .set((
+                    player_count.eq(count),
+                    online.eq(true),
+                    last_heartbeat.eq(Utc::now()),
+                ))
+                .get_result::<GameServer>(c)?;
+
+            // Most heartbeats change nothing anyone is shown
+            if updated.player_count != server.player_count || !server.online {
+                LiveEvent::new(Topic::Servers, None, &updated).publish(c);
+            }
+
+            Presence::refresh_server(c, server.id)
+        })
+        .await;
+
+    match result {
+        Ok(_) => Status::NoContent,
+        Err(err) => {
+            error!("game servers: heartbeat failed: {}", err);
+            Status::InternalServerError
+        }
+    }
+}
+
+#[post("/deregister")]
+async fn deregister(auth: GameServerAuth, conn: FumohouseDb) -> Status {
+    use crate::db::schema::game_servers::dsl::*;
+
+    let server = auth.server;
+
+    let result = conn
+        .run(move |c| {
+            let updated = diesel::update(game_servers.filter(id.eq(server.id)))
+                .set((online.eq(false), player_count.eq(0)))
+                .get_result::<GameServer>(c)?;
+
+            LiveEvent::new(Topic::Servers, None, &updated).publish(c);
+            Presence::clear_server(c, server.id)
+        })
+        .await;
+
+    match result {
+        Ok(_) => {
+            info!("game servers: {} went offline", server.name);
+            Status::NoContent
+        }
+        Err(err) => {
+            error!("game servers: deregistration failed: {}", err);
+            Status::InternalServerError
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct PresenceRequest {
+    #[serde(default)]
+    joined: Vec<i64>,
+    #[serde(default)]
+    left: Vec<i64>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct PresenceUpdate {
+    username: String,
+    #[serde(flatten)]
+    info: PresenceInfo,
+}
+
+/// Tells subscribers where a user is now, if the user shares that.
+fn publish_presence(c: &PgConnection, user_id: i64) -> QueryResult<()> {
+    let user = User::find_by_id(c, user_id)?;
+    let info = Presence::info(c, &user, None)?;
+
+    if info.status == PresenceStatus::Hidden {
+        return Ok(());
+    }
+
+    let update = PresenceUpdate {
+        username: user.username,
+        info,
+    };
+
+    LiveEvent::new(Topic::Presence, None, &update).publish(c);
+    Ok(())
+}
+
+/// Reports players joining or leaving a server, by user ID.
+#[post("/presence", data = "<body>")]
+async fn presence(
+    auth: GameServerAuth,
+    body: Json<PresenceRequest>,
+    conn: FumohouseDb,
+) -> Status {
+    let server_id = auth.server.id;
+    let body = body.into_inner();
+
+    let result = conn
+        .run(move |c| {
+            let c: &PgConnection = c;
+
+            // Applied in full or not at all
+            c.transaction(|| {
+                for user_id in &body.left {
+                    Presence::leave_server(c, *user_id, server_id)?;
+                }
+
+                for user_id in &body.joined {
+                    Presence::join_server(c, *user_id, server_id)?;
+                }
+
+                for user_id in body.left.iter().chain(&body.joined) {
+                    publish_presence(c, *user_id)?;
+                }
+
+                Ok::<_, DieselError>(())
+            })
+        })
+        .await;
+
+    match result {
+        Ok(_) => Status::NoContent,
+        // Unknown user IDs violate the foreign key constraint
+        Err(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) => {
+            Status::UnprocessableEntity
+        }
+        Err(err) => {
+            error!("game servers: presence update failed: {}", err);
+            Status::InternalServerError
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct JoinResponse {
+    ticket: String,
+    expires_at: DateTime<Utc>,
+    address: String,
+    port: i32,
+}
+
+/// Issues a single-use ticket the client hands to the game server it joins,
+/// so the server can verify who the player is.
+#[post("/<server_id>/join")]
+async fn join(
+    api_session: ApiSession,
+    server_id: i64,
+    conn: FumohouseDb,
+) -> Result<Json<JoinResponse>, Status> {
+    use crate::db::schema::{game_servers, join_tickets};
+
+    let user = api_session.user;
+
+    if user.banned {
+        return Err(Status::Forbidden);
+    }
+
+    let (ticket, hash) = GameServerUtils::new_join_ticket();
+    let user_id = user.id;
+    let expires_at = Utc::now() + Duration::seconds(JOIN_TICKET_EXPIRY);
+
+    let result = conn
+        .run(move |c| {
+            let server = game_servers::table
+                .find(server_id)
+                .filter(game_servers::online.eq(true))
+                .first::<GameServer>(c)?;
+
+            diesel::insert_into(join_tickets::table)
+                .values(&NewJoinTicket {
+                    user_id,
+                    server_id: server.id,
+                    ticket: &hash,
+                    expires_at,
+                })
+                .execute(c)?;
+
+            Ok::<_, DieselError>(server)
+        })
+        .await;
+
+    match result {
+        Ok(server) => Ok(Json(JoinResponse {
+            ticket,
+            expires_at,
+            address: server.address,
+            port: server.port,
+        })),
+        Err(DieselError::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("game servers: failed to issue join ticket: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct VerifyRequest {
+    ticket: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+struct VerifyResponse {
+    user_id: i64,
+    username: String,
+    banned: bool,
+}
+
+/// Consumes a join ticket presented by a connecting player.
+/// Tickets are only valid for the server they were issued for.
+#[post("/verify", data = "<body>")]
+async fn verify(
+    auth: GameServerAuth,
+    body: Json<VerifyRequest>,
+    conn: FumohouseDb,
+) -> Result<Json<VerifyResponse>, Status> {
+    use crate::db::schema::{join_tickets::dsl::*, users};
+
+    let hash = GameServerUtils::hash_join_ticket(&body.ticket);
+    let own_id = auth.server.id;
+
+    let result = conn
+        .run(move |c| {
+            let now = Utc::now();
+
+            let ticket_user_id = diesel::update(
+                join_tickets.filter(
+                    ticket
+                        .eq(hash)
+                        .and(server_id.eq(own_id))
+                        .and(used_at.is_null())
+                        .and(expires_at.gt(now)),
+                ),
+            )
+            .set(used_at.eq(now))
+            .returning(user_id)
+            .get_result::<i64>(c)?;
+
+            users::table.find(ticket_user_id).first::<User>(c)
+        })
+        .await;
+
+    match result {
+        Ok(user) => Ok(Json(VerifyResponse {
+            user_id: user.id,
+            username: user.username,
+            banned: user.banned,
+        })),
+        Err(DieselError::NotFound) => Err(Status::Unauthorized),
+        Err(err) => {
+            error!("game servers: failed to verify join ticket: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::{
+    db::{
+        models::{Channel, Platform, UpdateManifest},
+        FumohouseDb,
+    },
+    util::{
+        update::{SignedManifest, TrustedKey},
+        UpdateKeys, UserSession,
+    },
+};
+use rocket::{http::Status, serde::json::Json, Route, State};
+
+pub fn routes() -> Vec<Route> {
+    routes![keys, manifest]
+}
+
+/// Keys currently used to sign manifests. Launchers should only trust
+/// these through a verified manifest's `trusted_keys`, not this endpoint.
+#[get("/keys")]
+fn keys(update_keys: &State<UpdateKeys>) -> Json<&[TrustedKey]> {
+    Json(update_keys.keys())
+}
+
+/// The latest signed manifest for a channel and platform.
+#[get("/<channel>/<platform>")]
+async fn manifest(
+    channel: Channel,
+    platform: Platform,
+    user_session: UserSession,
+    conn: FumohouseDb,
+) -> Result<Json<SignedManifest>, Status> {
+    if !channel.visible_to(user_session.user.as_ref()) {
+        return Err(Status::NotFound);
+    }
+
+    match conn
+        .run(move |c| UpdateManifest::latest(c, channel, platform))
+        .await
+    {
+        Ok(Some(manifest)) => Ok(Json(SignedManifest {
+            key_id: manifest.key_id,
+            signature: manifest.signature,
+            manifest: manifest.manifest,
+        })),
+        Ok(None) => Err(Status::NotFound),
+        Err(err) => {
+            error!("update: failed to retrieve manifest: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
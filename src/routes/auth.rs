@@ -1,45 +1,71 @@
 use super::{BaseData, DefaultContext};
 use crate::{
     db::{
-        models::{NewUser, User},
+        models::{Invite, NewUser, User},
         FumohouseDb,
     },
-    util::{self, CaptchaVerifier, CsrfToken, CsrfVerify, SessionUtils, SiteMessages, UserSession},
+    util::{
+        self, markdown::PageCache, CaptchaVerifier, CsrfToken, CsrfVerify, InviteConfig,
+        LoginError, Policy, SessionUtils, SiteMessages, UserSession,
+    },
 };
 use argon2::Argon2;
-use diesel::{prelude::*, result::Error as DieselError};
+use chrono::{Duration, Utc};
+use diesel::{
+    prelude::*,
+    result::{DatabaseErrorKind, Error as DieselError},
+};
 use rocket::form::{Context, Contextual, Error, Form};
-use rocket::http::{CookieJar, Status};
+use rocket::http::{Cookie, CookieJar, Status};
 use rocket::response::Redirect;
+use rocket::serde::Serialize;
 use rocket::{Route, State};
 use rocket_dyn_templates::Template;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
 
 pub const PASSWORD_MIN_LENGTH: usize = 8;
 
-pub fn routes() -> Vec<Route> {
-    routes![register_get, register_post, login_get, login_post, logout]
-}
+/// Holds `<user id>:<expiry timestamp>:<password fingerprint>` for a login
+/// which is waiting on the user to accept new versions of the rules.
+const PENDING_LOGIN_COOKIE_NAME: &str = "fh_pending_login";
+const PENDING_LOGIN_EXPIRY: i64 = 15; // minutes
 
-fn valid_char(c: char) -> bool {
-    char::is_ascii_alphanumeric(&c)
-        || match c {
-            // a few symbols that are ok
-            ' ' => true,
-            '[' | ']' | '(' | ')' => true,
-            '-' | '_' => true,
-            _ => false,
-        }
+pub fn routes() -> Vec<Route> {
+    routes![
+        register_get,
+        register_post,
+        login_get,
+        login_post,
+        policies_get,
+        policies_post,
+        logout
+    ]
 }
 
 #[derive(FromForm)]
 struct RegisterForm<'a> {
-    #[field(validate = len(1..=32))]
-    #[field(validate = with(|u| u.chars().all(valid_char), SiteMessages::UsernameInvalid.description()))]
+    #[field(validate = len(1..=util::USERNAME_MAX_LENGTH))]
+    #[field(validate = with(|u| util::is_valid_username(u), SiteMessages::UsernameInvalid.description()))]
     username: &'a str,
     #[field(validate = len(PASSWORD_MIN_LENGTH..))]
     password: &'a str,
     #[field(name = "h-captcha-response")]
     captcha_response: &'a str,
+    /// Only checked if there are policies to accept
+    accept_policies: bool,
+    /// Only checked if registration is invite only
+    invite: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct PolicyContext<'a, 'b> {
+    base: BaseData<'a>,
+    captcha_site_key: Option<&'a str>,
+    invite_only: bool,
+    form_context: Option<&'a Context<'b>>,
+    /// Policies the user must accept
+    policies: &'a [Policy],
 }
 
 #[get("/register")]
@@ -47,6 +73,8 @@ fn register_get(
     user_session: UserSession,
     csrf: CsrfToken,
     captcha: &State<CaptchaVerifier>,
+    invites: &State<InviteConfig>,
+    pages: &State<PageCache>,
 ) -> Result<Template, Redirect> {
     if user_session.user.is_some() {
         return Err(Redirect::to(uri!("/")));
@@ -54,13 +82,12 @@ fn register_get(
 
     Ok(Template::render(
         "auth/register",
-        DefaultContext {
-            base: BaseData {
-                user: None,
-                csrf_token: &csrf.token,
-            },
+        PolicyContext {
+            base: BaseData::new(None, &csrf.token, pages),
             captcha_site_key: Some(&captcha.site_key),
+            invite_only: invites.invite_only,
             form_context: Some(&Context::default()),
+            policies: &pages.required_policies(),
         },
     ))
 }
@@ -69,10 +96,21 @@ async fn handle_register<'a>(
     conn: &FumohouseDb,
     argon: &Argon2<'_>,
     form_data: &RegisterForm<'a>,
+    invite_only: bool,
+    policies: Vec<Policy>,
     errors: &mut Vec<Error<'_>>,
 ) -> Option<User> {
     use crate::db::schema::users;
 
+    let invite = match form_data.invite.filter(|_| invite_only) {
+        Some(code) => Some(code.trim().to_string()),
+        None if invite_only => {
+            errors.push(SiteMessages::InviteInvalid.into());
+            return None;
+        }
+        None => None,
+    };
+
     let requested_username = form_data.username.to_string();
 
     let existing = conn.run(move |c| User::find(c, &requested_username)).await;
@@ -96,19 +134,50 @@ async fn handle_register<'a>(
 
     match hash_result {
         Ok(hash) => {
-            let new_user = conn
+            let result = conn
                 .run(move |c| {
-                    let new_user = NewUser {
-                        username: &requested_username,
-                        password: &hash.to_string(),
-                    };
-
-                    diesel::insert_into(users::table)
-                        .values(&new_user)
-                        .get_result::<User>(c)
+                    let c: &PgConnection = c;
+
+                    // Nobody is registered without their acceptance on record
+                    c.transaction(|| {
+                        let new_user = NewUser {
+                            username: &requested_username,
+                            password: &hash.to_string(),
+                        };
+
+                        let user = match invite {
+                            Some(code) => match Invite::redeem(c, &code, &new_user)? {
+                                Some(user) => user,
+                                None => return Ok(None),
+                            },
+                            None => diesel::insert_into(users::table)
+                                .values(&new_user)
+                                .get_result::<User>(c)?,
+                        };
+
+                        Policy::accept(c, user.id, &policies)?;
+
+                        Ok(Some(user))
+                    })
                 })
-                .await
-                .ok()?;
+                .await;
+
+            let new_user = match result {
+                Ok(Some(new_user)) => new_user,
+                Ok(None) => {
+                    errors.push(SiteMessages::InviteInvalid.into());
+                    return None;
+                }
+                Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                    errors.push(SiteMessages::UsernameInUse.into());
+                    return None;
+                }
+                Err(err) => {
+                    errors.push(SiteMessages::GenericError.into());
+                    error!("registration: failed to create user: {}", err);
+                    return None;
+                }
+            };
 
             info!("registration: new user: {}", new_user.username);
 
@@ -123,20 +192,38 @@ async fn handle_register<'a>(
     None
 }
 
+#[allow(clippy::too_many_arguments)]
 #[post("/register", data = "<form>")]
 async fn register_post<'a>(
     csrf: CsrfVerify,
     mut form: Form<Contextual<'a, RegisterForm<'a>>>,
     captcha: &State<CaptchaVerifier>,
+    invites: &State<InviteConfig>,
     argon: &State<Argon2<'_>>,
     conn: FumohouseDb,
     cookies: &CookieJar<'_>,
+    pages: &State<PageCache>,
 ) -> Result<Redirect, (Status, Template)> {
     // Errors are added all at once at the end of the request
     // to avoid issues with mutable references
     let mut errors = Vec::new();
+    let policies = pages.required_policies();
 
     if let Some(ref form_data) = form.value {
+        if !form_data.accept_policies && !policies.is_empty() {
+            errors.push(SiteMessages::PoliciesNotAccepted.into());
+            form.context.push_errors(errors);
+
+            return Err(register_template(
+                &csrf,
+                captcha,
+                invites,
+                &form.context,
+                &policies,
+                pages,
+            ));
+        }
+
         let captcha_success = captcha
             .verify(form_data.captcha_response)
             .await
@@ -148,7 +235,15 @@ async fn register_post<'a>(
             });
 
         if captcha_success {
-            let result = handle_register(&conn, &argon, form_data, &mut errors).await;
+            let result = handle_register(
+                &conn,
+                &argon,
+                form_data,
+                invites.invite_only,
+                policies.clone(),
+                &mut errors,
+            )
+            .await;
 
             if let Some(user) = result {
                 SessionUtils::begin_session(&user, &conn, cookies)
@@ -166,20 +261,37 @@ async fn register_post<'a>(
 
     form.context.push_errors(errors);
 
-    Err((
-        form.context.status(),
+    Err(register_template(
+        &csrf,
+        captcha,
+        invites,
+        &form.context,
+        &policies,
+        pages,
+    ))
+}
+
+fn register_template(
+    csrf: &CsrfVerify,
+    captcha: &CaptchaVerifier,
+    invites: &InviteConfig,
+    context: &Context<'_>,
+    policies: &[Policy],
+    pages: &PageCache,
+) -> (Status, Template) {
+    (
+        context.status(),
         Template::render(
             "auth/register",
-            DefaultContext {
-                base: BaseData {
-                    user: None,
-                    csrf_token: csrf.new_token(),
-                },
+            PolicyContext {
+                base: BaseData::new(None, csrf.new_token(), pages),
                 captcha_site_key: Some(&captcha.site_key),
-                form_context: Some(&form.context),
+                invite_only: invites.invite_only,
+                form_context: Some(context),
+                policies,
             },
         ),
-    ))
+    )
 }
 
 #[derive(FromForm)]
@@ -189,7 +301,11 @@ struct LoginForm<'a> {
 }
 
 #[get("/login")]
-async fn login_get(user_session: UserSession, csrf: CsrfToken) -> Result<Template, Redirect> {
+async fn login_get(
+    user_session: UserSession,
+    csrf: CsrfToken,
+    pages: &State<PageCache>,
+) -> Result<Template, Redirect> {
     if user_session.user.is_some() {
         return Err(Redirect::to(uri!("/")));
     }
@@ -197,32 +313,13 @@ async fn login_get(user_session: UserSession, csrf: CsrfToken) -> Result<Templat
     Ok(Template::render(
         "auth/login",
         DefaultContext {
-            base: BaseData {
-                user: None,
-                csrf_token: &csrf.token,
-            },
+            base: BaseData::new(None, &csrf.token, pages),
             captcha_site_key: None,
             form_context: Some(&Context::default()),
         },
     ))
 }
 
-async fn handle_login<'a>(
-    conn: &FumohouseDb,
-    argon: &Argon2<'_>,
-    form_data: &LoginForm<'a>,
-) -> Option<User> {
-    let username = form_data.username.to_string();
-    let user = conn.run(move |c| User::find(c, &username)).await.ok()?;
-
-    if user.verify_password(&argon, form_data.password).is_ok() {
-        info!("login: new login: {}", user.username);
-        return Some(user);
-    }
-
-    None
-}
-
 #[post("/login", data = "<form>")]
 async fn login_post<'a>(
     csrf: CsrfVerify,
@@ -230,14 +327,52 @@ async fn login_post<'a>(
     argon: &State<Argon2<'_>>,
     conn: FumohouseDb,
     cookies: &CookieJar<'_>,
+    pages: &State<PageCache>,
+    ip: Option<IpAddr>,
 ) -> Result<Redirect, (Status, Template)> {
     let mut errors: Vec<Error> = Vec::new();
 
     if let Some(ref form_data) = form.value {
-        let result = handle_login(&conn, &argon, form_data).await;
+        let result =
+            util::authenticate(&conn, argon, ip, form_data.username, form_data.password).await;
 
         match result {
-            Some(u) => {
+            Ok(u) => {
+                info!("login: new login: {}", u.username);
+
+                let policies = pages.required_policies();
+                let user_id = u.id;
+
+                let outstanding = conn
+                    .run(move |c| Policy::outstanding(c, user_id, &policies))
+                    .await;
+
+                match outstanding {
+                    // The session only begins once the new versions are accepted
+                    Ok(outstanding) if !outstanding.is_empty() => {
+                        let expiry = Utc::now() + Duration::minutes(PENDING_LOGIN_EXPIRY);
+                        cookies.add_private(Cookie::new(
+                            PENDING_LOGIN_COOKIE_NAME,
+                            format!(
+                                "{}:{}:{}",
+                                u.id,
+                                expiry.timestamp(),
+                                password_fingerprint(&u)
+                            ),
+                        ));
+
+                        return Ok(Redirect::to(uri!("/auth/policies")));
+                    }
+                    Ok(_) => (),
+                    Err(err) => {
+                        error!("login: failed to check policy acceptance: {}", err);
+                        errors.push(SiteMessages::GenericError.into());
+                        form.context.push_errors(errors);
+
+                        return Err(login_template(&csrf, &form.context, pages));
+                    }
+                }
+
                 SessionUtils::begin_session(&u, &conn, cookies)
                     .await
                     .unwrap_or_else(|err| {
@@ -246,28 +381,177 @@ async fn login_post<'a>(
 
                 return Ok(Redirect::to(uri!("/")));
             }
-            None => errors.push(SiteMessages::LoginFailed.into()),
+            Err(LoginError::InvalidCredentials) => errors.push(SiteMessages::LoginFailed.into()),
+            Err(LoginError::Throttled) => errors.push(SiteMessages::LoginThrottled.into()),
+            Err(LoginError::Database(err)) => {
+                error!("login: failed to check credentials: {}", err);
+                errors.push(SiteMessages::GenericError.into());
+            }
         }
     }
 
     form.context.push_errors(errors);
 
-    Err((
-        form.context.status(),
+    Err(login_template(&csrf, &form.context, pages))
+}
+
+fn login_template(
+    csrf: &CsrfVerify,
+    context: &Context<'_>,
+    pages: &PageCache,
+) -> (Status, Template) {
+    (
+        context.status(),
         Template::render(
             "auth/login",
             DefaultContext {
-                base: BaseData {
-                    user: None,
-                    csrf_token: csrf.new_token(),
-                },
-                form_context: Some(&form.context),
+                base: BaseData::new(None, csrf.new_token(), pages),
+                form_context: Some(context),
                 captcha_site_key: None,
             },
         ),
+    )
+}
+
+/// Ties a pending login to the password it was made with, so that it's
+/// void once the password changes.
+fn password_fingerprint(user: &User) -> String {
+    util::to_hex(&Sha256::digest(user.password.as_bytes()))
+}
+
+/// The user whose login is waiting on policy acceptance, if it hasn't
+/// expired and they can still log in the same way.
+async fn pending_login(conn: &FumohouseDb, cookies: &CookieJar<'_>) -> Option<User> {
+    let cookie = cookies.get_private(PENDING_LOGIN_COOKIE_NAME)?;
+    let mut parts = cookie.value().splitn(3, ':');
+    let (user_id, expiry, fingerprint) = (parts.next()?, parts.next()?, parts.next()?);
+
+    if expiry.parse::<i64>().ok()? < Utc::now().timestamp() {
+        cookies.remove_private(Cookie::named(PENDING_LOGIN_COOKIE_NAME));
+        return None;
+    }
+
+    let user_id = user_id.parse::<i64>().ok()?;
+    let user = conn.run(move |c| User::find_by_id(c, user_id)).await.ok();
+
+    match user {
+        Some(user) if password_fingerprint(&user) == fingerprint => Some(user),
+        _ => {
+            cookies.remove_private(Cookie::named(PENDING_LOGIN_COOKIE_NAME));
+            None
+        }
+    }
+}
+
+async fn outstanding_policies(
+    conn: &FumohouseDb,
+    user: &User,
+    pages: &PageCache,
+) -> Result<Vec<Policy>, Status> {
+    let policies = pages.required_policies();
+    let user_id = user.id;
+
+    conn.run(move |c| Policy::outstanding(c, user_id, &policies))
+        .await
+        .map_err(|err| {
+            error!("login: failed to check policy acceptance: {}", err);
+            Status::InternalServerError
+        })
+}
+
+#[get("/policies")]
+async fn policies_get(
+    csrf: CsrfToken,
+    conn: FumohouseDb,
+    cookies: &CookieJar<'_>,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user = match pending_login(&conn, cookies).await {
+        Some(user) => user,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let policies = outstanding_policies(&conn, &user, pages).await.map_err(Err)?;
+
+    Ok(Template::render(
+        "auth/policies",
+        PolicyContext {
+            base: BaseData::new(None, &csrf.token, pages),
+            captcha_site_key: None,
+            invite_only: false,
+            form_context: Some(&Context::default()),
+            policies: &policies,
+        },
     ))
 }
 
+#[derive(FromForm)]
+struct PolicyForm {
+    /// Only checked if there are policies to accept
+    accept_policies: bool,
+}
+
+#[post("/policies", data = "<form>")]
+async fn policies_post<'a>(
+    csrf: CsrfVerify,
+    mut form: Form<Contextual<'a, PolicyForm>>,
+    conn: FumohouseDb,
+    cookies: &CookieJar<'_>,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<(Status, Template), Status>> {
+    let user = match pending_login(&conn, cookies).await {
+        Some(user) => user,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    let policies = outstanding_policies(&conn, &user, pages).await.map_err(Err)?;
+
+    let accepted = form
+        .value
+        .as_ref()
+        .is_some_and(|form_data| form_data.accept_policies || policies.is_empty());
+
+    if accepted {
+        let user_id = user.id;
+        let accepted = policies.clone();
+
+        if let Err(err) = conn.run(move |c| Policy::accept(c, user_id, &accepted)).await {
+            error!("login: failed to record policy acceptance: {}", err);
+            return Err(Err(Status::InternalServerError));
+        }
+
+        info!("login: {} accepted the current policies", user.username);
+
+        cookies.remove_private(Cookie::named(PENDING_LOGIN_COOKIE_NAME));
+
+        SessionUtils::begin_session(&user, &conn, cookies)
+            .await
+            .unwrap_or_else(|err| {
+                error!("login: failed to start user session: {}", err);
+            });
+
+        return Ok(Redirect::to(uri!("/")));
+    }
+
+    if form.value.is_some() {
+        form.context.push_error(SiteMessages::PoliciesNotAccepted.into());
+    }
+
+    Err(Ok((
+        form.context.status(),
+        Template::render(
+            "auth/policies",
+            PolicyContext {
+                base: BaseData::new(None, csrf.new_token(), pages),
+                captcha_site_key: None,
+                invite_only: false,
+                form_context: Some(&form.context),
+                policies: &policies,
+            },
+        ),
+    )))
+}
+
 #[post("/logout")]
 async fn logout(
     _csrf: CsrfVerify,
@@ -0,0 +1,85 @@
+use super::BaseData;
+use crate::{
+    db::models::Channel,
+    util::{markdown::PageCache, Changelog, ChangelogEntry, CsrfToken, Pagination, UserSession},
+};
+use rocket::{
+    http::{uri::Origin, Status},
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+
+const RELEASES_PER_PAGE: i64 = 5;
+
+pub fn routes() -> Vec<Route> {
+    routes![list, release]
+}
+
+#[derive(Serialize)]
+struct ChangelogContext<'a> {
+    base: BaseData<'a>,
+    releases: &'a [&'a ChangelogEntry],
+    channel: Option<Channel>,
+    channels: Vec<Channel>,
+    pagination: Pagination,
+    query: String,
+}
+
+#[get("/?<page>&<channel>")]
+fn list(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    page: Option<i64>,
+    channel: Option<Channel>,
+    uri: &Origin<'_>,
+    changelog: &State<Changelog>,
+    pages: &State<PageCache>,
+) -> Template {
+    let channels = Channel::visible(user_session.user.as_ref());
+    let entries = changelog.entries(channel, &channels);
+    let pagination = Pagination::new(page, RELEASES_PER_PAGE, entries.len() as i64);
+
+    let start = pagination.offset() as usize;
+    let end = (start + pagination.limit() as usize).min(entries.len());
+
+    Template::render(
+        "changelog/list",
+        ChangelogContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            releases: &entries[start..end],
+            channel,
+            channels,
+            pagination,
+            query: Pagination::base_query(uri),
+        },
+    )
+}
+
+#[derive(Serialize)]
+struct ReleaseContext<'a> {
+    base: BaseData<'a>,
+    release: &'a ChangelogEntry,
+}
+
+#[get("/<version>")]
+fn release(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    version: &str,
+    changelog: &State<Changelog>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let channels = Channel::visible(user_session.user.as_ref());
+    let release = changelog
+        .find(version, &channels)
+        .ok_or(Status::NotFound)?;
+
+    Ok(Template::render(
+        "changelog/release",
+        ReleaseContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            release,
+        },
+    ))
+}
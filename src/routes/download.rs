@@ -0,0 +1,88 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{Channel, Platform, Release},
+        FumohouseDb,
+    },
+    util::{markdown::PageCache, CsrfToken, UserSession},
+};
+use rocket::{
+    http::Status,
+    request::{FromRequest, Outcome, Request},
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+use std::convert::Infallible;
+
+pub fn routes() -> Vec<Route> {
+    routes![download]
+}
+
+/// The visitor's platform, guessed from their user agent.
+struct DetectedPlatform(Option<Platform>);
+
+#[rocket::async_trait]
+impl<'a> FromRequest<'a> for DetectedPlatform {
+    type Error = Infallible;
+
+    async fn from_request(request: &'a Request<'_>) -> Outcome<Self, Self::Error> {
+        let platform = request
+            .headers()
+            .get_one("User-Agent")
+            .and_then(Platform::from_user_agent);
+
+        Outcome::Success(DetectedPlatform(platform))
+    }
+}
+
+#[derive(Serialize)]
+struct DownloadContext<'a> {
+    base: BaseData<'a>,
+    channel: Channel,
+    channels: Vec<Channel>,
+    platform: Option<Platform>,
+    recommended: Option<&'a Release>,
+    releases: &'a [Release],
+}
+
+#[get("/?<channel>")]
+async fn download(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    channel: Option<Channel>,
+    platform: DetectedPlatform,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let channel = channel.unwrap_or(Channel::Stable);
+    let channels = Channel::visible(user_session.user.as_ref());
+
+    // Don't reveal that hidden channels exist
+    if !channels.contains(&channel) {
+        return Err(Status::NotFound);
+    }
+
+    let releases = match conn.run(move |c| Release::latest(c, channel)).await {
+        Ok(releases) => releases,
+        Err(err) => {
+            error!("download: failed to list releases: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    let platform = platform.0;
+    let recommended = releases.iter().find(|r| Some(r.platform) == platform);
+
+    Ok(Template::render(
+        "download",
+        DownloadContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            channel,
+            channels,
+            platform,
+            recommended,
+            releases: &releases,
+        },
+    ))
+}
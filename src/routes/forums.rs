@@ -0,0 +1,893 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{
+            CategorySummary, ForumCategory, ForumPost, ForumThread, NewForumCategory,
+            NewForumThread, NotificationPayload, PostInfo, Role, ThreadInfo, User,
+        },
+        FumohouseDb,
+    },
+    util::{
+        self,
+        diff::{diff_lines, DiffHunk},
+        markdown::{self, PageCache},
+        CsrfToken, CsrfVerify, notify, Pagination, SiteMessages, UserSession,
+    },
+};
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use rocket::{
+    form::{Context, Contextual, Form},
+    http::{uri::Origin, Status},
+    response::Redirect,
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+use std::collections::HashSet;
+
+const THREADS_PER_PAGE: i64 = 25;
+const POSTS_PER_PAGE: i64 = 20;
+const MAX_POST_LENGTH: usize = 20_000;
+
+/// Category slugs which other forum routes would shadow
+const RESERVED_SLUGS: &[&str] = &["categories", "posts", "threads"];
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        index,
+        category_create,
+        category,
+        thread_new_get,
+        thread_new_post,
+        thread,
+        reply,
+        moderate,
+        post,
+        edit_get,
+        edit_post,
+        history,
+    ]
+}
+
+fn valid_slug(slug: &str) -> bool {
+    util::is_valid_slug(slug, RESERVED_SLUGS)
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("forums: database error: {}", err);
+    Status::InternalServerError
+}
+
+fn render(content: &str) -> Result<String, Status> {
+    markdown::render_untrusted(content)
+        .map(|rendered| rendered.html)
+        .map_err(|err| {
+            error!("forums: failed to render post: {}", err);
+            Status::InternalServerError
+        })
+}
+
+fn can_post(user: Option<&User>) -> bool {
+    user.is_some_and(|u| !u.banned)
+}
+
+/// Locked threads only take replies from moderators.
+fn can_reply(user: Option<&User>, thread: &ForumThread) -> bool {
+    can_post(user) && (!thread.locked || user.is_some_and(|u| u.has_role(Role::Moderator)))
+}
+
+/// Posts can be edited by their authors while the thread is open, and by
+/// moderators at any time.
+fn can_edit(user: Option<&User>, thread: &ForumThread, author_id: i64) -> bool {
+    let is_moderator = user.is_some_and(|u| u.has_role(Role::Moderator));
+    let is_author = user.is_some_and(|u| u.id == author_id);
+
+    can_post(user) && (is_moderator || (is_author && !thread.locked))
+}
+
+/// A block quote of a post, linking back to it.
+fn quote(author: &str, post_id: i64, content: &str) -> String {
+    let mut quoted = format!("> **{}** [wrote](/forums/posts/{}):\n>\n", author, post_id);
+
+    for line in content.trim_end().lines() {
+        quoted.push_str("> ");
+        quoted.push_str(line);
+        quoted.push('\n');
+    }
+
+    quoted.push('\n');
+    quoted
+}
+
+/// Which posts a post quotes, going by the links `quote` adds.
+fn quoted_posts(content: &str) -> Vec<i64> {
+    const LINK: &str = "[wrote](/forums/posts/";
+
+    let mut post_ids: Vec<i64> = content
+        .match_indices(LINK)
+        .filter_map(|(start, _)| {
+            let rest = &content[start + LINK.len()..];
+            let end = rest.find(|c: char| !c.is_ascii_digit())?;
+            rest[..end].parse().ok()
+        })
+        .collect();
+
+    post_ids.sort_unstable();
+    post_ids.dedup();
+    post_ids
+}
+
+/// Tells the authors of quoted posts, then the thread's author, about a
+/// reply. Nobody is told twice, or about their own post.
+async fn notify_reply(
+    conn: &FumohouseDb,
+    thread: &ForumThread,
+    post_id: i64,
+    author: &User,
+    content: &str,
+) {
+    let quoted = quoted_posts(content);
+
+    let quoted_authors = match conn.run(move |c| ForumPost::authors(c, &quoted)).await {
+        Ok(authors) => authors,
+        Err(err) => {
+            error!("forums: failed to find quoted posts: {}", err);
+            Vec::new()
+        }
+    };
+
+    let mut notified = HashSet::from([author.id]);
+
+    for quoted_author in quoted_authors {
+        if notified.insert(quoted_author) {
+            let payload = NotificationPayload::ForumQuote {
+                thread_id: thread.id,
+                thread_title: thread.title.clone(),
+                post_id,
+                author: author.username.clone(),
+            };
+
+            notify(conn, quoted_author, payload).await;
+        }
+    }
+
+    if notified.insert(thread.author_id) {
+        let payload = NotificationPayload::ForumReply {
+            thread_id: thread.id,
+            thread_title: thread.title.clone(),
+            post_id,
+            author: author.username.clone(),
+        };
+
+        notify(conn, thread.author_id, payload).await;
+    }
+}
+
+#[derive(Serialize)]
+struct IndexContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    categories: Vec<CategorySummary>,
+}
+
+async fn list_categories(conn: &FumohouseDb) -> Result<Vec<CategorySummary>, Status> {
+    conn.run(ForumCategory::summaries).await.map_err(db_error)
+}
+
+#[get("/")]
+async fn index(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let categories = list_categories(&conn).await?;
+
+    Ok(Template::render(
+        "forums/index",
+        IndexContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            categories,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct CategoryForm<'r> {
+    #[field(validate = len(1..=64))]
+    name: &'r str,
+    #[field(validate = len(1..=64))]
+    #[field(validate = with(|s| valid_slug(s), SiteMessages::ForumSlugInvalid.description()))]
+    slug: &'r str,
+    #[field(validate = len(..=256))]
+    description: &'r str,
+    position: i32,
+}
+
+#[post("/categories", data = "<form>")]
+async fn category_create<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, CategoryForm<'r>>>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    if !user_session.has_role(Role::Admin) {
+        return Err(Err(Status::Forbidden));
+    }
+
+    if let Some(ref form_data) = form.value {
+        let slug = form_data.slug.to_string();
+        let name = form_data.name.trim().to_string();
+        let description = form_data.description.trim().to_string();
+        let position = form_data.position;
+
+        let result = conn
+            .run(move |c| {
+                let category = NewForumCategory {
+                    slug: &slug,
+                    name: &name,
+                    description: &description,
+                    position,
+                };
+
+                ForumCategory::create(c, &category)
+            })
+            .await;
+
+        let message = match result {
+            Ok(_) => {
+                info!(
+                    "forums: {} created category {}",
+                    user_session.user.as_ref().unwrap().username,
+                    form_data.slug
+                );
+
+                return Ok(Redirect::to(uri!("/forums")));
+            }
+            Err(DieselError::DatabaseError(DatabaseErrorKind::UniqueViolation, _)) => {
+                SiteMessages::ForumCategoryExists
+            }
+            Err(err) => {
+                error!("forums: failed to create category: {}", err);
+                SiteMessages::GenericError
+            }
+        };
+
+        form.context.push_error(message.into());
+    }
+
+    let categories = list_categories(&conn).await.map_err(Err)?;
+
+    Err(Ok(Template::render(
+        "forums/index",
+        IndexContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            categories,
+        },
+    )))
+}
+
+async fn find_category(conn: &FumohouseDb, slug: &str) -> Result<ForumCategory, Status> {
+    let slug = slug.to_string();
+
+    conn.run(move |c| ForumCategory::find(c, &slug))
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)
+}
+
+#[derive(Serialize)]
+struct CategoryContext<'a> {
+    base: BaseData<'a>,
+    category: ForumCategory,
+    threads: Vec<ThreadInfo>,
+    pagination: Pagination,
+    query: String,
+    can_post: bool,
+}
+
+#[get("/<slug>?<page>", rank = 2)]
+async fn category(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    page: Option<i64>,
+    uri: &Origin<'_>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let category = find_category(&conn, slug).await?;
+    let category_id = category.id;
+
+    let (threads, pagination) = conn
+        .run(move |c| {
+            let total = ForumThread::count(c, category_id)?;
+            let pagination = Pagination::new(page, THREADS_PER_PAGE, total);
+            let threads =
+                ForumThread::list(c, category_id, pagination.limit(), pagination.offset())?;
+
+            Ok((threads, pagination))
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Template::render(
+        "forums/category",
+        CategoryContext {
+            can_post: can_post(user_session.user.as_ref()),
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            category,
+            threads,
+            pagination,
+            query: Pagination::base_query(uri),
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct NewThreadContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    category: ForumCategory,
+}
+
+#[get("/<slug>/new", rank = 2)]
+async fn thread_new_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    match user_session.user {
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+        Some(ref user) if user.banned => return Err(Err(Status::Forbidden)),
+        _ => (),
+    }
+
+    let category = find_category(&conn, slug).await.map_err(Err)?;
+
+    Ok(Template::render(
+        "forums/new",
+        NewThreadContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            category,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct ThreadForm<'r> {
+    #[field(validate = len(1..=128))]
+    title: &'r str,
+    #[field(validate = len(1..=MAX_POST_LENGTH))]
+    content: &'r str,
+}
+
+#[post("/<slug>/new", data = "<form>", rank = 2)]
+async fn thread_new_post<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, ThreadForm<'r>>>,
+    conn: FumohouseDb,
+    slug: &str,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let user = match user_session.user {
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+        Some(ref user) if user.banned => return Err(Err(Status::Forbidden)),
+        Some(ref user) => user,
+    };
+
+    let category = find_category(&conn, slug).await.map_err(Err)?;
+
+    if let Some(ref form_data) = form.value {
+        let category_id = category.id;
+        let author_id = user.id;
+        let title = form_data.title.trim().to_string();
+        let content = form_data.content.to_string();
+        let html = render(&content).map_err(Err)?;
+
+        let result = conn
+            .run(move |c| {
+                let thread = NewForumThread {
+                    category_id,
+                    author_id,
+                    title: &title,
+                };
+
+                ForumThread::create(c, thread, &content, &html)
+            })
+            .await;
+
+        match result {
+            Ok(thread) => {
+                info!(
+                    "forums: {} started thread {} in {}",
+                    user.username, thread.id, category.slug
+                );
+
+                return Ok(Redirect::to(format!("/forums/threads/{}", thread.id)));
+            }
+            Err(err) => {
+                error!("forums: failed to start thread: {}", err);
+                form.context.push_error(SiteMessages::GenericError.into());
+            }
+        }
+    }
+
+    Err(Ok(Template::render(
+        "forums/new",
+        NewThreadContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            category,
+        },
+    )))
+}
+
+/// A post along with its rendered content.
+#[derive(Serialize)]
+struct RenderedPost {
+    #[serde(flatten)]
+    post: PostInfo,
+    html: String,
+    can_edit: bool,
+}
+
+/// Everything on a page of a thread, besides the reply form.
+struct ThreadPage {
+    thread: ForumThread,
+    category: ForumCategory,
+    posts: Vec<PostInfo>,
+    pagination: Pagination,
+}
+
+async fn thread_page(
+    conn: &FumohouseDb,
+    thread_id: i64,
+    page: Option<i64>,
+) -> Result<ThreadPage, Status> {
+    conn.run(move |c| {
+        let thread = match ForumThread::find(c, thread_id)? {
+            Some(thread) => thread,
+            None => return Ok(Err(Status::NotFound)),
+        };
+
+        let category = ForumCategory::find_by_id(c, thread.category_id)?;
+        let total = ForumPost::count_for_thread(c, thread_id)?;
+        let pagination = Pagination::new(page, POSTS_PER_PAGE, total);
+        let posts = ForumPost::for_thread(c, thread_id, pagination.limit(), pagination.offset())?;
+
+        Ok(Ok(ThreadPage {
+            thread,
+            category,
+            posts,
+            pagination,
+        }))
+    })
+    .await
+    .map_err(db_error)?
+}
+
+#[derive(Serialize)]
+struct ThreadContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    thread: ForumThread,
+    category: ForumCategory,
+    posts: Vec<RenderedPost>,
+    pagination: Pagination,
+    can_reply: bool,
+    can_moderate: bool,
+    /// Prefilled reply, such as a quote
+    reply: &'a str,
+}
+
+fn thread_template(
+    user_session: UserSession,
+    csrf_token: &str,
+    data: ThreadPage,
+    form_context: &Context<'_>,
+    reply: &str,
+    pages: &PageCache,
+) -> Result<Template, Status> {
+    let user = user_session.user.as_ref();
+
+    let posts = data
+        .posts
+        .into_iter()
+        .map(|mut post| {
+            let html = match post.content_html.take() {
+                Some(html) => html,
+                None => render(&post.content)?,
+            };
+
+            Ok(RenderedPost {
+                can_edit: can_edit(user, &data.thread, post.author_id),
+                post,
+                html,
+            })
+        })
+        .collect::<Result<Vec<RenderedPost>, Status>>()?;
+
+    Ok(Template::render(
+        "forums/thread",
+        ThreadContext {
+            can_reply: can_reply(user, &data.thread),
+            can_moderate: user_session.has_role(Role::Moderator),
+            base: BaseData::new(user_session.user, csrf_token, pages),
+            form_context,
+            thread: data.thread,
+            category: data.category,
+            posts,
+            pagination: data.pagination,
+            reply,
+        },
+    ))
+}
+
+/// Setting `quote` to a post's id starts the reply with a quote of it.
+#[get("/threads/<thread_id>?<page>&<quote>")]
+async fn thread(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    thread_id: i64,
+    page: Option<i64>,
+    quote: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let data = thread_page(&conn, thread_id, page).await?;
+
+    let quoted = match quote {
+        Some(post_id) => conn
+            .run(move |c| match ForumPost::find(c, post_id)? {
+                Some(post) if post.thread_id == thread_id => {
+                    let author = User::find_by_id(c, post.author_id)?;
+                    Ok(self::quote(&author.username, post.id, &post.content))
+                }
+                _ => Ok(String::new()),
+            })
+            .await
+            .map_err(db_error)?,
+        None => String::new(),
+    };
+
+    thread_template(
+        user_session,
+        &csrf.token,
+        data,
+        &Context::default(),
+        &quoted,
+        pages,
+    )
+}
+
+#[derive(FromForm)]
+struct ReplyForm<'r> {
+    #[field(validate = len(1..=MAX_POST_LENGTH))]
+    content: &'r str,
+}
+
+#[post("/threads/<thread_id>/reply", data = "<form>")]
+async fn reply<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, ReplyForm<'r>>>,
+    conn: FumohouseDb,
+    thread_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let user = match user_session.user {
+        Some(ref user) => user,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    let thread = conn
+        .run(move |c| ForumThread::find(c, thread_id))
+        .await
+        .map_err(|err| Err(db_error(err)))?
+        .ok_or(Err(Status::NotFound))?;
+
+    if user.banned {
+        form.context.push_error(SiteMessages::ForumBanned.into());
+    } else if !can_reply(Some(user), &thread) {
+        form.context
+            .push_error(SiteMessages::ForumThreadLocked.into());
+    } else if let Some(ref form_data) = form.value {
+        let author_id = user.id;
+        let content = form_data.content.to_string();
+        let html = render(&content).map_err(Err)?;
+
+        match conn
+            .run(move |c| ForumThread::reply(c, thread_id, author_id, &content, &html))
+            .await
+        {
+            Ok(post_id) => {
+                notify_reply(&conn, &thread, post_id, user, form_data.content).await;
+                return Ok(Redirect::to(format!("/forums/posts/{}", post_id)));
+            }
+            Err(err) => {
+                error!("forums: failed to reply to thread {}: {}", thread_id, err);
+                form.context.push_error(SiteMessages::GenericError.into());
+            }
+        }
+    }
+
+    // Back to the last page, where the reply form is
+    let data = thread_page(&conn, thread_id, Some(i64::MAX))
+        .await
+        .map_err(Err)?;
+    let reply = form.context.field_value("content").unwrap_or_default();
+
+    Err(thread_template(
+        user_session,
+        csrf.new_token(),
+        data,
+        &form.context,
+        reply,
+        pages,
+    ))
+}
+
+#[derive(FromForm)]
+struct ModerateForm {
+    locked: bool,
+    pinned: bool,
+}
+
+#[post("/threads/<thread_id>/moderate", data = "<form>")]
+async fn moderate(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    form: Form<ModerateForm>,
+    conn: FumohouseDb,
+    thread_id: i64,
+) -> Result<Redirect, Status> {
+    if !user_session.has_role(Role::Moderator) {
+        return Err(Status::Forbidden);
+    }
+
+    let (locked, pinned) = (form.locked, form.pinned);
+
+    let updated = conn
+        .run(move |c| {
+            ForumThread::set_locked(c, thread_id, locked)?;
+            ForumThread::set_pinned(c, thread_id, pinned)
+        })
+        .await
+        .map_err(db_error)?;
+
+    if updated == 0 {
+        return Err(Status::NotFound);
+    }
+
+    info!(
+        "forums: {} set thread {} to locked: {}, pinned: {}",
+        user_session.user.as_ref().unwrap().username,
+        thread_id,
+        locked,
+        pinned
+    );
+
+    Ok(Redirect::to(format!("/forums/threads/{}", thread_id)))
+}
+
+/// Links to a post from anywhere, by going to the page of its thread it's on.
+#[get("/posts/<post_id>")]
+async fn post(conn: FumohouseDb, post_id: i64) -> Result<Redirect, Status> {
+    let (thread_id, position) = conn
+        .run(move |c| match ForumPost::find(c, post_id)? {
+            Some(post) => Ok(Some((post.thread_id, post.position(c)?))),
+            None => Ok(None),
+        })
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)?;
+
+    let page = (position - 1) / POSTS_PER_PAGE + 1;
+
+    Ok(Redirect::to(format!(
+        "/forums/threads/{}?page={}#post-{}",
+        thread_id, page, post_id
+    )))
+}
+
+/// Finds a post along with its thread.
+async fn find_post(
+    conn: &FumohouseDb,
+    post_id: i64,
+) -> Result<(ForumPost, ForumThread), Status> {
+    conn.run(move |c| {
+        let post = match ForumPost::find(c, post_id)? {
+            Some(post) => post,
+            None => return Ok(None),
+        };
+
+        let thread = ForumThread::find(c, post.thread_id)?;
+        Ok(thread.map(|thread| (post, thread)))
+    })
+    .await
+    .map_err(db_error)?
+    .ok_or(Status::NotFound)
+}
+
+#[derive(Serialize)]
+struct EditContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    post: ForumPost,
+    thread: ForumThread,
+    content: &'a str,
+}
+
+#[get("/posts/<post_id>/edit")]
+async fn edit_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    post_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    if user_session.user.is_none() {
+        return Err(Ok(Redirect::to(uri!("/auth/login"))));
+    }
+
+    let (post, thread) = find_post(&conn, post_id).await.map_err(Err)?;
+
+    if !can_edit(user_session.user.as_ref(), &thread, post.author_id) {
+        return Err(Err(Status::Forbidden));
+    }
+
+    let content = post.content.clone();
+
+    Ok(Template::render(
+        "forums/edit",
+        EditContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            post,
+            thread,
+            content: &content,
+        },
+    ))
+}
+
+#[post("/posts/<post_id>/edit", data = "<form>")]
+async fn edit_post<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, ReplyForm<'r>>>,
+    conn: FumohouseDb,
+    post_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let user = match user_session.user {
+        Some(ref user) => user,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    let (post, thread) = find_post(&conn, post_id).await.map_err(Err)?;
+
+    if !can_edit(Some(user), &thread, post.author_id) {
+        return Err(Err(Status::Forbidden));
+    }
+
+    if let Some(ref form_data) = form.value {
+        let editor_id = user.id;
+        let content = form_data.content.to_string();
+        let html = render(&content).map_err(Err)?;
+
+        let result = conn
+            .run(move |c| ForumPost::edit(c, post_id, editor_id, &content, &html))
+            .await;
+
+        match result {
+            Ok(edited) => {
+                if edited {
+                    info!("forums: {} edited post {}", user.username, post_id);
+                }
+
+                return Ok(Redirect::to(format!("/forums/posts/{}", post_id)));
+            }
+            Err(err) => {
+                error!("forums: failed to edit post {}: {}", post_id, err);
+                form.context.push_error(SiteMessages::GenericError.into());
+            }
+        }
+    }
+
+    let content = form.context.field_value("content").unwrap_or_default();
+
+    Err(Ok(Template::render(
+        "forums/edit",
+        EditContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            post,
+            thread,
+            content,
+        },
+    )))
+}
+
+/// A version of a post, and what changed from the one before it.
+#[derive(Serialize)]
+struct PostVersion {
+    created_at: chrono::DateTime<chrono::Utc>,
+    author: String,
+    hunks: Vec<DiffHunk>,
+}
+
+#[derive(Serialize)]
+struct HistoryContext<'a> {
+    base: BaseData<'a>,
+    post: ForumPost,
+    thread: ForumThread,
+    /// Newest first
+    versions: Vec<PostVersion>,
+}
+
+#[get("/posts/<post_id>/history")]
+async fn history(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    post_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let (post, thread) = find_post(&conn, post_id).await?;
+
+    let author_id = post.author_id;
+
+    let (edits, author) = conn
+        .run(move |c| {
+            let edits = ForumPost::edits(c, post_id)?;
+            let author = User::find_by_id(c, author_id)?;
+
+            Ok::<_, DieselError>((edits, author.username))
+        })
+        .await
+        .map_err(db_error)?;
+
+    // Each edit holds the content it replaced, so the first version was
+    // written by the post's author and each later one by the editor
+    // recorded with the version before it
+    let mut contents: Vec<&str> = edits.iter().map(|e| e.content.as_str()).collect();
+    contents.push(&post.content);
+
+    let mut versions = Vec::with_capacity(contents.len());
+    let mut previous = "";
+
+    for (i, content) in contents.iter().enumerate() {
+        let (created_at, author) = match i {
+            0 => (post.created_at, author.clone()),
+            _ => (edits[i - 1].created_at, edits[i - 1].editor.clone()),
+        };
+
+        versions.push(PostVersion {
+            created_at,
+            author,
+            hunks: diff_lines(previous, content),
+        });
+
+        previous = content;
+    }
+
+    versions.reverse();
+
+    Ok(Template::render(
+        "forums/history",
+        HistoryContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            post,
+            thread,
+            versions,
+        },
+    ))
+}
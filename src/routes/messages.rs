@@ -0,0 +1,589 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{
+            BlockedUser, Conversation, ConversationMember, ConversationSummary, Message,
+            MessageRange, MessageReport, UserBlock,
+        },
+        FumohouseDb,
+    },
+    util::{
+        markdown::PageCache,
+        messaging::{self, MessagingError, RenderedMessage, MAX_MESSAGE_LENGTH},
+        CsrfToken, CsrfVerify, Pagination, SiteMessages, UserSession,
+    },
+};
+use diesel::result::Error as DieselError;
+use rocket::{
+    form::{Context, Contextual, Form},
+    http::Status,
+    response::Redirect,
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+use std::collections::HashMap;
+
+const CONVERSATIONS_PER_PAGE: i64 = 25;
+const MESSAGES_PER_PAGE: i64 = 50;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        index,
+        new_get,
+        new_post,
+        blocked,
+        conversation,
+        send,
+        leave,
+        report_get,
+        report_post,
+    ]
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("messages: database error: {}", err);
+    Status::InternalServerError
+}
+
+fn site_message(err: MessagingError) -> SiteMessages {
+    match err {
+        MessagingError::Banned => SiteMessages::MessagesBanned,
+        MessagingError::Blocked => SiteMessages::MessagesBlocked,
+        MessagingError::RateLimited => SiteMessages::MessagesRateLimited,
+        MessagingError::UnknownUser(_) => SiteMessages::MessagesUnknownRecipient,
+        MessagingError::NoRecipients => SiteMessages::MessagesNoRecipients,
+        MessagingError::TooManyMembers => SiteMessages::MessagesTooManyMembers,
+        MessagingError::Database(err) => {
+            error!("messages: database error: {}", err);
+            SiteMessages::GenericError
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct IndexContext<'a> {
+    base: BaseData<'a>,
+    conversations: Vec<ConversationSummary>,
+    pagination: Pagination,
+}
+
+#[get("/?<page>")]
+async fn index(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    page: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let (conversations, pagination) = conn
+        .run(move |c| {
+            let count = Conversation::count_for_user(c, user_id)?;
+            let pagination = Pagination::new(page, CONVERSATIONS_PER_PAGE, count);
+            let conversations =
+                Conversation::for_user(c, user_id, pagination.limit(), pagination.offset())?;
+
+            Ok((conversations, pagination))
+        })
+        .await
+        .map_err(|err| Err(db_error(err)))?;
+
+    Ok(Template::render(
+        "messages/index",
+        IndexContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            conversations,
+            pagination,
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct NewContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    /// Prefilled recipients, when coming from a profile
+    to: &'a str,
+}
+
+/// Setting `to` to a username starts the form with them as the recipient.
+#[get("/new?<to>")]
+async fn new_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    to: Option<&str>,
+    pages: &State<PageCache>,
+) -> Result<Template, Redirect> {
+    if user_session.user.is_none() {
+        return Err(Redirect::to(uri!("/auth/login")));
+    }
+
+    Ok(Template::render(
+        "messages/new",
+        NewContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            to: to.unwrap_or_default(),
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct NewForm<'r> {
+    #[field(validate = len(1..=512))]
+    to: &'r str,
+    #[field(validate = len(..=64))]
+    title: &'r str,
+    #[field(validate = len(1..=MAX_MESSAGE_LENGTH))]
+    content: &'r str,
+}
+
+#[post("/new", data = "<form>")]
+async fn new_post<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, NewForm<'r>>>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Template> {
+    let user = match user_session.user {
+        Some(ref user) => user.clone(),
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    if let Some(ref form_data) = form.value {
+        let recipients = messaging::parse_recipients(form_data.to);
+        let title = form_data.title.trim().to_string();
+        let content = form_data.content.to_string();
+        let username = user.username.clone();
+
+        let result = conn
+            .run(move |c| messaging::start(c, &user, &recipients, Some(&title), &content))
+            .await;
+
+        match result {
+            Ok((conversation, message)) => {
+                info!(
+                    "messages: {} messaged conversation {}",
+                    username, conversation.id
+                );
+
+                return Ok(Redirect::to(format!(
+                    "/messages/{}#message-{}",
+                    conversation.id, message.id
+                )));
+            }
+            Err(err) => form.context.push_error(site_message(err).into()),
+        }
+    }
+
+    Err(Template::render(
+        "messages/new",
+        NewContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            to: "",
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct BlockedContext<'a> {
+    base: BaseData<'a>,
+    blocked: Vec<BlockedUser>,
+}
+
+#[get("/blocked")]
+async fn blocked(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let blocked = conn
+        .run(move |c| UserBlock::list(c, user_id))
+        .await
+        .map_err(|err| Err(db_error(err)))?;
+
+    Ok(Template::render(
+        "messages/blocked",
+        BlockedContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            blocked,
+        },
+    ))
+}
+
+/// A message along with who has read up to it.
+#[derive(Serialize)]
+struct ShownMessage {
+    #[serde(flatten)]
+    message: RenderedMessage,
+    seen_by: Vec<String>,
+}
+
+/// Everything on a page of a conversation, besides the message form.
+struct ConversationPage {
+    conversation: Conversation,
+    members: Vec<ConversationMember>,
+    messages: Vec<ShownMessage>,
+    /// Why the user can't send messages, if they can't
+    cannot_send: Option<SiteMessages>,
+}
+
+/// Loads the messages before `before`, or the newest ones, marking the
+/// newest as read. Returns `None` if the user isn't in the conversation.
+async fn conversation_page(
+    user_session: &UserSession,
+    conn: &FumohouseDb,
+    conversation_id: i64,
+    before: Option<i64>,
+) -> Result<Option<ConversationPage>, Status> {
+    let user = user_session.user.clone().unwrap();
+
+    conn.run(move |c| {
+        let conversation = match Conversation::find_for_member(c, conversation_id, user.id)? {
+            Some(conversation) => conversation,
+            None => return Ok(None),
+        };
+
+        let members = Conversation::members(c, conversation_id)?;
+        let blocked = UserBlock::blocked_by(c, user.id)?;
+
+        let range = match before {
+            Some(id) => MessageRange::Before(id),
+            None => MessageRange::Latest,
+        };
+
+        let messages = Message::for_conversation(c, conversation_id, range, MESSAGES_PER_PAGE)?;
+
+        if let (None, Some(newest)) = (before, messages.last()) {
+            Conversation::mark_read(c, conversation_id, user.id, newest.id)?;
+        }
+
+        // Read receipts go under the newest message each member has seen,
+        // leaving out their own
+        let mut seen_by: HashMap<i64, Vec<String>> = HashMap::new();
+
+        for member in members.iter().filter(|m| m.user_id != user.id) {
+            let seen = messages
+                .iter()
+                .rev()
+                .find(|m| m.id <= member.last_read_id && m.author_id != member.user_id);
+
+            if let Some(message) = seen {
+                seen_by
+                    .entry(message.id)
+                    .or_default()
+                    .push(member.username.clone());
+            }
+        }
+
+        let messages = messages
+            .into_iter()
+            .map(|message| ShownMessage {
+                seen_by: seen_by.remove(&message.id).unwrap_or_default(),
+                message: RenderedMessage::new(message, &blocked),
+            })
+            .collect();
+
+        let cannot_send = match messaging::can_send(c, &conversation, &members, &user) {
+            Ok(()) => None,
+            Err(MessagingError::Database(err)) => return Err(err),
+            Err(err) => Some(site_message(err)),
+        };
+
+        Ok(Some(ConversationPage {
+            conversation,
+            members,
+            messages,
+            cannot_send,
+        }))
+    })
+    .await
+    .map_err(db_error)
+}
+
+#[derive(Serialize)]
+struct ConversationContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    conversation: Conversation,
+    members: Vec<ConversationMember>,
+    messages: Vec<ShownMessage>,
+    /// Older messages start before the first one shown
+    has_older: bool,
+    cannot_send: Option<&'a str>,
+}
+
+fn conversation_template(
+    user_session: UserSession,
+    csrf_token: &str,
+    data: ConversationPage,
+    form_context: &Context<'_>,
+    pages: &PageCache,
+) -> Template {
+    Template::render(
+        "messages/conversation",
+        ConversationContext {
+            base: BaseData::new(user_session.user, csrf_token, pages),
+            form_context,
+            has_older: data.messages.len() as i64 == MESSAGES_PER_PAGE,
+            conversation: data.conversation,
+            members: data.members,
+            messages: data.messages,
+            cannot_send: data.cannot_send.as_ref().map(SiteMessages::description),
+        },
+    )
+}
+
+/// Setting `before` to a message's id shows the messages before it.
+#[get("/<conversation_id>?<before>")]
+async fn conversation(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    before: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    if user_session.user.is_none() {
+        return Err(Ok(Redirect::to(uri!("/auth/login"))));
+    }
+
+    let data = conversation_page(&user_session, &conn, conversation_id, before)
+        .await
+        .map_err(Err)?
+        .ok_or(Err(Status::NotFound))?;
+
+    Ok(conversation_template(
+        user_session,
+        &csrf.token,
+        data,
+        &Context::default(),
+        pages,
+    ))
+}
+
+#[derive(FromForm)]
+struct MessageForm<'r> {
+    #[field(validate = len(1..=MAX_MESSAGE_LENGTH))]
+    content: &'r str,
+}
+
+#[post("/<conversation_id>", data = "<form>")]
+async fn send<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, MessageForm<'r>>>,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let user = match user_session.user {
+        Some(ref user) => user.clone(),
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    if let Some(ref form_data) = form.value {
+        let content = form_data.content.to_string();
+
+        let result = conn
+            .run(move |c| {
+                let conversation = match Conversation::find_for_member(c, conversation_id, user.id)? {
+                    Some(conversation) => conversation,
+                    None => return Ok(None),
+                };
+
+                messaging::send(c, &conversation, &user, &content).map(Some)
+            })
+            .await;
+
+        match result {
+            Ok(Some(message)) => {
+                return Ok(Redirect::to(format!(
+                    "/messages/{}#message-{}",
+                    conversation_id, message.id
+                )))
+            }
+            Ok(None) => return Err(Err(Status::NotFound)),
+            Err(err) => form.context.push_error(site_message(err).into()),
+        }
+    }
+
+    let data = conversation_page(&user_session, &conn, conversation_id, None)
+        .await
+        .map_err(Err)?
+        .ok_or(Err(Status::NotFound))?;
+
+    Err(Ok(conversation_template(
+        user_session,
+        csrf.new_token(),
+        data,
+        &form.context,
+        pages,
+    )))
+}
+
+/// Only groups can be left, since one-to-one conversations would be
+/// started again by the next message.
+#[post("/<conversation_id>/leave")]
+async fn leave(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+) -> Result<Redirect, Status> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    conn.run(move |c| match Conversation::find_for_member(c, conversation_id, user_id)? {
+        Some(conversation) if conversation.is_group => {
+            Conversation::leave(c, conversation_id, user_id).map(|_| Ok(()))
+        }
+        Some(_) => Ok(Err(Status::BadRequest)),
+        None => Ok(Err(Status::NotFound)),
+    })
+    .await
+    .map_err(db_error)??;
+
+    Ok(Redirect::to(uri!("/messages")))
+}
+
+#[derive(Serialize)]
+struct ReportContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    message: RenderedMessage,
+    reported: bool,
+}
+
+/// The message to report, if it's in a conversation the user is in and
+/// someone else sent it.
+async fn reportable_message(
+    conn: &FumohouseDb,
+    user_id: i64,
+    conversation_id: i64,
+    message_id: i64,
+) -> Result<RenderedMessage, Status> {
+    let message = conn
+        .run(move |c| {
+            if Conversation::find_for_member(c, conversation_id, user_id)?.is_none() {
+                return Ok(None);
+            }
+
+            Message::find(c, message_id)
+        })
+        .await
+        .map_err(db_error)?;
+
+    match message {
+        Some(message)
+            if message.conversation_id == conversation_id && message.author_id != user_id =>
+        {
+            Ok(RenderedMessage::new(message, &[]))
+        }
+        _ => Err(Status::NotFound),
+    }
+}
+
+#[get("/<conversation_id>/report/<message_id>")]
+async fn report_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    message_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let message = reportable_message(&conn, user_id, conversation_id, message_id)
+        .await
+        .map_err(Err)?;
+
+    Ok(Template::render(
+        "messages/report",
+        ReportContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            message,
+            reported: false,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct ReportForm<'r> {
+    #[field(validate = len(1..=512))]
+    reason: &'r str,
+}
+
+#[post("/<conversation_id>/report/<message_id>", data = "<form>")]
+async fn report_post<'r>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'r, ReportForm<'r>>>,
+    conn: FumohouseDb,
+    conversation_id: i64,
+    message_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let message = reportable_message(&conn, user_id, conversation_id, message_id)
+        .await
+        .map_err(Err)?;
+
+    let mut reported = false;
+
+    if let Some(ref form_data) = form.value {
+        let reason = form_data.reason.trim().to_string();
+        let info = message.message.clone();
+
+        match conn
+            .run(move |c| MessageReport::create(c, &info, user_id, &reason))
+            .await
+        {
+            Ok(true) => {
+                info!("messages: user {} reported message {}", user_id, message_id);
+                reported = true;
+            }
+            Ok(false) => form
+                .context
+                .push_error(SiteMessages::MessageAlreadyReported.into()),
+            Err(err) => {
+                error!("messages: failed to report message {}: {}", message_id, err);
+                form.context.push_error(SiteMessages::GenericError.into());
+            }
+        }
+    }
+
+    Ok(Template::render(
+        "messages/report",
+        ReportContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            message,
+            reported,
+        },
+    ))
+}
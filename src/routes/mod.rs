@@ -1,14 +1,48 @@
-use crate::db::models::User;
+use crate::{
+    db::models::User,
+    util::{markdown::PageCache, nav::NavEntry},
+};
 use rocket::{form::Context, serde::Serialize};
 
 pub mod account;
+pub mod admin;
+pub mod api;
 pub mod auth;
+pub mod changelog;
+pub mod download;
+pub mod forums;
+pub mod messages;
+pub mod moderation;
+pub mod news;
+pub mod notifications;
 pub mod pages;
+pub mod releases;
+pub mod search;
+pub mod servers;
+pub mod users;
+pub mod wiki;
 
 #[derive(Serialize)]
 pub struct BaseData<'a> {
     user: Option<User>,
     csrf_token: &'a str,
+    unread_notifications: i64,
+    /// The navigation tree, as seen by the user
+    nav: Vec<NavEntry>,
+}
+
+impl<'a> BaseData<'a> {
+    pub fn new(user: Option<User>, csrf_token: &'a str, pages: &PageCache) -> BaseData<'a> {
+        let nav = pages.nav().visible(user.as_ref());
+        let unread_notifications = user.as_ref().map_or(0, |u| u.unread_notifications);
+
+        BaseData {
+            user,
+            csrf_token,
+            unread_notifications,
+            nav,
+        }
+    }
 }
 
 #[derive(Serialize)]
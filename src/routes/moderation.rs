@@ -0,0 +1,140 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{Message, MessageInfo, MessageRange, MessageReport, OpenReport, Role},
+        FumohouseDb,
+    },
+    util::{
+        markdown::PageCache, messaging::RenderedMessage, CsrfToken, CsrfVerify, Pagination,
+        UserSession,
+    },
+};
+use diesel::result::Error as DieselError;
+use rocket::{http::Status, response::Redirect, serde::Serialize, Route, State};
+use rocket_dyn_templates::Template;
+
+const REPORTS_PER_PAGE: i64 = 20;
+
+/// How many messages before a reported one are shown with it
+const REPORT_CONTEXT: i64 = 5;
+
+pub fn routes() -> Vec<Route> {
+    routes![reports, resolve]
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("moderation: database error: {}", err);
+    Status::InternalServerError
+}
+
+/// A report with the conversation leading up to the reported message.
+#[derive(Serialize)]
+struct ReportEntry {
+    #[serde(flatten)]
+    report: OpenReport,
+    context: Vec<RenderedMessage>,
+}
+
+#[derive(Serialize)]
+struct ReportsContext<'a> {
+    base: BaseData<'a>,
+    reports: Vec<ReportEntry>,
+    pagination: Pagination,
+}
+
+/// Reported messages nobody has dealt with yet, oldest first.
+#[get("/reports?<page>")]
+async fn reports(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    page: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !user_session.has_role(Role::Moderator) {
+        return Err(Status::Forbidden);
+    }
+
+    let (reports, pagination) = conn
+        .run(move |c| {
+            let count = MessageReport::count_open(c)?;
+            let pagination = Pagination::new(page, REPORTS_PER_PAGE, count);
+
+            let reports = MessageReport::open(c, pagination.limit(), pagination.offset())?
+                .into_iter()
+                .map(|report| {
+                    let info = &report.report;
+                    let context = match info.message_id {
+                        Some(message_id) => {
+                            let range = MessageRange::Before(message_id + 1);
+                            Message::for_conversation(
+                                c,
+                                info.conversation_id,
+                                range,
+                                REPORT_CONTEXT,
+                            )?
+                        }
+                        // Only the copy kept with the report is left
+                        None => vec![MessageInfo {
+                            id: 0,
+                            conversation_id: info.conversation_id,
+                            author_id: info.author_id.unwrap_or(0),
+                            content: info.content.clone(),
+                            created_at: info.sent_at,
+                            author: info.author.clone(),
+                        }],
+                    }
+                    .into_iter()
+                    .map(|message| RenderedMessage::new(message, &[]))
+                    .collect();
+
+                    Ok(ReportEntry { report, context })
+                })
+                .collect::<Result<Vec<ReportEntry>, DieselError>>()?;
+
+            Ok((reports, pagination))
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Template::render(
+        "moderation/reports",
+        ReportsContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            reports,
+            pagination,
+        },
+    ))
+}
+
+/// Resolves the report, along with any others of the same message.
+#[post("/reports/<report_id>/resolve")]
+async fn resolve(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    report_id: i64,
+) -> Result<Redirect, Status> {
+    let moderator = match user_session.user {
+        Some(ref user) if user.has_role(Role::Moderator) => user,
+        _ => return Err(Status::Forbidden),
+    };
+
+    let moderator_id = moderator.id;
+
+    let resolved = conn
+        .run(move |c| MessageReport::resolve(c, report_id, moderator_id))
+        .await
+        .map_err(db_error)?;
+
+    if resolved == 0 {
+        return Err(Status::NotFound);
+    }
+
+    info!(
+        "moderation: {} resolved report {}",
+        moderator.username, report_id
+    );
+
+    Ok(Redirect::to(uri!("/moderation/reports")))
+}
@@ -0,0 +1,133 @@
+use super::BaseData;
+use crate::util::{markdown::PageCache, CsrfToken, News, NewsPost, Pagination, UserSession};
+use rocket::{
+    http::{uri::Origin, ContentType, Status},
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+
+const POSTS_PER_PAGE: i64 = 10;
+const FEED_POSTS: usize = 20;
+
+pub fn routes() -> Vec<Route> {
+    routes![list, tag, post, atom, rss]
+}
+
+#[derive(Serialize)]
+struct NewsContext<'a> {
+    base: BaseData<'a>,
+    posts: &'a [&'a NewsPost],
+    tag: Option<&'a str>,
+    pagination: Pagination,
+    query: String,
+}
+
+fn list_template(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    page: Option<i64>,
+    tag: Option<&str>,
+    uri: &Origin<'_>,
+    news: &News,
+    pages: &PageCache,
+) -> Template {
+    let posts = news.posts(tag);
+    let pagination = Pagination::new(page, POSTS_PER_PAGE, posts.len() as i64);
+
+    let start = pagination.offset() as usize;
+    let end = (start + pagination.limit() as usize).min(posts.len());
+
+    Template::render(
+        "news/list",
+        NewsContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            posts: &posts[start..end],
+            tag,
+            pagination,
+            query: Pagination::base_query(uri),
+        },
+    )
+}
+
+#[get("/?<page>")]
+fn list(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    page: Option<i64>,
+    uri: &Origin<'_>,
+    news: &State<News>,
+    pages: &State<PageCache>,
+) -> Template {
+    list_template(csrf, user_session, page, None, uri, news, pages)
+}
+
+#[get("/tags/<tag>?<page>")]
+fn tag(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    tag: &str,
+    page: Option<i64>,
+    uri: &Origin<'_>,
+    news: &State<News>,
+    pages: &State<PageCache>,
+) -> Template {
+    list_template(csrf, user_session, page, Some(tag), uri, news, pages)
+}
+
+#[derive(Serialize)]
+struct PostContext<'a> {
+    base: BaseData<'a>,
+    post: &'a NewsPost,
+}
+
+#[get("/<slug>")]
+fn post(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    slug: &str,
+    news: &State<News>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let post = news.find(slug).ok_or(Status::NotFound)?;
+
+    Ok(Template::render(
+        "news/post",
+        PostContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            post,
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct FeedContext<'a> {
+    site_url: &'a str,
+    posts: &'a [NewsPost],
+}
+
+fn feed(template: &'static str, news: &News) -> Template {
+    Template::render(
+        template,
+        FeedContext {
+            site_url: news.site_url(),
+            posts: news.latest(FEED_POSTS),
+        },
+    )
+}
+
+#[get("/atom.xml")]
+fn atom(news: &State<News>) -> (ContentType, Template) {
+    (
+        ContentType::new("application", "atom+xml"),
+        feed("news/atom", news),
+    )
+}
+
+#[get("/rss.xml")]
+fn rss(news: &State<News>) -> (ContentType, Template) {
+    (
+        ContentType::new("application", "rss+xml"),
+        feed("news/rss", news),
+    )
+}
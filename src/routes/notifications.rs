@@ -0,0 +1,241 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{Notification, NotificationInfo, NotificationKind, NotificationPreference},
+        FumohouseDb,
+    },
+    util::{markdown::PageCache, CsrfToken, CsrfVerify, Pagination, UserSession},
+};
+use diesel::result::Error as DieselError;
+use rocket::{
+    form::{Context, Form},
+    http::Status,
+    response::Redirect,
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+use std::collections::HashMap;
+
+const NOTIFICATIONS_PER_PAGE: i64 = 25;
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        index,
+        open,
+        read,
+        read_all,
+        preferences_get,
+        preferences_post
+    ]
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("notifications: database error: {}", err);
+    Status::InternalServerError
+}
+
+#[derive(Serialize)]
+struct IndexContext<'a> {
+    base: BaseData<'a>,
+    notifications: Vec<NotificationInfo>,
+    pagination: Pagination,
+}
+
+#[get("/?<page>")]
+async fn index(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    page: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let (notifications, pagination) = conn
+        .run(move |c| {
+            let count = Notification::count_for_user(c, user_id)?;
+            let pagination = Pagination::new(page, NOTIFICATIONS_PER_PAGE, count);
+            let notifications =
+                Notification::for_user(c, user_id, pagination.limit(), pagination.offset())?;
+
+            Ok((notifications, pagination))
+        })
+        .await
+        .map_err(|err| Err(db_error(err)))?;
+
+    Ok(Template::render(
+        "notifications/index",
+        IndexContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            notifications,
+            pagination,
+        },
+    ))
+}
+
+/// Follows a notification's link, marking it as read on the way.
+#[get("/<notification_id>")]
+async fn open(
+    user_session: UserSession,
+    conn: FumohouseDb,
+    notification_id: i64,
+) -> Result<Redirect, Status> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    let notification = conn
+        .run(move |c| {
+            let notification = Notification::find(c, user_id, notification_id)?;
+
+            if notification.is_some() {
+                Notification::mark_read(c, user_id, &[notification_id])?;
+            }
+
+            Ok(notification)
+        })
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)?;
+
+    Ok(Redirect::to(notification.url))
+}
+
+#[post("/<notification_id>/read")]
+async fn read(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    notification_id: i64,
+) -> Result<Redirect, Status> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    conn.run(move |c| Notification::mark_read(c, user_id, &[notification_id]))
+        .await
+        .map_err(db_error)?;
+
+    Ok(Redirect::to(uri!("/notifications")))
+}
+
+#[post("/read")]
+async fn read_all(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+) -> Result<Redirect, Status> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    conn.run(move |c| Notification::mark_all_read(c, user_id))
+        .await
+        .map_err(db_error)?;
+
+    Ok(Redirect::to(uri!("/notifications")))
+}
+
+#[derive(Serialize)]
+struct KindPreference {
+    kind: NotificationKind,
+    description: &'static str,
+    enabled: bool,
+}
+
+#[derive(Serialize)]
+struct PreferencesContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    preferences: Vec<KindPreference>,
+    saved: bool,
+}
+
+async fn preferences_template(
+    user_session: UserSession,
+    csrf_token: &str,
+    conn: &FumohouseDb,
+    saved: bool,
+    pages: &PageCache,
+) -> Result<Template, Status> {
+    let user_id = user_session.user.as_ref().unwrap().id;
+
+    let disabled = conn
+        .run(move |c| NotificationPreference::disabled(c, user_id))
+        .await
+        .map_err(db_error)?;
+
+    let preferences = NotificationKind::ALL
+        .iter()
+        .map(|kind| KindPreference {
+            kind: *kind,
+            description: kind.description(),
+            enabled: !disabled.contains(kind),
+        })
+        .collect();
+
+    Ok(Template::render(
+        "notifications/preferences",
+        PreferencesContext {
+            base: BaseData::new(user_session.user, csrf_token, pages),
+            form_context: &Context::default(),
+            preferences,
+            saved,
+        },
+    ))
+}
+
+#[get("/preferences")]
+async fn preferences_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    if user_session.user.is_none() {
+        return Err(Ok(Redirect::to(uri!("/auth/login"))));
+    }
+
+    preferences_template(user_session, &csrf.token, &conn, false, pages)
+        .await
+        .map_err(Err)
+}
+
+/// Each kind is a checkbox named after it, so unchecked kinds are missing.
+#[post("/preferences", data = "<form>")]
+async fn preferences_post(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    form: Form<HashMap<String, String>>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let user_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let settings: Vec<(NotificationKind, bool)> = NotificationKind::ALL
+        .iter()
+        .map(|kind| (*kind, form.get(kind.as_str()).is_some_and(|v| v == "true")))
+        .collect();
+
+    conn.run(move |c| {
+        settings
+            .into_iter()
+            .try_for_each(|(kind, enabled)| NotificationPreference::set(c, user_id, kind, enabled))
+    })
+    .await
+    .map_err(|err| Err(db_error(err)))?;
+
+    preferences_template(user_session, csrf.new_token(), &conn, true, pages)
+        .await
+        .map_err(Err)
+}
@@ -1,16 +1,67 @@
-use crate::util::markdown::markdown_route;
-use rocket::Route;
+use super::BaseData;
+use crate::util::{
+    markdown::{self, PageCache},
+    CsrfToken, News, UserSession,
+};
+use rocket::{
+    http::uri::{fmt::Path, Segments},
+    request::Request,
+    Catcher, Route, State,
+};
+use rocket_dyn_templates::Template;
 
 pub fn routes() -> Vec<Route> {
-    routes![index, policy, code, contributor_agreement]
+    routes![page]
 }
 
-markdown_route!(index, "/", "home.md");
+pub fn catchers() -> Vec<Catcher> {
+    catchers![not_found]
+}
+
+/// Serves pages from `markdown/` by their URL path. Ranked below the
+/// static file server, so static files and other routes take priority.
+#[get("/<path..>", rank = 20)]
+fn page(
+    path: Segments<'_, Path>,
+    csrf: CsrfToken,
+    user_session: UserSession,
+    pages: &State<PageCache>,
+    news: &State<News>,
+) -> Option<Template> {
+    let url = path.collect::<Vec<&str>>().join("/");
+    let page = pages.route(&url)?;
+    let section = pages.nav().section(&url, user_session.user.as_ref());
 
-markdown_route!(policy, "/rules", "rules/rules.md");
-markdown_route!(code, "/rules/code", "rules/code_of_conduct.md");
-markdown_route!(
-    contributor_agreement,
-    "/rules/contributors",
-    "rules/contributor_agreement.md"
-);
+    let latest_news = news.latest(page.front_matter.latest_news.unwrap_or(0));
+
+    Some(markdown::template(
+        &page,
+        section,
+        latest_news,
+        BaseData::new(user_session.user, &csrf.token, pages),
+    ))
+}
+
+#[catch(404)]
+async fn not_found(request: &Request<'_>) -> Template {
+    let pages = request.guard::<&State<PageCache>>().await.unwrap();
+    let csrf = request.guard::<CsrfToken>().await.succeeded();
+    let user_session = request
+        .guard::<UserSession>()
+        .await
+        .succeeded()
+        .unwrap_or_default();
+
+    Template::render(
+        "errors/404",
+        super::DefaultContext {
+            base: BaseData::new(
+                user_session.user,
+                csrf.as_ref().map_or("", |csrf| &csrf.token),
+                pages,
+            ),
+            captcha_site_key: None,
+            form_context: None,
+        },
+    )
+}
@@ -0,0 +1,39 @@
+use super::admin::RELEASES_DIR;
+use crate::{
+    db::{models::Release, FumohouseDb},
+    util::UserSession,
+};
+use rocket::{fs::NamedFile, http::Status, Route};
+use std::path::{Path, PathBuf};
+
+pub fn routes() -> Vec<Route> {
+    routes![file]
+}
+
+/// Serves uploaded builds and patches to those who can see their channel.
+/// The launcher authenticates with its API session for hidden channels.
+#[get("/<path..>")]
+async fn file(
+    path: PathBuf,
+    user_session: UserSession,
+    conn: FumohouseDb,
+) -> Result<NamedFile, Status> {
+    let url = format!("/{}/{}", RELEASES_DIR, path.display());
+
+    let channel = match conn.run(move |c| Release::channel_for_file(c, &url)).await {
+        Ok(Some(channel)) => channel,
+        Ok(None) => return Err(Status::NotFound),
+        Err(err) => {
+            error!("releases: failed to look up file: {}", err);
+            return Err(Status::InternalServerError);
+        }
+    };
+
+    if !channel.visible_to(user_session.user.as_ref()) {
+        return Err(Status::NotFound);
+    }
+
+    NamedFile::open(Path::new(RELEASES_DIR).join(path))
+        .await
+        .map_err(|_| Status::NotFound)
+}
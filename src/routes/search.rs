@@ -0,0 +1,79 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{Channel, SearchDocument, SearchResult},
+        FumohouseDb,
+    },
+    util::{markdown::PageCache, CsrfToken, Pagination, UserSession},
+};
+use rocket::{
+    http::{uri::Origin, Status},
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+
+const RESULTS_PER_PAGE: i64 = 10;
+
+pub fn routes() -> Vec<Route> {
+    routes![search]
+}
+
+#[derive(Serialize)]
+struct SearchContext<'a> {
+    base: BaseData<'a>,
+    q: &'a str,
+    results: Vec<SearchResult>,
+    pagination: Pagination,
+    query: String,
+}
+
+#[get("/?<q>&<page>")]
+async fn search(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    q: Option<&str>,
+    page: Option<i64>,
+    uri: &Origin<'_>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let q = q.unwrap_or("").trim();
+    let channels = Channel::visible(user_session.user.as_ref());
+
+    let (results, pagination) = if q.is_empty() {
+        (Vec::new(), Pagination::new(None, RESULTS_PER_PAGE, 0))
+    } else {
+        let query = q.to_string();
+
+        conn.run(move |c| {
+            let count = SearchDocument::count(c, &query, &channels)?;
+            let pagination = Pagination::new(page, RESULTS_PER_PAGE, count);
+            let results = SearchDocument::search(
+                c,
+                &query,
+                &channels,
+                pagination.limit(),
+                pagination.offset(),
+            )?;
+
+            Ok((results, pagination))
+        })
+        .await
+        .map_err(|err: diesel::result::Error| {
+            error!("search: failed to search: {}", err);
+            Status::InternalServerError
+        })?
+    };
+
+    Ok(Template::render(
+        "search",
+        SearchContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            q,
+            results,
+            pagination,
+            query: Pagination::base_query(uri),
+        },
+    ))
+}
@@ -0,0 +1,67 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{GameServer, ServerFilter},
+        FumohouseDb,
+    },
+    util::{markdown::PageCache, CsrfToken, Pagination, UserSession},
+};
+use diesel::result::Error as DieselError;
+use rocket::{http::uri::Origin, http::Status, serde::Serialize, Route, State};
+use rocket_dyn_templates::Template;
+
+pub fn routes() -> Vec<Route> {
+    routes![list]
+}
+
+#[derive(Serialize)]
+struct ServerListContext<'a> {
+    base: BaseData<'a>,
+    servers: Vec<GameServer>,
+    filter: ServerFilter,
+    pagination: Pagination,
+    query: String,
+}
+
+#[get("/?<page>&<filter..>")]
+async fn list(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    page: Option<i64>,
+    filter: ServerFilter,
+    uri: &Origin<'_>,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let result = conn
+        .run(move |c| {
+            let total = GameServer::count(c, &filter)?;
+            let pagination = Pagination::new(
+                page,
+                super::api::servers::SERVERS_PER_PAGE,
+                total,
+            );
+            let servers =
+                GameServer::list(c, &filter, pagination.offset(), pagination.limit())?;
+
+            Ok::<_, DieselError>((servers, filter, pagination))
+        })
+        .await;
+
+    match result {
+        Ok((servers, filter, pagination)) => Ok(Template::render(
+            "servers/list",
+            ServerListContext {
+                base: BaseData::new(user_session.user, &csrf.token, pages),
+                servers,
+                filter,
+                pagination,
+                query: Pagination::base_query(uri),
+            },
+        )),
+        Err(err) => {
+            error!("servers: listing failed: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{ContributorSignature, ForumPost, Presence, PresenceInfo, User, UserBlock},
+        FumohouseDb,
+    },
+    util::{markdown::PageCache, CsrfToken, CsrfVerify, UserSession},
+};
+use chrono::{DateTime, Utc};
+use diesel::result::Error as DieselError;
+use rocket::{http::Status, response::Redirect, serde::Serialize, Route, State};
+use rocket_dyn_templates::Template;
+
+pub fn routes() -> Vec<Route> {
+    routes![profile, block, unblock]
+}
+
+#[derive(Serialize)]
+struct Profile {
+    id: i64,
+    username: String,
+    created_at: DateTime<Utc>,
+    banned: bool,
+    presence: PresenceInfo,
+    /// Has signed the contributor agreement
+    contributor: bool,
+    post_count: i64,
+    /// Blocked by the user viewing the profile
+    blocked: bool,
+}
+
+#[derive(Serialize)]
+struct ProfileContext<'a> {
+    base: BaseData<'a>,
+    profile: Profile,
+}
+
+#[get("/<username>")]
+async fn profile(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    username: String,
+    conn: FumohouseDb,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let viewer_id = user_session.user.as_ref().map(|u| u.id);
+
+    let result = conn
+        .run(move |c| {
+            let user = User::find(c, &username)?;
+            let presence = Presence::info(c, &user, viewer_id)?;
+            let contributor = ContributorSignature::latest_for_user(c, user.id)?.is_some();
+            let post_count = ForumPost::count_by_author(c, user.id)?;
+            let blocked = match viewer_id {
+                Some(viewer_id) => UserBlock::has_blocked(c, viewer_id, user.id)?,
+                None => false,
+            };
+
+            Ok::<_, DieselError>(Profile {
+                id: user.id,
+                username: user.username,
+                created_at: user.created_at,
+                banned: user.banned,
+                presence,
+                contributor,
+                post_count,
+                blocked,
+            })
+        })
+        .await;
+
+    match result {
+        Ok(profile) => Ok(Template::render(
+            "users/profile",
+            ProfileContext {
+                base: BaseData::new(user_session.user, &csrf.token, pages),
+                profile,
+            },
+        )),
+        Err(DieselError::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("profile: lookup failed: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+/// Blocks or unblocks a user, then heads back to their profile.
+async fn set_blocked(
+    user_session: UserSession,
+    conn: FumohouseDb,
+    username: String,
+    blocked: bool,
+) -> Result<Redirect, Status> {
+    let blocker_id = match user_session.user {
+        Some(ref user) => user.id,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    let result = conn
+        .run(move |c| {
+            let user = User::find(c, &username)?;
+
+            if user.id == blocker_id {
+                return Ok(None);
+            }
+
+            if blocked {
+                UserBlock::block(c, blocker_id, user.id)?;
+            } else {
+                UserBlock::unblock(c, blocker_id, user.id)?;
+            }
+
+            Ok::<_, DieselError>(Some(user.username))
+        })
+        .await;
+
+    match result {
+        Ok(Some(blocked)) => Ok(Redirect::to(uri!("/users", profile(blocked)))),
+        Ok(None) => Err(Status::BadRequest),
+        Err(DieselError::NotFound) => Err(Status::NotFound),
+        Err(err) => {
+            error!("profile: failed to update block: {}", err);
+            Err(Status::InternalServerError)
+        }
+    }
+}
+
+#[post("/<username>/block")]
+async fn block(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    username: String,
+) -> Result<Redirect, Status> {
+    set_blocked(user_session, conn, username, true).await
+}
+
+#[post("/<username>/unblock")]
+async fn unblock(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    username: String,
+) -> Result<Redirect, Status> {
+    set_blocked(user_session, conn, username, false).await
+}
@@ -0,0 +1,811 @@
+use super::BaseData;
+use crate::{
+    db::{
+        models::{
+            DocumentKind, NewSearchDocument, NotificationPayload, RevisionInfo, Role,
+            SearchDocument, User, WikiEdit, WikiPage, WikiRevision, WikiSave,
+        },
+        FumohouseDb,
+    },
+    util::{
+        self,
+        diff::{diff_lines, DiffHunk},
+        markdown::{self, PageCache, Rendered},
+        CsrfToken, CsrfVerify, News, notify, Pagination, SiteMessages, UserSession,
+    },
+};
+use diesel::result::Error as DieselError;
+use rocket::{
+    form::{Context, Contextual, Form},
+    http::{uri::Origin, ContentType, Status},
+    response::Redirect,
+    serde::Serialize,
+    Route, State,
+};
+use rocket_dyn_templates::Template;
+
+const REVISIONS_PER_PAGE: i64 = 25;
+const FEED_REVISIONS: i64 = 30;
+const MAX_CONTENT_LENGTH: usize = 100_000;
+
+/// Slugs which other wiki routes would shadow
+const RESERVED_SLUGS: &[&str] = &["recent"];
+
+pub fn routes() -> Vec<Route> {
+    routes![
+        index,
+        recent,
+        recent_feed,
+        page,
+        revision,
+        history,
+        diff,
+        edit_get,
+        edit_post,
+        revert,
+        protect,
+    ]
+}
+
+fn valid_slug(slug: &str) -> bool {
+    slug.len() <= 128 && util::is_valid_slug(slug, RESERVED_SLUGS)
+}
+
+/// Turns a title like "Fumo Types" into a slug like `fumo-types`.
+fn slugify(title: &str) -> String {
+    title
+        .trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+fn db_error(err: DieselError) -> Status {
+    error!("wiki: database error: {}", err);
+    Status::InternalServerError
+}
+
+fn render(content: &str) -> Result<Rendered, Status> {
+    markdown::render_untrusted(content).map_err(|err| {
+        error!("wiki: failed to render page: {}", err);
+        Status::InternalServerError
+    })
+}
+
+fn can_create(user: Option<&User>) -> bool {
+    user.is_some_and(|u| !u.banned)
+}
+
+async fn find_page(conn: &FumohouseDb, slug: &str) -> Result<WikiPage, Status> {
+    let slug = slug.to_string();
+
+    conn.run(move |c| WikiPage::find(c, &slug))
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)
+}
+
+/// The page along with its latest revision.
+async fn latest_revision(
+    conn: &FumohouseDb,
+    page: WikiPage,
+) -> Result<(WikiPage, WikiRevision), Status> {
+    conn.run(move |c| {
+        let revision = page.latest_revision(c)?;
+        Ok((page, revision))
+    })
+    .await
+    .map_err(db_error)
+}
+
+async fn find_revision(
+    conn: &FumohouseDb,
+    page_id: i64,
+    revision_id: i64,
+) -> Result<WikiRevision, Status> {
+    conn.run(move |c| WikiRevision::find(c, page_id, revision_id))
+        .await
+        .map_err(db_error)?
+        .ok_or(Status::NotFound)
+}
+
+/// Tells everyone else who has edited the page about a new revision.
+async fn notify_editors(
+    conn: &FumohouseDb,
+    slug: &str,
+    revision: &WikiRevision,
+    author: &User,
+) {
+    let page_id = revision.page_id;
+
+    let editors = match conn.run(move |c| WikiRevision::editors(c, page_id)).await {
+        Ok(editors) => editors,
+        Err(err) => {
+            error!("wiki: failed to find editors of /wiki/{}: {}", slug, err);
+            return;
+        }
+    };
+
+    for editor in editors.into_iter().filter(|id| *id != author.id) {
+        let payload = NotificationPayload::WikiEdit {
+            slug: slug.to_string(),
+            title: revision.title.clone(),
+            revision_id: revision.id,
+            author: author.username.clone(),
+        };
+
+        notify(conn, editor, payload).await;
+    }
+}
+
+/// Keeps the page's entry in the search index in line with its content.
+/// Failing to do so doesn't fail the edit.
+async fn index_page(conn: &FumohouseDb, slug: &str, title: &str, rendered: Rendered) {
+    let document = NewSearchDocument {
+        kind: DocumentKind::Wiki,
+        url: format!("/wiki/{}", slug),
+        title: title.to_string(),
+        headings: rendered.text.headings,
+        body: rendered.text.body,
+        channel: None,
+    };
+
+    if let Err(err) = conn.run(move |c| SearchDocument::upsert(c, &document)).await {
+        error!("wiki: failed to index /wiki/{}: {}", slug, err);
+    }
+}
+
+#[derive(Serialize)]
+struct IndexContext<'a> {
+    base: BaseData<'a>,
+    pages: Vec<WikiPage>,
+    can_create: bool,
+    create: Option<&'a str>,
+}
+
+/// Every page. Visiting with `create` set to a title goes to the editor for
+/// the page it would be at.
+#[get("/?<create>")]
+async fn index(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    create: Option<&str>,
+    cache: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    if let Some(title) = create {
+        let slug = slugify(title);
+
+        if valid_slug(&slug) {
+            return Err(Ok(Redirect::to(format!("/wiki/{}/edit", slug))));
+        }
+    }
+
+    let pages = conn.run(WikiPage::all).await.map_err(|err| Err(db_error(err)))?;
+
+    Ok(Template::render(
+        "wiki/index",
+        IndexContext {
+            can_create: can_create(user_session.user.as_ref()),
+            base: BaseData::new(user_session.user, &csrf.token, cache),
+            pages,
+            create,
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct RecentContext<'a> {
+    base: BaseData<'a>,
+    revisions: Vec<RevisionInfo>,
+    pagination: Pagination,
+    query: String,
+}
+
+#[get("/recent?<page>")]
+async fn recent(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    page: Option<i64>,
+    uri: &Origin<'_>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let (revisions, pagination) = conn
+        .run(move |c| {
+            let pagination = Pagination::new(page, REVISIONS_PER_PAGE, WikiRevision::count(c)?);
+            let revisions = WikiRevision::recent(c, pagination.limit(), pagination.offset())?;
+
+            Ok((revisions, pagination))
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Template::render(
+        "wiki/recent",
+        RecentContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            revisions,
+            pagination,
+            query: Pagination::base_query(uri),
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct FeedContext<'a> {
+    site_url: &'a str,
+    revisions: Vec<RevisionInfo>,
+}
+
+#[get("/recent.xml")]
+async fn recent_feed(
+    conn: FumohouseDb,
+    news: &State<News>,
+) -> Result<(ContentType, Template), Status> {
+    let revisions = conn
+        .run(|c| WikiRevision::recent(c, FEED_REVISIONS, 0))
+        .await
+        .map_err(db_error)?;
+
+    Ok((
+        ContentType::new("application", "atom+xml"),
+        Template::render(
+            "wiki/atom",
+            FeedContext {
+                site_url: news.site_url(),
+                revisions,
+            },
+        ),
+    ))
+}
+
+#[derive(Serialize)]
+struct PageContext<'a> {
+    base: BaseData<'a>,
+    wiki_page: &'a WikiPage,
+    revision: &'a WikiRevision,
+    html: String,
+    toc: Vec<markdown::TocEntry>,
+    is_current: bool,
+    can_edit: bool,
+    /// Roles a moderator may protect the page with, if the user is one
+    protection_roles: Vec<Role>,
+}
+
+fn page_template(
+    user_session: UserSession,
+    csrf_token: &str,
+    page: &WikiPage,
+    revision: &WikiRevision,
+    is_current: bool,
+    pages: &PageCache,
+) -> Result<Template, Status> {
+    let rendered = render(&revision.content)?;
+    let user = user_session.user.as_ref();
+
+    let protection_roles = match user {
+        Some(user) if user.has_role(Role::Moderator) => Role::ALL
+            .iter()
+            .copied()
+            .filter(|role| user.has_role(*role))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(Template::render(
+        "wiki/page",
+        PageContext {
+            can_edit: page.editable_by(user),
+            protection_roles,
+            base: BaseData::new(user_session.user, csrf_token, pages),
+            wiki_page: page,
+            revision,
+            html: rendered.html,
+            toc: rendered.toc,
+            is_current,
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct MissingContext<'a> {
+    base: BaseData<'a>,
+    slug: &'a str,
+    can_create: bool,
+}
+
+#[get("/<slug>", rank = 2)]
+async fn page(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    if !valid_slug(slug) {
+        return Err(Status::NotFound);
+    }
+
+    let owned_slug = slug.to_string();
+    let found = conn
+        .run(move |c| match WikiPage::find(c, &owned_slug)? {
+            Some(page) => {
+                let revision = page.latest_revision(c)?;
+                Ok(Some((page, revision)))
+            }
+            None => Ok(None),
+        })
+        .await
+        .map_err(db_error)?;
+
+    match found {
+        Some((page, revision)) => {
+            page_template(user_session, &csrf.token, &page, &revision, true, pages)
+        }
+        None => Ok(Template::render(
+            "wiki/missing",
+            MissingContext {
+                can_create: can_create(user_session.user.as_ref()),
+                base: BaseData::new(user_session.user, &csrf.token, pages),
+                slug,
+            },
+        )),
+    }
+}
+
+#[get("/<slug>/revisions/<revision_id>")]
+async fn revision(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    revision_id: i64,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let page = find_page(&conn, slug).await?;
+    let revision = find_revision(&conn, page.id, revision_id).await?;
+
+    let latest = latest_revision(&conn, page).await?;
+
+    page_template(
+        user_session,
+        &csrf.token,
+        &latest.0,
+        &revision,
+        latest.1.id == revision.id,
+        pages,
+    )
+}
+
+#[derive(Serialize)]
+struct HistoryContext<'a> {
+    base: BaseData<'a>,
+    wiki_page: WikiPage,
+    can_edit: bool,
+    revisions: Vec<RevisionInfo>,
+    pagination: Pagination,
+    query: String,
+}
+
+#[get("/<slug>/history?<page>")]
+async fn history(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    page: Option<i64>,
+    uri: &Origin<'_>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let wiki_page = find_page(&conn, slug).await?;
+    let page_id = wiki_page.id;
+
+    let (revisions, pagination) = conn
+        .run(move |c| {
+            let total = WikiRevision::count_for_page(c, page_id)?;
+            let pagination = Pagination::new(page, REVISIONS_PER_PAGE, total);
+            let revisions =
+                WikiRevision::history(c, page_id, pagination.limit(), pagination.offset())?;
+
+            Ok((revisions, pagination))
+        })
+        .await
+        .map_err(db_error)?;
+
+    Ok(Template::render(
+        "wiki/history",
+        HistoryContext {
+            can_edit: wiki_page.editable_by(user_session.user.as_ref()),
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            wiki_page,
+            revisions,
+            pagination,
+            query: Pagination::base_query(uri),
+        },
+    ))
+}
+
+#[derive(Serialize)]
+struct DiffContext<'a> {
+    base: BaseData<'a>,
+    wiki_page: WikiPage,
+    can_edit: bool,
+    from: Option<WikiRevision>,
+    to: WikiRevision,
+    title_changed: bool,
+    hunks: Vec<DiffHunk>,
+}
+
+/// Changes between two revisions. `to` defaults to the latest revision and
+/// `from` to the one before `to`, so a page's first revision is compared
+/// against nothing.
+#[get("/<slug>/diff?<from>&<to>")]
+async fn diff(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+    pages: &State<PageCache>,
+) -> Result<Template, Status> {
+    let page = find_page(&conn, slug).await?;
+
+    let (page, to) = match to {
+        Some(to) => {
+            let revision = find_revision(&conn, page.id, to).await?;
+            (page, revision)
+        }
+        None => latest_revision(&conn, page).await?,
+    };
+
+    let (from, to) = match from {
+        Some(from) => (Some(find_revision(&conn, page.id, from).await?), to),
+        None => conn
+            .run(move |c| Ok((to.previous(c)?, to)))
+            .await
+            .map_err(db_error)?,
+    };
+
+    let (old_title, old_content) = from
+        .as_ref()
+        .map_or(("", ""), |r| (r.title.as_str(), r.content.as_str()));
+
+    let title_changed = old_title != to.title;
+    let hunks = diff_lines(old_content, &to.content);
+
+    Ok(Template::render(
+        "wiki/diff",
+        DiffContext {
+            can_edit: page.editable_by(user_session.user.as_ref()),
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            wiki_page: page,
+            from,
+            to,
+            title_changed,
+            hunks,
+        },
+    ))
+}
+
+#[derive(FromForm)]
+struct EditForm<'r> {
+    #[field(validate = len(1..=128))]
+    title: &'r str,
+    #[field(validate = len(..=MAX_CONTENT_LENGTH))]
+    content: &'r str,
+    #[field(validate = len(..=256))]
+    summary: &'r str,
+    /// The revision the edit was made on, to catch conflicting edits
+    base_revision: Option<i64>,
+    /// Show the rendered page instead of saving it
+    preview: bool,
+}
+
+#[derive(Serialize)]
+struct EditContext<'a, 'b> {
+    base: BaseData<'a>,
+    form_context: &'a Context<'b>,
+    slug: &'a str,
+    wiki_page: Option<WikiPage>,
+    title: &'a str,
+    content: &'a str,
+    summary: &'a str,
+    base_revision: Option<i64>,
+    /// Set after a conflicting edit, to link to the changes which were missed
+    conflict_from: Option<i64>,
+    preview: Option<String>,
+}
+
+/// Where the editor starts out from, and whether the user may save it.
+struct EditState {
+    page: Option<WikiPage>,
+    latest: Option<WikiRevision>,
+}
+
+/// Finds the page being edited, checking that the user may edit it.
+async fn edit_state(conn: &FumohouseDb, slug: &str, user: &User) -> Result<EditState, Status> {
+    if !valid_slug(slug) {
+        return Err(Status::NotFound);
+    }
+
+    let slug = slug.to_string();
+    let state = conn
+        .run(move |c| match WikiPage::find(c, &slug)? {
+            Some(page) => {
+                let latest = page.latest_revision(c)?;
+
+                Ok(EditState {
+                    page: Some(page),
+                    latest: Some(latest),
+                })
+            }
+            None => Ok(EditState {
+                page: None,
+                latest: None,
+            }),
+        })
+        .await
+        .map_err(db_error)?;
+
+    let allowed = match state.page {
+        Some(ref page) => page.editable_by(Some(user)),
+        None => can_create(Some(user)),
+    };
+
+    if !allowed {
+        return Err(Status::Forbidden);
+    }
+
+    Ok(state)
+}
+
+#[get("/<slug>/edit")]
+async fn edit_get(
+    csrf: CsrfToken,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    pages: &State<PageCache>,
+) -> Result<Template, Result<Redirect, Status>> {
+    let state = match user_session.user {
+        Some(ref user) => edit_state(&conn, slug, user).await.map_err(Err)?,
+        None => return Err(Ok(Redirect::to(uri!("/auth/login")))),
+    };
+
+    let (title, content, base_revision) = match state.latest {
+        Some(ref latest) => (latest.title.as_str(), latest.content.as_str(), Some(latest.id)),
+        None => ("", "", None),
+    };
+
+    Ok(Template::render(
+        "wiki/edit",
+        EditContext {
+            base: BaseData::new(user_session.user, &csrf.token, pages),
+            form_context: &Context::default(),
+            slug,
+            wiki_page: state.page,
+            title,
+            content,
+            summary: "",
+            base_revision,
+            conflict_from: None,
+            preview: None,
+        },
+    ))
+}
+
+#[post("/<slug>/edit", data = "<form>")]
+async fn edit_post<'a>(
+    csrf: CsrfVerify,
+    user_session: UserSession,
+    mut form: Form<Contextual<'a, EditForm<'a>>>,
+    conn: FumohouseDb,
+    slug: &'a str,
+    pages: &State<PageCache>,
+) -> Result<Redirect, Result<Template, Status>> {
+    let user = match user_session.user {
+        Some(ref user) => user,
+        None => return Ok(Redirect::to(uri!("/auth/login"))),
+    };
+
+    let mut state = edit_state(&conn, slug, user).await.map_err(Err)?;
+    let mut base_revision = state.latest.as_ref().map(|r| r.id);
+    let mut conflict_from = None;
+    let mut preview = None;
+
+    if let Some(ref form_data) = form.value {
+        base_revision = form_data.base_revision;
+
+        if form_data.preview {
+            preview = Some(render(form_data.content).map_err(Err)?.html);
+        } else {
+            let rendered = render(form_data.content).map_err(Err)?;
+
+            let owned_slug = slug.to_string();
+            let title = form_data.title.trim().to_string();
+            let content = form_data.content.to_string();
+            let summary = form_data.summary.trim().to_string();
+            let author_id = user.id;
+            let author_role = user.role;
+            let edit_base = form_data.base_revision;
+
+            let result = conn
+                .run(move |c| {
+                    let edit = WikiEdit {
+                        author_id,
+                        author_role,
+                        title: &title,
+                        content: &content,
+                        summary: &summary,
+                        reverted_from: None,
+                    };
+
+                    WikiPage::save(c, &owned_slug, edit, edit_base)
+                })
+                .await;
+
+            let message = match result {
+                Ok(WikiSave::Saved(revision)) => {
+                    info!(
+                        "wiki: {} saved revision {} of /wiki/{}",
+                        user.username, revision.id, slug
+                    );
+
+                    index_page(&conn, slug, &revision.title, rendered).await;
+                    notify_editors(&conn, slug, &revision, user).await;
+
+                    return Ok(Redirect::to(format!("/wiki/{}", slug)));
+                }
+                Ok(WikiSave::Conflict) => {
+                    // Saving again overwrites the other edit, once the
+                    // user has had the chance to look at it
+                    state = edit_state(&conn, slug, user).await.map_err(Err)?;
+                    conflict_from = form_data.base_revision;
+                    base_revision = state.latest.as_ref().map(|r| r.id);
+
+                    SiteMessages::WikiEditConflict
+                }
+                Ok(WikiSave::Unchanged) => SiteMessages::WikiNoChanges,
+                Ok(WikiSave::Forbidden) => return Err(Err(Status::Forbidden)),
+                Err(err) => {
+                    error!("wiki: failed to save /wiki/{}: {}", slug, err);
+                    SiteMessages::GenericError
+                }
+            };
+
+            form.context.push_error(message.into());
+        }
+    }
+
+    let title = form.context.field_value("title").unwrap_or_default();
+    let content = form.context.field_value("content").unwrap_or_default();
+    let summary = form.context.field_value("summary").unwrap_or_default();
+
+    Err(Ok(Template::render(
+        "wiki/edit",
+        EditContext {
+            base: BaseData::new(user_session.user, csrf.new_token(), pages),
+            form_context: &form.context,
+            slug,
+            wiki_page: state.page,
+            title,
+            content,
+            summary,
+            base_revision,
+            conflict_from,
+            preview,
+        },
+    )))
+}
+
+/// Restores an earlier revision by saving a copy of it.
+#[post("/<slug>/revert/<revision_id>")]
+async fn revert(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    revision_id: i64,
+) -> Result<Redirect, Status> {
+    let user = user_session.user.as_ref().ok_or(Status::Unauthorized)?;
+    let state = edit_state(&conn, slug, user).await?;
+
+    let (page, latest) = match state {
+        EditState {
+            page: Some(page),
+            latest: Some(latest),
+        } => (page, latest),
+        _ => return Err(Status::NotFound),
+    };
+
+    let old = find_revision(&conn, page.id, revision_id).await?;
+    let rendered = render(&old.content)?;
+
+    let owned_slug = slug.to_string();
+    let author_id = user.id;
+    let author_role = user.role;
+    let summary = format!("Reverted to revision {}", old.id);
+
+    let result = conn
+        .run(move |c| {
+            let edit = WikiEdit {
+                author_id,
+                author_role,
+                title: &old.title,
+                content: &old.content,
+                summary: &summary,
+                reverted_from: Some(old.id),
+            };
+
+            WikiPage::save(c, &owned_slug, edit, Some(latest.id))
+        })
+        .await
+        .map_err(db_error)?;
+
+    match result {
+        WikiSave::Saved(revision) => {
+            info!(
+                "wiki: {} reverted /wiki/{} to revision {}",
+                user.username, slug, revision_id
+            );
+
+            index_page(&conn, slug, &revision.title, rendered).await;
+            notify_editors(&conn, slug, &revision, user).await;
+
+            Ok(Redirect::to(format!("/wiki/{}", slug)))
+        }
+        WikiSave::Unchanged => Ok(Redirect::to(format!("/wiki/{}", slug))),
+        // Someone else edited the page in the meantime
+        WikiSave::Conflict => Err(Status::Conflict),
+        WikiSave::Forbidden => Err(Status::Forbidden),
+    }
+}
+
+#[derive(FromForm)]
+struct ProtectForm<'r> {
+    protection: &'r str,
+}
+
+/// Sets the lowest role allowed to edit a page. Moderators can protect
+/// pages up to their own role, and only change the protection of pages
+/// they can edit.
+#[post("/<slug>/protect", data = "<form>")]
+async fn protect(
+    _csrf: CsrfVerify,
+    user_session: UserSession,
+    conn: FumohouseDb,
+    slug: &str,
+    form: Form<ProtectForm<'_>>,
+) -> Result<Redirect, Status> {
+    let user = user_session.user.as_ref().ok_or(Status::Unauthorized)?;
+
+    let role: Role = form
+        .protection
+        .parse()
+        .map_err(|_| Status::UnprocessableEntity)?;
+
+    let page = find_page(&conn, slug).await?;
+    let page_id = page.id;
+
+    // Nor can they lower the protection of pages above them
+    if !user.has_role(Role::Moderator) || !user.has_role(role) || !user.has_role(page.protection) {
+        return Err(Status::Forbidden);
+    }
+
+    conn.run(move |c| WikiPage::set_protection(c, page_id, role))
+        .await
+        .map_err(db_error)?;
+
+    info!(
+        "wiki: {} set the protection of /wiki/{} to {}",
+        user.username,
+        slug,
+        role.as_str()
+    );
+
+    Ok(Redirect::to(format!("/wiki/{}", slug)))
+}
@@ -0,0 +1,104 @@
+use super::markdown::{FrontMatter, MarkdownError, Page, PageCache, PageText};
+use crate::db::models::Channel;
+use rocket::serde::Serialize;
+use std::{cmp::Reverse, error::Error};
+
+/// Under `markdown/`
+const CHANGELOG_DIR: &str = "changelog";
+
+#[derive(Serialize)]
+pub struct ChangelogEntry {
+    pub version: String,
+    pub channel: Channel,
+    pub front_matter: FrontMatter,
+    pub html: String,
+    #[serde(skip)]
+    pub text: PageText,
+}
+
+/// Release notes from `markdown/changelog`, indexed once at startup from
+/// the pages already parsed into the `PageCache`.
+pub struct Changelog {
+    /// Newest first
+    entries: Vec<ChangelogEntry>,
+}
+
+impl Changelog {
+    pub fn load(pages: &PageCache) -> Result<Changelog, Box<dyn Error>> {
+        let mut entries = Vec::new();
+
+        for (path, page) in pages.in_dir(CHANGELOG_DIR) {
+            let entry = Self::load_entry(&page)
+                .map_err(|err| format!("{}/{}: {}", CHANGELOG_DIR, path.display(), err))?;
+
+            entries.push(entry);
+        }
+
+        entries.sort_by_key(|e| Reverse(e.front_matter.date));
+
+        info!("changelog: indexed {} releases", entries.len());
+
+        Ok(Changelog { entries })
+    }
+
+    fn load_entry(page: &Page) -> Result<ChangelogEntry, Box<dyn Error>> {
+        let front_matter = page.front_matter.clone();
+
+        let version = front_matter
+            .version
+            .clone()
+            .ok_or(MarkdownError::MissingField("version"))?;
+
+        if front_matter.date.is_none() {
+            return Err(MarkdownError::MissingField("date").into());
+        }
+
+        let channel = front_matter
+            .channel
+            .as_deref()
+            .ok_or(MarkdownError::MissingField("channel"))?
+            .parse::<Channel>()?;
+
+        Ok(ChangelogEntry {
+            version,
+            channel,
+            front_matter,
+            html: page.html.clone(),
+            text: page.text.clone(),
+        })
+    }
+
+    /// Entries in `channel` (or every channel), limited to those in `visible`.
+    pub fn entries(&self, channel: Option<Channel>, visible: &[Channel]) -> Vec<&ChangelogEntry> {
+        self.entries
+            .iter()
+            .filter(|e| visible.contains(&e.channel))
+            .filter(|e| channel.is_none_or(|c| e.channel == c))
+            .collect()
+    }
+
+    pub fn find(&self, version: &str, visible: &[Channel]) -> Option<&ChangelogEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.version == version && visible.contains(&e.channel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn entries_come_from_the_page_cache() {
+        let pages = PageCache::load().unwrap();
+        let changelog = Changelog::load(&pages).unwrap();
+
+        let entry = changelog.find("0.1.0", &[Channel::Stable]).unwrap();
+        let page = pages.get(Path::new("changelog/0.1.0.md")).unwrap();
+
+        assert_eq!(entry.channel, Channel::Stable);
+        assert_eq!(entry.html, page.html);
+        assert!(changelog.find("0.1.0", &[Channel::Beta]).is_none());
+    }
+}
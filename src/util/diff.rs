@@ -0,0 +1,47 @@
+use rocket::serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+
+/// Lines of unchanged context kept around each change
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Serialize)]
+pub struct DiffLine {
+    /// `equal`, `insert` or `delete`
+    pub kind: &'static str,
+    /// Line numbers, starting at 1, on the old and new side
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+/// A run of changes with the lines around them.
+#[derive(Serialize)]
+pub struct DiffHunk {
+    pub lines: Vec<DiffLine>,
+}
+
+/// A line by line diff of two texts, grouped into hunks. Empty when they
+/// are the same.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffHunk> {
+    let diff = TextDiff::from_lines(old, new);
+
+    diff.grouped_ops(CONTEXT_LINES)
+        .iter()
+        .map(|group| DiffHunk {
+            lines: group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    kind: match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Insert => "insert",
+                        ChangeTag::Delete => "delete",
+                    },
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    text: change.to_string_lossy().trim_end_matches(['\r', '\n']).to_string(),
+                })
+                .collect(),
+        })
+        .collect()
+}
@@ -0,0 +1,196 @@
+use super::live::{LiveEvent, Topic};
+use crate::db::{models::GameServer, FumohouseDb};
+use chrono::{Duration as ChronoDuration, Utc};
+use diesel::{prelude::*, result::Error as DieselError, PgConnection};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Status,
+    outcome::Outcome::{Failure, Success},
+    request::{FromRequest, Outcome, Request},
+    Rocket, State,
+};
+use std::env;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+const SERVER_TOKEN_LENGTH: usize = 48;
+pub const HEARTBEAT_INTERVAL: u64 = 30; // seconds
+const HEARTBEAT_TIMEOUT: i64 = 90; // seconds
+
+const JOIN_TICKET_LENGTH: usize = 32;
+pub const JOIN_TICKET_EXPIRY: i64 = 60; // seconds
+
+const OFFLINE_CHECK: u64 = 30; // seconds
+
+pub struct GameServerUtils;
+
+#[rocket::async_trait]
+impl Fairing for GameServerUtils {
+    fn info(&self) -> Info {
+        Info {
+            name: "mark stale game servers offline, purge join tickets",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<rocket::Orbit>) {
+        use crate::db::schema::join_tickets;
+        use rocket::tokio::{
+            self,
+            time::{self, Duration as TokioDuration},
+        };
+
+        let conn = FumohouseDb::get_one(rocket).await.unwrap();
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(TokioDuration::from_secs(OFFLINE_CHECK));
+
+            loop {
+                interval.tick().await;
+
+                let result = conn.run(GameServerUtils::mark_stale_offline).await;
+
+                match result {
+                    Ok(0) => (),
+                    Ok(count) => info!("game servers: marked {} servers offline", count),
+                    Err(err) => error!("fairing: error marking servers offline: {}", err),
+                }
+
+                let result = conn
+                    .run(|c| {
+                        diesel::delete(
+                            join_tickets::table.filter(join_tickets::expires_at.lt(Utc::now())),
+                        )
+                        .execute(c)
+                    })
+                    .await;
+
+                if let Err(err) = result {
+                    error!("fairing: error purging join tickets: {}", err);
+                }
+            }
+        });
+    }
+}
+
+impl GameServerUtils {
+    /// Marks servers which have stopped sending heartbeats offline,
+    /// returning how many there were.
+    pub fn mark_stale_offline(c: &mut PgConnection) -> QueryResult<usize> {
+        use crate::db::schema::game_servers::dsl::*;
+
+        let cutoff = Utc::now() - ChronoDuration::seconds(HEARTBEAT_TIMEOUT);
+
+        let servers =
+            diesel::update(game_servers.filter(online.eq(true).and(last_heartbeat.lt(cutoff))))
+                .set((online.eq(false), player_count.eq(0)))
+                .get_results::<GameServer>(c)?;
+
+        for server in &servers {
+            LiveEvent::new(Topic::Servers, None, server).publish(c);
+        }
+
+        Ok(servers.len())
+    }
+
+    pub fn new_token() -> (String, Vec<u8>) {
+        let token = super::rand_string(SERVER_TOKEN_LENGTH);
+        let hash = super::sha256(&token);
+        (token, hash)
+    }
+
+    pub fn new_join_ticket() -> (String, Vec<u8>) {
+        let ticket = super::rand_string(JOIN_TICKET_LENGTH);
+        let hash = super::sha256(&ticket);
+        (ticket, hash)
+    }
+
+    pub fn hash_join_ticket(ticket: &str) -> Vec<u8> {
+        super::sha256(ticket)
+    }
+}
+
+pub struct GameServerConfig {
+    secret_hash: Vec<u8>,
+}
+
+impl GameServerConfig {
+    pub fn new() -> GameServerConfig {
+        let secret =
+            env::var("GAME_SERVER_SECRET").expect("Did not find GAME_SERVER_SECRET.");
+
+        GameServerConfig {
+            secret_hash: super::sha256(&secret),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum GameServerError {
+    #[error("Missing or invalid game server credentials.")]
+    Unauthorized,
+    #[error("Failed to retrieve game server information: {diesel_error}.")]
+    RetrieveFailed { diesel_error: DieselError },
+}
+
+/// Guard for requests made with the shared game server secret,
+/// which is required to register new servers. The secret's hash is compared
+/// in constant time.
+pub struct RegistrationAuth;
+
+#[rocket::async_trait]
+impl<'a> FromRequest<'a> for RegistrationAuth {
+    type Error = GameServerError;
+
+    async fn from_request(request: &'a Request<'_>) -> Outcome<Self, Self::Error> {
+        let config = request.guard::<&State<GameServerConfig>>().await.unwrap();
+
+        match super::bearer_token(request) {
+            Some(secret) if bool::from(super::sha256(secret).ct_eq(&config.secret_hash)) => {
+                Success(RegistrationAuth)
+            }
+            _ => Failure((Status::Unauthorized, GameServerError::Unauthorized)),
+        }
+    }
+}
+
+/// Guard for requests made by a registered game server,
+/// using the token it was issued on registration.
+pub struct GameServerAuth {
+    pub server: GameServer,
+}
+
+#[rocket::async_trait]
+impl<'a> FromRequest<'a> for GameServerAuth {
+    type Error = GameServerError;
+
+    async fn from_request(request: &'a Request<'_>) -> Outcome<Self, Self::Error> {
+        use crate::db::schema::game_servers::dsl::*;
+
+        let token_hash = match super::bearer_token(request) {
+            Some(bearer) => super::sha256(bearer),
+            None => return Failure((Status::Unauthorized, GameServerError::Unauthorized)),
+        };
+
+        let conn = request.guard::<FumohouseDb>().await.unwrap();
+
+        let result = conn
+            .run(move |c| {
+                game_servers
+                    .filter(token.eq(token_hash))
+                    .first::<GameServer>(c)
+            })
+            .await;
+
+        match result {
+            Ok(server) => Success(GameServerAuth { server }),
+            Err(DieselError::NotFound) => {
+                Failure((Status::Unauthorized, GameServerError::Unauthorized))
+            }
+            Err(diesel_error) => Failure((
+                Status::InternalServerError,
+                GameServerError::RetrieveFailed { diesel_error },
+            )),
+        }
+    }
+}
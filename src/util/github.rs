@@ -0,0 +1,196 @@
+use chrono::{Duration, Utc};
+use reqwest::{header, Client, Url};
+use rocket::http::{Cookie, CookieJar};
+use serde::{Deserialize, Serialize};
+use std::{env, error::Error};
+use subtle::ConstantTimeEq;
+
+const GITHUB_URL: &str = "https://github.com";
+const GITHUB_API_URL: &str = "https://api.github.com";
+const USER_AGENT: &str = "fumohouse";
+
+const STATE_COOKIE_NAME: &str = "fh_github_state";
+const ACCOUNT_COOKIE_NAME: &str = "fh_github_account";
+const ACCOUNT_EXPIRY: i64 = 15; // minutes
+
+#[derive(Serialize)]
+struct TokenBody<'a> {
+    client_id: &'a str,
+    client_secret: &'a str,
+    code: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    error_description: Option<String>,
+}
+
+/// A GitHub account whose owner logged in to it.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GithubAccount {
+    pub id: i64,
+    pub login: String,
+}
+
+struct Credentials {
+    client_id: String,
+    client_secret: String,
+}
+
+/// Verifies GitHub accounts through an OAuth app. Without
+/// `GITHUB_CLIENT_ID` and `GITHUB_CLIENT_SECRET`, nothing can be verified.
+pub struct GithubOAuth {
+    client: Client,
+    credentials: Option<Credentials>,
+    url: String,
+    api_url: String,
+}
+
+impl GithubOAuth {
+    pub fn new() -> GithubOAuth {
+        let client = Client::builder()
+            .build()
+            .expect("Failed to create GitHub client");
+
+        let credentials = match (
+            env::var("GITHUB_CLIENT_ID"),
+            env::var("GITHUB_CLIENT_SECRET"),
+        ) {
+            (Ok(client_id), Ok(client_secret)) => Some(Credentials {
+                client_id,
+                client_secret,
+            }),
+            _ => {
+                warn!("GITHUB_CLIENT_ID or GITHUB_CLIENT_SECRET is not set, so GitHub accounts can't be verified");
+                None
+            }
+        };
+
+        GithubOAuth {
+            client,
+            credentials,
+            url: env::var("GITHUB_URL").unwrap_or_else(|_| GITHUB_URL.into()),
+            api_url: env::var("GITHUB_API_URL").unwrap_or_else(|_| GITHUB_API_URL.into()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.credentials.is_some()
+    }
+
+    /// Where to send the user to log in to GitHub. The state ties the
+    /// callback to this browser.
+    pub fn authorize_url(&self, cookies: &CookieJar<'_>) -> Option<String> {
+        let credentials = self.credentials.as_ref()?;
+        let state = super::rand_string(32);
+
+        cookies.add_private(Cookie::new(STATE_COOKIE_NAME, state.clone()));
+
+        let url = Url::parse_with_params(
+            &format!("{}/login/oauth/authorize", self.url),
+            &[
+                ("client_id", credentials.client_id.as_str()),
+                ("state", state.as_str()),
+                ("allow_signup", "false"),
+            ],
+        )
+        .ok()?;
+
+        Some(url.into())
+    }
+
+    /// Exchanges the code GitHub sent back for the account it belongs to,
+    /// and remembers it for the given user for a short while.
+    pub async fn verify(
+        &self,
+        cookies: &CookieJar<'_>,
+        user_id: i64,
+        code: &str,
+        state: &str,
+    ) -> Result<GithubAccount, Box<dyn Error>> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or("GitHub is not configured")?;
+
+        let expected = cookies
+            .get_private(STATE_COOKIE_NAME)
+            .ok_or("missing OAuth state")?;
+        cookies.remove_private(Cookie::named(STATE_COOKIE_NAME));
+
+        if !bool::from(expected.value().as_bytes().ct_eq(state.as_bytes())) {
+            return Err("OAuth state mismatch".into());
+        }
+
+        let token = self
+            .client
+            .post(format!("{}/login/oauth/access_token", self.url))
+            .header(header::ACCEPT, "application/json")
+            .form(&TokenBody {
+                client_id: &credentials.client_id,
+                client_secret: &credentials.client_secret,
+                code,
+            })
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<TokenResponse>()
+            .await?;
+
+        let access_token = match token.access_token {
+            Some(access_token) => access_token,
+            None => return Err(token.error_description.unwrap_or_default().into()),
+        };
+
+        let account = self
+            .client
+            .get(format!("{}/user", self.api_url))
+            .header(header::ACCEPT, "application/vnd.github+json")
+            .header(header::USER_AGENT, USER_AGENT)
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<GithubAccount>()
+            .await?;
+
+        let expiry = Utc::now() + Duration::minutes(ACCOUNT_EXPIRY);
+        cookies.add_private(Cookie::new(
+            ACCOUNT_COOKIE_NAME,
+            format!(
+                "{}:{}:{}:{}",
+                user_id,
+                expiry.timestamp(),
+                account.id,
+                account.login
+            ),
+        ));
+
+        Ok(account)
+    }
+
+    /// The account the given user recently verified, if any.
+    pub fn verified_account(cookies: &CookieJar<'_>, user_id: i64) -> Option<GithubAccount> {
+        let cookie = cookies.get_private(ACCOUNT_COOKIE_NAME)?;
+        let mut parts = cookie.value().splitn(4, ':');
+        let (owner, expiry, id, login) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+
+        if owner.parse::<i64>().ok()? != user_id
+            || expiry.parse::<i64>().ok()? < Utc::now().timestamp()
+        {
+            cookies.remove_private(Cookie::named(ACCOUNT_COOKIE_NAME));
+            return None;
+        }
+
+        Some(GithubAccount {
+            id: id.parse().ok()?,
+            login: login.to_string(),
+        })
+    }
+
+    pub fn forget_account(cookies: &CookieJar<'_>) {
+        cookies.remove_private(Cookie::named(ACCOUNT_COOKIE_NAME));
+    }
+}
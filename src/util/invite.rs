@@ -0,0 +1,22 @@
+use std::env;
+
+const INVITE_CODE_LENGTH: usize = 16;
+
+/// Whether registration needs an invite, from INVITE_ONLY.
+pub struct InviteConfig {
+    pub invite_only: bool,
+}
+
+impl InviteConfig {
+    pub fn new() -> InviteConfig {
+        let invite_only = env::var("INVITE_ONLY")
+            .map(|value| matches!(value.as_str(), "1" | "true"))
+            .unwrap_or(false);
+
+        InviteConfig { invite_only }
+    }
+}
+
+pub fn generate_code() -> String {
+    super::rand_string(INVITE_CODE_LENGTH)
+}
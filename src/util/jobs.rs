@@ -0,0 +1,391 @@
+use super::mail::MailError;
+use crate::db::models::{Job, NewJob};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    result::Error as DieselError,
+    PgConnection, QueryResult,
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    serde::{
+        de::DeserializeOwned,
+        json::{self, Value},
+        Deserialize, Serialize,
+    },
+    Rocket,
+};
+use std::{collections::HashMap, sync::Arc};
+use thiserror::Error;
+
+const JOB_POLL: u64 = 5; // seconds
+/// How many jobs each web instance runs at once
+const JOB_WORKERS: usize = 4;
+/// How long a worker has to finish a job before another may take it. The
+/// lease is renewed every heartbeat while the job runs.
+const JOB_LEASE: i64 = 10; // minutes
+const JOB_HEARTBEAT: u64 = 60; // seconds
+
+/// The wait between attempts doubles each time, from 30 seconds up to an hour.
+const RETRY_BASE: i64 = 30; // seconds
+const RETRY_MAX: i64 = 60 * 60; // seconds
+
+/// How long finished jobs are kept for the admin view
+const JOB_RETENTION: i64 = 7; // days
+
+#[derive(Error, Debug)]
+pub enum JobError {
+    #[error("Invalid payload: {0}.")]
+    Payload(#[from] json::serde_json::Error),
+    #[error("Database error: {0}.")]
+    Database(#[from] DieselError),
+    #[error("{0}")]
+    Mail(#[from] MailError),
+}
+
+/// A kind of job. Its fields are stored as the job's payload, and it runs
+/// on a worker's own connection, outside of any request.
+pub trait JobHandler: Serialize + DeserializeOwned {
+    /// Stored in the `kind` column, so this must never change
+    const KIND: &'static str;
+    const MAX_ATTEMPTS: i32 = 5;
+
+    fn run(self, c: &mut PgConnection) -> Result<(), JobError>;
+}
+
+/// Adds a job to the queue, to run as soon as possible or at `run_at`.
+pub fn enqueue<J: JobHandler>(
+    c: &mut PgConnection,
+    job: &J,
+    run_at: Option<DateTime<Utc>>,
+) -> Result<i64, JobError> {
+    let payload = json::to_value(job)?;
+
+    Ok(Job::enqueue(
+        c,
+        &NewJob {
+            kind: J::KIND,
+            payload: &payload,
+            max_attempts: J::MAX_ATTEMPTS,
+            run_at: run_at.unwrap_or_else(Utc::now),
+            repeat_every: None,
+        },
+    )?)
+}
+
+type RunFn = fn(&mut PgConnection, Value) -> Result<(), JobError>;
+
+fn run_job<J: JobHandler>(c: &mut PgConnection, payload: Value) -> Result<(), JobError> {
+    json::from_value::<J>(payload)?.run(c)
+}
+
+struct Recurring {
+    kind: &'static str,
+    payload: Value,
+    max_attempts: i32,
+    every: i32, // seconds
+}
+
+/// The handlers for every kind of job, and the jobs which run on a schedule.
+#[derive(Default)]
+pub struct JobRegistry {
+    handlers: HashMap<&'static str, RunFn>,
+    recurring: Vec<Recurring>,
+}
+
+impl JobRegistry {
+    pub fn register<J: JobHandler>(mut self) -> Self {
+        self.handlers.insert(J::KIND, run_job::<J>);
+        self
+    }
+
+    /// Registers the job's kind and runs it every so often, starting as
+    /// soon as the site starts.
+    pub fn recurring<J: JobHandler>(mut self, job: J, every: Duration) -> Self {
+        self.recurring.push(Recurring {
+            kind: J::KIND,
+            payload: json::to_value(job).expect("Failed to serialize recurring job."),
+            max_attempts: J::MAX_ATTEMPTS,
+            every: every
+                .num_seconds()
+                .try_into()
+                .expect("Recurring jobs can't be that far apart."),
+        });
+
+        self.register::<J>()
+    }
+
+    /// Schedules the first run of each recurring job, unless one is already
+    /// waiting.
+    pub fn schedule_recurring(&self, c: &mut PgConnection) -> QueryResult<()> {
+        for job in &self.recurring {
+            Job::schedule_recurring(
+                c,
+                &NewJob {
+                    kind: job.kind,
+                    payload: &job.payload,
+                    max_attempts: job.max_attempts,
+                    run_at: Utc::now(),
+                    repeat_every: Some(job.every),
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes up to `limit` due jobs of the kinds registered here. Jobs of
+    /// other kinds are left for instances that know how to run them.
+    pub fn claim_due(&self, c: &mut PgConnection, limit: i64) -> QueryResult<Vec<Job>> {
+        let kinds: Vec<&str> = self.handlers.keys().copied().collect();
+        Job::claim_due(c, &kinds, limit, Duration::minutes(JOB_LEASE))
+    }
+
+    /// Runs a claimed job and records how it went.
+    pub fn run(&self, c: &mut PgConnection, job: Job) -> QueryResult<()> {
+        let handler = match self.handlers.get(job.kind.as_str()) {
+            Some(handler) => handler,
+            None => {
+                error!("jobs: no handler for job {} ({})", job.id, job.kind);
+                return finish(c, &job, Some("No handler for this kind of job."), None);
+            }
+        };
+
+        let err = match handler(c, job.payload.clone()) {
+            Ok(()) => {
+                info!("jobs: finished job {} ({})", job.id, job.kind);
+                return finish(c, &job, None, None);
+            }
+            Err(err) => err.to_string(),
+        };
+
+        let retry_at = if job.attempts < job.max_attempts {
+            let delay = RETRY_BASE
+                .saturating_mul(1 << (job.attempts - 1).clamp(0, 16))
+                .min(RETRY_MAX);
+            Some(Utc::now() + Duration::seconds(delay))
+        } else {
+            None
+        };
+
+        match retry_at {
+            Some(at) => warn!(
+                "jobs: job {} ({}) failed, retrying at {}: {}",
+                job.id, job.kind, at, err
+            ),
+            None => error!("jobs: job {} ({}) is dead: {}", job.id, job.kind, err),
+        }
+
+        finish(c, &job, Some(&err), retry_at)
+    }
+
+    /// Claims and runs whatever is due, one job after another, returning how
+    /// many jobs ran.
+    pub fn run_due(&self, c: &mut PgConnection) -> QueryResult<usize> {
+        let due = self.claim_due(c, JOB_WORKERS as i64)?;
+        let count = due.len();
+
+        for job in due {
+            self.run(c, job)?;
+        }
+
+        Ok(count)
+    }
+}
+
+fn finish(
+    c: &mut PgConnection,
+    job: &Job,
+    error: Option<&str>,
+    retry_at: Option<DateTime<Utc>>,
+) -> QueryResult<()> {
+    if !Job::finish(c, job, error, retry_at)? {
+        warn!(
+            "jobs: job {} ({}) was taken by another worker before it finished",
+            job.id, job.kind
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs a claimed job on its own connection, renewing its lease until it's
+/// done.
+async fn run_claimed(
+    pool: Pool<ConnectionManager<PgConnection>>,
+    registry: Arc<JobRegistry>,
+    job: Job,
+) {
+    use rocket::tokio::{self, task, time};
+
+    let heartbeat = {
+        let pool = pool.clone();
+        let (id, attempts, kind) = (job.id, job.attempts, job.kind.clone());
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(time::Duration::from_secs(JOB_HEARTBEAT));
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let pool = pool.clone();
+                let result = task::spawn_blocking(move || {
+                    let mut c = pool.get().map_err(|err| err.to_string())?;
+                    Job::extend_lease(&mut c, id, attempts, Duration::minutes(JOB_LEASE))
+                        .map_err(|err| err.to_string())
+                })
+                .await;
+
+                match result {
+                    Ok(Ok(true)) => {}
+                    Ok(Ok(false)) => {
+                        warn!("jobs: lost the lease on job {} ({})", id, kind);
+                        break;
+                    }
+                    Ok(Err(err)) => error!("jobs: error renewing job {}: {}", id, err),
+                    Err(err) => error!("jobs: renewing job {} panicked: {}", id, err),
+                }
+            }
+        })
+    };
+
+    let result = task::spawn_blocking(move || {
+        let mut c = pool.get().map_err(|err| err.to_string())?;
+        registry.run(&mut c, job).map_err(|err| err.to_string())
+    })
+    .await;
+
+    heartbeat.abort();
+
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => error!("jobs: error running job: {}", err),
+        Err(err) => error!("jobs: job panicked: {}", err),
+    }
+}
+
+/// Runs queued jobs in the background, a few at a time. Connections come
+/// from a small pool of the worker's own, and are only held while there is
+/// work to do.
+pub struct JobWorker {
+    registry: Arc<JobRegistry>,
+}
+
+impl JobWorker {
+    pub fn new(registry: JobRegistry) -> JobWorker {
+        JobWorker {
+            registry: Arc::new(registry),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for JobWorker {
+    fn info(&self) -> Info {
+        Info {
+            name: "run queued jobs",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<rocket::Orbit>) {
+        use rocket::tokio::{self, sync::Semaphore, task, time};
+
+        let url: String = rocket
+            .figment()
+            .extract_inner("databases.fumohouse_db.url")
+            .expect("Database URL is not configured.");
+
+        // One for each running job and its heartbeat, and one for claiming
+        let pool = Pool::builder()
+            .max_size(JOB_WORKERS as u32 * 2 + 1)
+            .min_idle(Some(0))
+            .idle_timeout(Some(std::time::Duration::from_secs(JOB_POLL * 12)))
+            .build_unchecked(ConnectionManager::<PgConnection>::new(url));
+
+        let registry = self.registry.clone();
+        let workers = Arc::new(Semaphore::new(JOB_WORKERS));
+
+        tokio::spawn(async move {
+            let mut interval = time::interval(time::Duration::from_secs(JOB_POLL));
+            let mut scheduled = false;
+
+            loop {
+                interval.tick().await;
+
+                // Only as many jobs are claimed as there are free workers
+                let free = workers.available_permits();
+
+                if free == 0 {
+                    continue;
+                }
+
+                let claimed = {
+                    let pool = pool.clone();
+                    let registry = registry.clone();
+
+                    task::spawn_blocking(move || {
+                        let mut c = pool.get().map_err(|err| err.to_string())?;
+
+                        if !scheduled {
+                            registry
+                                .schedule_recurring(&mut c)
+                                .map_err(|err| err.to_string())?;
+                        }
+
+                        registry
+                            .claim_due(&mut c, free as i64)
+                            .map_err(|err| err.to_string())
+                    })
+                    .await
+                };
+
+                let due = match claimed {
+                    Ok(Ok(due)) => {
+                        scheduled = true;
+                        due
+                    }
+                    Ok(Err(err)) => {
+                        error!("jobs: error claiming jobs: {}", err);
+                        continue;
+                    }
+                    Err(err) => {
+                        error!("jobs: claiming jobs panicked: {}", err);
+                        continue;
+                    }
+                };
+
+                for job in due {
+                    let worker = match workers.clone().acquire_owned().await {
+                        Ok(worker) => worker,
+                        Err(_) => return,
+                    };
+
+                    let (pool, registry) = (pool.clone(), registry.clone());
+
+                    tokio::spawn(async move {
+                        run_claimed(pool, registry, job).await;
+                        drop(worker);
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Deletes finished jobs once they're a week old.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PruneJobs;
+
+impl JobHandler for PruneJobs {
+    const KIND: &'static str = "prune_jobs";
+
+    fn run(self, c: &mut PgConnection) -> Result<(), JobError> {
+        let count = Job::prune(c, Utc::now() - Duration::days(JOB_RETENTION))?;
+        info!("jobs: pruned {} finished jobs", count);
+
+        Ok(())
+    }
+}
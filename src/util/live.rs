@@ -0,0 +1,224 @@
+use diesel::{sql_types::Text, Connection, PgConnection, RunQueryDsl};
+use native_tls::TlsConnector;
+use postgres_native_tls::MakeTlsConnector;
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    futures::{stream, StreamExt},
+    serde::{
+        json::{self, Value},
+        Deserialize, Serialize,
+    },
+    tokio::{
+        self,
+        sync::broadcast::{self, Receiver, Sender},
+        time::{sleep, Duration},
+    },
+    Orbit, Rocket,
+};
+use std::{error::Error, sync::Arc};
+use tokio_postgres::AsyncMessage;
+
+/// The Postgres channel events are sent through
+const CHANNEL: &str = "fumohouse_live";
+
+/// How many events can be waiting for slow streams before they miss some
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// How long to wait before listening again after losing the connection
+const RECONNECT_DELAY: u64 = 5; // seconds
+
+/// What clients can subscribe to.
+#[derive(Serialize, Deserialize, FromFormField, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Topic {
+    /// The user's own notifications
+    Notifications,
+    /// Messages sent to the user's conversations
+    Messages,
+    /// Players joining and leaving servers, for users who share their presence
+    Presence,
+    /// Servers coming online, going offline and changing player counts
+    Servers,
+}
+
+impl Topic {
+    pub const ALL: &'static [Topic] = &[
+        Topic::Notifications,
+        Topic::Messages,
+        Topic::Presence,
+        Topic::Servers,
+    ];
+
+    /// Used as the name of the topic's events.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Topic::Notifications => "notifications",
+            Topic::Messages => "messages",
+            Topic::Presence => "presence",
+            Topic::Servers => "servers",
+        }
+    }
+}
+
+/// Something which happened, as sent to subscribers of its topic.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct LiveEvent {
+    pub topic: Topic,
+    /// Private events are only sent to this user
+    pub user_id: Option<i64>,
+    pub data: Value,
+}
+
+impl LiveEvent {
+    pub fn new<T: Serialize>(topic: Topic, user_id: Option<i64>, data: &T) -> LiveEvent {
+        LiveEvent {
+            topic,
+            user_id,
+            // Serializing plain data structures doesn't fail
+            data: json::to_value(data).unwrap_or(Value::Null),
+        }
+    }
+
+    /// Sends the event to every web instance through Postgres. Events sent
+    /// in a transaction only go out once it commits.
+    ///
+    /// Streams are only a convenience, so failing to publish is logged rather
+    /// than failing whatever the event is about. The notification is sent in
+    /// a savepoint, which keeps a failure from aborting the caller's
+    /// transaction.
+    pub fn publish(&self, c: &PgConnection) {
+        let payload = match json::to_string(self) {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!(
+                    "live: failed to serialize {} event: {}",
+                    self.topic.as_str(),
+                    err
+                );
+                return;
+            }
+        };
+
+        let result = c.transaction(|| {
+            diesel::sql_query("SELECT pg_notify($1, $2)")
+                .bind::<Text, _>(CHANNEL)
+                .bind::<Text, _>(payload)
+                .execute(c)
+        });
+
+        if let Err(err) = result {
+            error!(
+                "live: failed to publish {} event: {}",
+                self.topic.as_str(),
+                err
+            );
+        }
+    }
+
+    pub fn visible_to(&self, user_id: i64) -> bool {
+        self.user_id.is_none_or(|id| id == user_id)
+    }
+}
+
+/// Passes events published by any web instance on to this instance's streams.
+pub struct LiveEvents {
+    sender: Sender<Arc<LiveEvent>>,
+}
+
+impl Default for LiveEvents {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        LiveEvents { sender }
+    }
+}
+
+impl LiveEvents {
+    /// Receives every event from now on, of every topic.
+    pub fn subscribe(&self) -> Receiver<Arc<LiveEvent>> {
+        self.sender.subscribe()
+    }
+}
+
+/// Listens for events on a dedicated connection, since pooled connections
+/// can't wait for notifications.
+pub struct LiveListener;
+
+#[rocket::async_trait]
+impl Fairing for LiveListener {
+    fn info(&self) -> Info {
+        Info {
+            name: "listen for live events",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let url: String = match rocket.figment().extract_inner("databases.fumohouse_db.url") {
+            Ok(url) => url,
+            Err(err) => {
+                error!("live: no database to listen to: {}", err);
+                return;
+            }
+        };
+
+        let sender = match rocket.state::<LiveEvents>() {
+            Some(events) => events.sender.clone(),
+            None => {
+                error!("live: LiveEvents is not managed");
+                return;
+            }
+        };
+
+        tokio::spawn(async move {
+            loop {
+                match listen(&url, &sender).await {
+                    Ok(()) => warn!("live: connection closed, listening again"),
+                    Err(err) => error!("live: listening failed: {}", err),
+                }
+
+                sleep(Duration::from_secs(RECONNECT_DELAY)).await;
+            }
+        });
+    }
+}
+
+/// Forwards events until the connection is lost. TLS is used the way the
+/// database URL's `sslmode` asks, as for the pooled connections.
+async fn listen(
+    url: &str,
+    sender: &Sender<Arc<LiveEvent>>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let tls = MakeTlsConnector::new(TlsConnector::new()?);
+    let (client, mut connection) = tokio_postgres::connect(url, tls).await?;
+    let sender = sender.clone();
+
+    // The connection has to be polled for anything to happen, LISTEN included
+    let forward = tokio::spawn(async move {
+        let mut messages = stream::poll_fn(move |cx| connection.poll_message(cx));
+
+        while let Some(message) = messages.next().await {
+            let notification = match message? {
+                AsyncMessage::Notification(notification) => notification,
+                _ => continue,
+            };
+
+            match json::from_str::<LiveEvent>(notification.payload()) {
+                // Nobody is listening unless a stream is open
+                Ok(event) => {
+                    let _ = sender.send(Arc::new(event));
+                }
+                Err(err) => warn!("live: ignoring malformed event: {}", err),
+            }
+        }
+
+        Ok(())
+    });
+
+    client.batch_execute(&format!("LISTEN {}", CHANNEL)).await?;
+    info!("live: listening for events");
+
+    let result: Result<(), tokio_postgres::Error> = forward.await.unwrap_or(Ok(()));
+
+    drop(client);
+    Ok(result?)
+}
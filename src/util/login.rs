@@ -0,0 +1,107 @@
+use super::jobs::{JobError, JobHandler};
+use crate::db::{
+    models::{LoginAttempt, NewLoginAttempt, User},
+    FumohouseDb,
+};
+use argon2::Argon2;
+use chrono::{Duration, Utc};
+use diesel::{result::Error as DieselError, PgConnection};
+use rocket::serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use thiserror::Error;
+
+const ATTEMPT_WINDOW: i64 = 15; // minutes
+const MAX_ATTEMPTS_PER_IP: i64 = 30;
+const MAX_ATTEMPTS_PER_ACCOUNT: i64 = 10;
+
+#[derive(Error, Debug)]
+pub enum LoginError {
+    #[error("Too many login attempts.")]
+    Throttled,
+    #[error("Invalid username or password.")]
+    InvalidCredentials,
+    #[error("Database error: {0}.")]
+    Database(#[from] DieselError),
+}
+
+/// Checks a username and password, shared by the site and API logins.
+///
+/// Every attempt is recorded before it's checked, so concurrent guesses
+/// count against each other. Too many from one address or on one account
+/// within the window are refused until it passes.
+pub async fn authenticate(
+    conn: &FumohouseDb,
+    argon: &Argon2<'_>,
+    ip: Option<IpAddr>,
+    username: &str,
+    password: &str,
+) -> Result<User, LoginError> {
+    let ip = ip.map(|ip| ip.to_string());
+    let username = username.to_lowercase();
+
+    let lookup = username.clone();
+    let user = conn
+        .run(move |c| -> Result<_, LoginError> {
+            LoginAttempt::record(
+                c,
+                &NewLoginAttempt {
+                    ip_address: ip.as_deref(),
+                    username: &lookup,
+                },
+            )?;
+
+            let since = Utc::now() - Duration::minutes(ATTEMPT_WINDOW);
+
+            if let Some(ref ip) = ip {
+                if LoginAttempt::count_by_ip_since(c, ip, since)? > MAX_ATTEMPTS_PER_IP {
+                    return Err(LoginError::Throttled);
+                }
+            }
+
+            if LoginAttempt::count_by_username_since(c, &lookup, since)? > MAX_ATTEMPTS_PER_ACCOUNT
+            {
+                return Err(LoginError::Throttled);
+            }
+
+            match User::find(c, &lookup) {
+                Ok(user) => Ok(Some(user)),
+                Err(DieselError::NotFound) => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+        .await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            // Hash the password anyway, so that the response takes as long
+            // as it would for a user who exists
+            let _ = super::hash_password(argon, password);
+            return Err(LoginError::InvalidCredentials);
+        }
+    };
+
+    if user.verify_password(argon, password).is_err() {
+        return Err(LoginError::InvalidCredentials);
+    }
+
+    conn.run(move |c| LoginAttempt::clear(c, &username)).await?;
+
+    Ok(user)
+}
+
+/// Deletes login attempts which no longer count towards throttling.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PurgeLoginAttempts;
+
+impl JobHandler for PurgeLoginAttempts {
+    const KIND: &'static str = "purge_login_attempts";
+
+    fn run(self, c: &mut PgConnection) -> Result<(), JobError> {
+        let before = Utc::now() - Duration::minutes(ATTEMPT_WINDOW);
+        LoginAttempt::purge_before(c, before)?;
+
+        Ok(())
+    }
+}
@@ -1,23 +1,458 @@
+use super::{
+    nav::{Nav, NavEntry, NavOptions},
+    policy::{Policy, PolicyOptions},
+    NewsPost,
+};
 use crate::routes::BaseData;
-use comrak::{nodes::NodeValue, Arena, ComrakExtensionOptions, ComrakOptions};
+use chrono::NaiveDate;
+use comrak::{
+    adapters::SyntaxHighlighterAdapter,
+    arena_tree::Node,
+    nodes::{Ast, AstNode, NodeCode, NodeHeading, NodeHtmlBlock, NodeValue},
+    plugins::syntect::SyntectAdapter,
+    Anchorizer, Arena, ComrakExtensionOptions, ComrakOptions, ComrakPlugins, ComrakRenderOptions,
+};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use rocket::tokio::sync::watch;
 use rocket_dyn_templates::Template;
 use serde::{Deserialize, Serialize};
-use std::{error::Error, fs, path::Path, str};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    error::Error,
+    fs,
+    path::{Component, Path, PathBuf},
+    str,
+    sync::{Arc, OnceLock, RwLock},
+    time::SystemTime,
+};
 use thiserror::Error;
 
 const FRONT_MATTER_SEP: &str = "---";
+pub const MARKDOWN_DIR: &str = "markdown";
 
-#[derive(Serialize, Deserialize)]
+/// Directories whose pages are served by their own routes
+const UNROUTED_DIRS: &[&str] = &["changelog", "news"];
+
+/// Prepended to ids in user markdown, so that they can't clash with the
+/// site's own.
+const USER_ID_PREFIX: &str = "user-content-";
+
+/// One of syntect's default themes, picked to suit the dark background
+const HIGHLIGHT_THEME: &str = "base16-ocean.dark";
+
+/// Admonition kinds, as (marker, classes, default title)
+const ADMONITIONS: &[(&str, &str, &str)] = &[
+    ("NOTE", "info", "Note"),
+    ("TIP", "info", "Tip"),
+    ("IMPORTANT", "info", "Important"),
+    ("WARNING", "info warning", "Warning"),
+    ("CAUTION", "info warning", "Caution"),
+];
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct FrontMatter {
     pub category: String,
     pub page: Option<String>,
     pub title: String,
+    /// Replaces the file name in the page's URL
+    pub slug: Option<String>,
+    pub nav: Option<NavOptions>,
+    pub policy: Option<PolicyOptions>,
+    /// How many of the latest news posts to list below the page
+    pub latest_news: Option<usize>,
+    // Release notes and policies
+    pub version: Option<String>,
+    pub date: Option<NaiveDate>,
+    pub channel: Option<String>,
+    // News posts
+    pub author: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub summary: Option<String>,
 }
 
 #[derive(Error, Debug)]
 pub enum MarkdownError {
     #[error("No front matter was found.")]
     NoFrontMatter,
+    #[error("Front matter is missing the required field `{0}`.")]
+    MissingField(&'static str),
+}
+
+pub struct Page {
+    pub front_matter: FrontMatter,
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub text: PageText,
+    /// SHA-256 hash of the page's source
+    pub sha256: Vec<u8>,
+}
+
+/// Markdown rendered to HTML, along with its contents and text.
+pub struct Rendered {
+    pub html: String,
+    pub toc: Vec<TocEntry>,
+    pub text: PageText,
+}
+
+/// The text of a page without any markup, for the search index.
+#[derive(Clone, Default)]
+pub struct PageText {
+    /// Every heading, one per line
+    pub headings: String,
+    /// Everything else, one block per line
+    pub body: String,
+}
+
+/// A heading linked from a page's table of contents.
+#[derive(Serialize, Clone)]
+pub struct TocEntry {
+    pub level: u32,
+    /// The heading's HTML id
+    pub id: String,
+    pub title: String,
+}
+
+struct CachedPage {
+    page: Arc<Page>,
+    modified: SystemTime,
+}
+
+struct Pages {
+    files: HashMap<PathBuf, CachedPage>,
+    /// URL paths, without the leading slash, to the files they're served from
+    routes: HashMap<String, PathBuf>,
+    /// Bumped whenever the routed pages change
+    generation: u64,
+    nav: Arc<Nav>,
+    /// Sent the new generation after every change
+    changes: watch::Sender<u64>,
+}
+
+impl Default for Pages {
+    fn default() -> Pages {
+        Pages {
+            files: HashMap::new(),
+            routes: HashMap::new(),
+            generation: 0,
+            nav: Arc::default(),
+            changes: watch::channel(0).0,
+        }
+    }
+}
+
+impl Pages {
+    fn insert(&mut self, key: PathBuf, cached: CachedPage) {
+        self.files.insert(key, cached);
+        self.reindex();
+    }
+
+    fn remove(&mut self, key: &Path) -> bool {
+        let removed = self.files.remove(key).is_some();
+
+        if removed {
+            self.reindex();
+        }
+
+        removed
+    }
+
+    fn reindex(&mut self) {
+        let mut keys: Vec<&PathBuf> = self.files.keys().collect();
+        keys.sort();
+
+        self.routes.clear();
+        self.generation += 1;
+
+        for key in keys {
+            let url = match url_path(key, &self.files[key].page.front_matter) {
+                Some(url) => url,
+                None => continue,
+            };
+
+            if let Some(existing) = self.routes.get(&url) {
+                warn!(
+                    "markdown: {} and {} are both served at /{}, ignoring the latter",
+                    existing.display(),
+                    key.display(),
+                    url
+                );
+                continue;
+            }
+
+            self.routes.insert(url, key.clone());
+        }
+
+        let entries = self
+            .routes
+            .iter()
+            .filter_map(|(url, key)| {
+                let front_matter = &self.files[key].page.front_matter;
+                let options = front_matter.nav.as_ref()?;
+
+                Some(NavEntry::new(url, &front_matter.title, options))
+            })
+            .collect();
+
+        self.nav = Arc::new(Nav::build(entries));
+        self.changes.send_replace(self.generation);
+    }
+}
+
+/// The URL path a page is served at, if it is routed at all.
+/// `rules/index.md` is served at `rules`, and a `slug` replaces the file name.
+fn url_path(key: &Path, front_matter: &FrontMatter) -> Option<String> {
+    let mut segments = key
+        .parent()?
+        .components()
+        .map(|c| c.as_os_str().to_str().map(str::to_string))
+        .collect::<Option<Vec<String>>>()?;
+
+    if segments
+        .first()
+        .is_some_and(|dir| UNROUTED_DIRS.contains(&dir.as_str()))
+    {
+        return None;
+    }
+
+    let stem = key.file_stem()?.to_str()?;
+
+    match front_matter.slug.as_deref().map(|slug| slug.trim_matches('/')) {
+        Some("") => (),
+        Some(slug) => segments.push(slug.to_string()),
+        None if stem == "index" => (),
+        None => segments.push(stem.to_string()),
+    }
+
+    Some(segments.join("/"))
+}
+
+/// Parsed pages under `markdown/`, keyed by their path relative to it.
+///
+/// Every page is parsed at startup. In debug builds, pages are reparsed
+/// when their modification time changes and a filesystem watcher picks up
+/// edits as they happen; release builds never read from disk again.
+///
+/// Clones share the same pages, for use outside of requests.
+#[derive(Clone)]
+pub struct PageCache {
+    pages: Arc<RwLock<Pages>>,
+    _watcher: Option<Arc<RecommendedWatcher>>,
+}
+
+impl PageCache {
+    pub fn load() -> Result<PageCache, Box<dyn Error>> {
+        let mut paths = Vec::new();
+        find_pages(Path::new(MARKDOWN_DIR), &mut paths)?;
+
+        let mut pages = Pages::default();
+
+        for path in paths {
+            let cached = load_page(&path)?;
+            let key = path.strip_prefix(MARKDOWN_DIR)?.to_path_buf();
+
+            pages.files.insert(key, cached);
+        }
+
+        pages.reindex();
+
+        info!(
+            "markdown: cached {} pages, {} routed",
+            pages.files.len(),
+            pages.routes.len()
+        );
+
+        let pages = Arc::new(RwLock::new(pages));
+
+        let watcher = if cfg!(debug_assertions) {
+            Some(Arc::new(watch_pages(pages.clone())?))
+        } else {
+            None
+        };
+
+        Ok(PageCache {
+            pages,
+            _watcher: watcher,
+        })
+    }
+
+    /// The page at `path`, relative to `markdown/`.
+    pub fn get(&self, path: &Path) -> Option<Arc<Page>> {
+        // Never look outside of `markdown/`
+        if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return None;
+        }
+
+        if cfg!(debug_assertions) {
+            revalidate(&self.pages, path);
+        }
+
+        let pages = self.pages.read().unwrap();
+        pages.files.get(path).map(|cached| cached.page.clone())
+    }
+
+    /// Receives the new generation whenever a page is added, removed or
+    /// reloaded.
+    pub fn subscribe(&self) -> watch::Receiver<u64> {
+        self.pages.read().unwrap().changes.subscribe()
+    }
+
+    /// The navigation tree, as of the last change to the pages.
+    pub fn nav(&self) -> Arc<Nav> {
+        self.pages.read().unwrap().nav.clone()
+    }
+
+    /// Every routed page, with its URL path.
+    pub fn routed(&self) -> Vec<(String, Arc<Page>)> {
+        let pages = self.pages.read().unwrap();
+
+        pages
+            .routes
+            .iter()
+            .map(|(url, key)| (url.clone(), pages.files[key].page.clone()))
+            .collect()
+    }
+
+    /// Every page in a directory under `markdown/`, such as the changelog,
+    /// with its path relative to that directory.
+    pub fn in_dir(&self, dir: &str) -> Vec<(PathBuf, Arc<Page>)> {
+        let pages = self.pages.read().unwrap();
+
+        pages
+            .files
+            .iter()
+            .filter_map(|(key, cached)| {
+                let path = key.strip_prefix(dir).ok()?.to_path_buf();
+                Some((path, cached.page.clone()))
+            })
+            .collect()
+    }
+
+    /// The current versions of every routed policy document.
+    pub fn policies(&self) -> Vec<Policy> {
+        let mut policies: Vec<Policy> = self
+            .routed()
+            .into_iter()
+            .filter_map(|(url, page)| {
+                let front_matter = &page.front_matter;
+                let options = front_matter.policy.as_ref()?;
+
+                Some(Policy {
+                    id: options.id.clone(),
+                    version: front_matter.version.clone()?,
+                    title: front_matter.title.clone(),
+                    url: format!("/{}", url),
+                    required: options.required,
+                    sha256: super::to_hex(&page.sha256),
+                })
+            })
+            .collect();
+
+        policies.sort_by(|a, b| a.url.cmp(&b.url));
+        policies
+    }
+
+    /// The current versions of the policies every user must accept before
+    /// their session begins.
+    pub fn required_policies(&self) -> Vec<Policy> {
+        self.policies().into_iter().filter(|p| p.required).collect()
+    }
+
+    pub fn policy(&self, id: &str) -> Option<Policy> {
+        self.policies().into_iter().find(|p| p.id == id)
+    }
+
+    /// The page served at a URL path, given without its leading slash.
+    /// Only paths in the index resolve, so no URL can reach other files.
+    pub fn route(&self, url: &str) -> Option<Arc<Page>> {
+        let key = self.pages.read().unwrap().routes.get(url)?.clone();
+        self.get(&key)
+    }
+}
+
+fn find_pages(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    for dir_entry in fs::read_dir(dir)? {
+        let path = dir_entry?.path();
+
+        if path.is_dir() {
+            find_pages(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            paths.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+fn load_page(path: &Path) -> Result<CachedPage, Box<dyn Error>> {
+    let modified = fs::metadata(path)?.modified()?;
+    let page = parse(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    Ok(CachedPage {
+        page: Arc::new(page),
+        modified,
+    })
+}
+
+/// Brings the cached copy of a page in line with the file on disk.
+fn revalidate(pages: &RwLock<Pages>, key: &Path) {
+    let path = Path::new(MARKDOWN_DIR).join(key);
+
+    let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+        Ok(modified) => modified,
+        Err(_) => {
+            if pages.write().unwrap().remove(key) {
+                info!("markdown: removed {}", key.display());
+            }
+            return;
+        }
+    };
+
+    let is_current = pages
+        .read()
+        .unwrap()
+        .files
+        .get(key)
+        .is_some_and(|cached| cached.modified == modified);
+
+    if is_current || path.extension().is_none_or(|ext| ext != "md") {
+        return;
+    }
+
+    // A page which fails to parse keeps serving its last good version
+    match load_page(&path) {
+        Ok(cached) => {
+            pages.write().unwrap().insert(key.to_path_buf(), cached);
+            info!("markdown: reloaded {}", key.display());
+        }
+        Err(err) => error!("markdown: failed to reload {}", err),
+    }
+}
+
+fn watch_pages(pages: Arc<RwLock<Pages>>) -> Result<RecommendedWatcher, Box<dyn Error>> {
+    let root = fs::canonicalize(MARKDOWN_DIR)?;
+    let watch_root = root.clone();
+
+    let mut watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+        let event = match result {
+            Ok(event) => event,
+            Err(err) => {
+                error!("markdown: watcher error: {}", err);
+                return;
+            }
+        };
+
+        for path in event.paths {
+            if let Ok(key) = path.strip_prefix(&root) {
+                revalidate(&pages, key);
+            }
+        }
+    })?;
+
+    watcher.watch(&watch_root, RecursiveMode::Recursive)?;
+
+    Ok(watcher)
 }
 
 #[derive(Serialize)]
@@ -25,52 +460,59 @@ struct MarkdownContext<'a> {
     base: BaseData<'a>,
     html: &'a str,
     front_matter: FrontMatter,
+    toc: &'a [TocEntry],
+    section: Option<NavEntry>,
+    latest_news: &'a [NewsPost],
 }
 
-macro_rules! markdown_route {
-    ($route_name:ident, $route:literal, $file_name:literal) => {
-        #[get($route)]
-        fn $route_name(
-            csrf: crate::util::CsrfToken,
-            user_session: crate::util::UserSession,
-        ) -> Result<rocket_dyn_templates::Template, rocket::http::Status> {
-            let result = crate::util::markdown::template(
-                $file_name,
-                crate::routes::BaseData {
-                    user: user_session.user,
-                    csrf_token: &csrf.token,
-                },
-            );
-
-            match result {
-                Ok(template) => Ok(template),
-                Err(err) => {
-                    error!("error parsing markdown: {}", err);
-                    Err(rocket::http::Status::InternalServerError)
-                }
-            }
-        }
-    };
+/// GitHub flavored markdown, with ids on headings for the table of contents.
+fn options() -> ComrakOptions {
+    ComrakOptions {
+        extension: ComrakExtensionOptions {
+            strikethrough: true,
+            table: true,
+            autolink: true,
+            tasklist: true,
+            footnotes: true,
+            header_ids: Some(String::new()),
+            front_matter_delimiter: Some(FRONT_MATTER_SEP.to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
 }
 
-pub(crate) use markdown_route;
+/// Highlights fenced code blocks with inline styles.
+struct Highlighter(SyntectAdapter<'static>);
 
-pub fn template<'a>(file_name: &str, base_data: BaseData<'a>) -> Result<Template, Box<dyn Error>> {
-    let path = Path::new("markdown").join(file_name);
+impl SyntaxHighlighterAdapter for Highlighter {
+    fn highlight(&self, lang: Option<&str>, code: &str) -> String {
+        // The adapter leaves the newline after syntect's <pre> tag behind
+        let html = self.0.highlight(lang, code);
+        html.strip_prefix('\n').unwrap_or(&html).to_string()
+    }
+
+    fn build_pre_tag(&self, attributes: &HashMap<String, String>) -> String {
+        self.0.build_pre_tag(attributes)
+    }
+
+    fn build_code_tag(&self, attributes: &HashMap<String, String>) -> String {
+        self.0.build_code_tag(attributes)
+    }
+}
+
+/// Loading the syntax definitions is slow, so this is only done once.
+fn highlighter() -> &'static Highlighter {
+    static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+    HIGHLIGHTER.get_or_init(|| Highlighter(SyntectAdapter::new(HIGHLIGHT_THEME)))
+}
+
+pub fn parse(path: &Path) -> Result<Page, Box<dyn Error>> {
     let contents = fs::read_to_string(path)?;
 
+    let options = options();
     let arena = Arena::new();
-    let root = comrak::parse_document(
-        &arena,
-        &contents,
-        &ComrakOptions {
-            extension: ComrakExtensionOptions {
-                front_matter_delimiter: Some(FRONT_MATTER_SEP.to_string()),
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    );
+    let root = comrak::parse_document(&arena, &contents, &options);
 
     let front_matter = root
         .children()
@@ -90,16 +532,456 @@ pub fn template<'a>(file_name: &str, base_data: BaseData<'a>) -> Result<Template
 
     let front_matter: FrontMatter = serde_yaml::from_str(&front_matter)?;
 
+    if front_matter.policy.is_some() && front_matter.version.is_none() {
+        return Err(MarkdownError::MissingField("version").into());
+    }
+
+    let rendered = render(&arena, root, &options)?;
+
+    Ok(Page {
+        front_matter,
+        html: rendered.html,
+        toc: rendered.toc,
+        text: rendered.text,
+        sha256: super::sha256(&contents),
+    })
+}
+
+/// Renders markdown written by users, such as wiki pages. It has no front
+/// matter, and any raw HTML in it is let through comrak and then sanitized
+/// along with everything else. Ids and the links to them are prefixed with
+/// `USER_ID_PREFIX`.
+pub fn render_untrusted(contents: &str) -> Result<Rendered, Box<dyn Error>> {
+    let mut options = options();
+    options.extension.front_matter_delimiter = None;
+    options.render.unsafe_ = true;
+
+    let arena = Arena::new();
+    let root = comrak::parse_document(&arena, contents, &options);
+
+    let mut rendered = render(&arena, root, &options)?;
+    rendered.html = sanitizer().clean(&rendered.html).to_string();
+
+    for entry in &mut rendered.toc {
+        entry.id.insert_str(0, USER_ID_PREFIX);
+    }
+
+    Ok(rendered)
+}
+
+/// Renders the small part of markdown allowed in direct messages: emphasis,
+/// links, code, quotes and lists. Headings, images and the like are stripped,
+/// and HTML is shown as typed.
+pub fn render_lite(contents: &str) -> String {
+    let options = ComrakOptions {
+        extension: ComrakExtensionOptions {
+            strikethrough: true,
+            autolink: true,
+            ..Default::default()
+        },
+        render: ComrakRenderOptions {
+            // Line breaks are kept, as in any chat
+            hardbreaks: true,
+            escape: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let html = comrak::markdown_to_html(contents, &options);
+    lite_sanitizer().clean(&html).to_string()
+}
+
+fn render<'a>(
+    arena: &'a Arena<AstNode<'a>>,
+    root: &'a AstNode<'a>,
+    options: &ComrakOptions,
+) -> Result<Rendered, Box<dyn Error>> {
+    let mut plugins = ComrakPlugins::default();
+    plugins.render.codefence_syntax_highlighter = Some(highlighter());
+
+    let text = page_text(root);
+    let toc = table_of_contents(root);
+
+    // Admonitions are raw HTML blocks, so any HTML the page itself has is
+    // omitted here instead, the way comrak would have
+    let mut options = options.clone();
+    if !options.render.unsafe_ {
+        omit_raw_html(root);
+        options.render.unsafe_ = true;
+    }
+
+    insert_admonitions(arena, root);
+
     let mut html = Vec::new();
-    comrak::format_html(root, &ComrakOptions::default(), &mut html)?;
-    let html = str::from_utf8(&html)?;
+    comrak::format_html_with_plugins(root, &options, &mut html, &plugins)?;
+    let html = String::from_utf8(html)?;
+
+    Ok(Rendered { html, toc, text })
+}
+
+/// Allows everything the renderer itself produces, on top of ammonia's
+/// defaults. Inline styles are only kept for the highlighter's colours, and
+/// ids are prefixed along with the fragment links pointing at them.
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+
+        builder
+            .add_tags(&["input", "section"])
+            .add_tag_attributes("a", &["id", "aria-hidden"])
+            .add_tag_attributes("li", &["id"])
+            .add_tag_attributes("input", &["type", "checked", "disabled"])
+            .add_tag_attributes("pre", &["style"])
+            .add_tag_attributes("span", &["style"])
+            .add_tag_attributes("td", &["align"])
+            .add_tag_attributes("th", &["align"])
+            .add_allowed_classes("a", &["anchor", "footnote-backref"])
+            .add_allowed_classes("div", &["info", "info__title", "warning"])
+            .add_allowed_classes("section", &["footnotes"])
+            .add_allowed_classes("sup", &["footnote-ref"])
+            .attribute_filter(|element, attribute, value| match (element, attribute) {
+                ("input", "type") if value != "checkbox" => None,
+                (_, "style") => safe_style(value).map(Into::into),
+                (_, "id") => Some(format!("{}{}", USER_ID_PREFIX, value).into()),
+                ("a", "href") => match value.strip_prefix('#') {
+                    Some(fragment) => Some(format!("#{}{}", USER_ID_PREFIX, fragment).into()),
+                    None => Some(value.into()),
+                },
+                _ => Some(value.into()),
+            });
+
+        builder
+    })
+}
+
+fn lite_sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::default();
+
+        builder.tags(
+            [
+                "a", "blockquote", "br", "code", "del", "em", "li", "ol", "p", "pre", "strong",
+                "ul",
+            ]
+            .into_iter()
+            .collect(),
+        );
+
+        builder
+    })
+}
+
+/// Keeps the declarations syntect writes, dropping the whole attribute if
+/// anything else is in it.
+fn safe_style(style: &str) -> Option<String> {
+    const PROPERTIES: &[&str] = &[
+        "color",
+        "background-color",
+        "font-weight",
+        "font-style",
+        "text-decoration",
+    ];
+
+    let mut declarations = Vec::new();
+
+    for declaration in style.split(';').map(str::trim).filter(|d| !d.is_empty()) {
+        let (property, value) = declaration.split_once(':')?;
+        let (property, value) = (property.trim(), value.trim());
+
+        let value_ok = value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '#' | '-' | ' '));
+
+        if !PROPERTIES.contains(&property) || !value_ok {
+            return None;
+        }
+
+        declarations.push(format!("{}:{};", property, value));
+    }
+
+    Some(declarations.concat())
+}
+
+/// Plain text of a node, the same way comrak builds heading ids from it.
+fn plain_text<'a>(node: &'a AstNode<'a>, output: &mut String) {
+    match &node.data.borrow().value {
+        NodeValue::Text(literal) | NodeValue::Code(NodeCode { literal, .. }) => {
+            output.push_str(&String::from_utf8_lossy(literal))
+        }
+        NodeValue::LineBreak | NodeValue::SoftBreak => output.push(' '),
+        _ => {
+            for child in node.children() {
+                plain_text(child, output);
+            }
+        }
+    }
+}
+
+fn page_text<'a>(root: &'a AstNode<'a>) -> PageText {
+    let mut text = PageText::default();
+
+    for node in root.descendants() {
+        let output = match &node.data.borrow().value {
+            NodeValue::Heading(_) => &mut text.headings,
+            NodeValue::Paragraph | NodeValue::TableCell => &mut text.body,
+            NodeValue::CodeBlock(code) => {
+                text.body.push_str(&String::from_utf8_lossy(&code.literal));
+                continue;
+            }
+            _ => continue,
+        };
+
+        plain_text(node, output);
+        output.push('\n');
+    }
+
+    text
+}
 
-    Ok(Template::render(
+/// Second and third level headings, linked by the ids comrak gives them.
+fn table_of_contents<'a>(root: &'a AstNode<'a>) -> Vec<TocEntry> {
+    let mut anchorizer = Anchorizer::new();
+    let mut toc = Vec::new();
+
+    for node in root.descendants() {
+        let level = match node.data.borrow().value {
+            NodeValue::Heading(NodeHeading { level, .. }) => level,
+            _ => continue,
+        };
+
+        let mut title = String::new();
+        plain_text(node, &mut title);
+
+        // Every heading takes up an id, even those left out of the contents
+        let id = anchorizer.anchorize(title.clone());
+
+        if (2..=3).contains(&level) {
+            toc.push(TocEntry { level, id, title });
+        }
+    }
+
+    toc
+}
+
+/// Callouts, written like GitHub's alerts, with an optional title:
+///
+/// ```markdown
+/// > [!WARNING] Heads up!
+/// > This will delete your save.
+/// ```
+///
+/// Each block quote with a marker is replaced by its contents, wrapped in
+/// HTML blocks which open and close an `.info` box.
+fn insert_admonitions<'a>(arena: &'a Arena<AstNode<'a>>, root: &'a AstNode<'a>) {
+    let quotes: Vec<&AstNode> = root
+        .descendants()
+        .filter(|node| matches!(node.data.borrow().value, NodeValue::BlockQuote))
+        .collect();
+
+    for quote in quotes {
+        let (class, title) = match take_admonition_marker(quote) {
+            Some(marker) => marker,
+            None => continue,
+        };
+
+        let open = format!(
+            "<div class=\"{class}\">\n<div class=\"{title_class}\">{title}</div>",
+            class = class,
+            title_class = class.replace("info", "info__title"),
+            title = super::escape_html(&title),
+        );
+
+        quote.insert_before(html_block(arena, open));
+
+        while let Some(child) = quote.first_child() {
+            quote.insert_before(child);
+        }
+
+        quote.insert_before(html_block(arena, "</div>".to_string()));
+        quote.detach();
+    }
+}
+
+fn html_block<'a>(arena: &'a Arena<AstNode<'a>>, html: String) -> &'a AstNode<'a> {
+    let mut block = NodeHtmlBlock::default();
+    block.literal = html.into_bytes();
+
+    arena.alloc(Node::new(RefCell::new(Ast::new(NodeValue::HtmlBlock(
+        block,
+    )))))
+}
+
+/// Replaces raw HTML with the comment comrak leaves when it isn't allowed.
+fn omit_raw_html<'a>(root: &'a AstNode<'a>) {
+    const OMITTED: &[u8] = b"<!-- raw HTML omitted -->";
+
+    for node in root.descendants() {
+        match node.data.borrow_mut().value {
+            NodeValue::HtmlBlock(ref mut block) => block.literal = OMITTED.to_vec(),
+            NodeValue::HtmlInline(ref mut literal) => *literal = OMITTED.to_vec(),
+            _ => (),
+        }
+    }
+}
+
+/// Removes the `[!KIND] Title` line from the start of a block quote,
+/// returning the admonition's classes and title if there was one.
+fn take_admonition_marker<'a>(quote: &'a AstNode<'a>) -> Option<(&'static str, String)> {
+    let paragraph = quote.first_child()?;
+    if !matches!(paragraph.data.borrow().value, NodeValue::Paragraph) {
+        return None;
+    }
+
+    let mut line = Vec::new();
+    let mut first_line = String::new();
+
+    for node in paragraph.children() {
+        let is_break = matches!(
+            node.data.borrow().value,
+            NodeValue::SoftBreak | NodeValue::LineBreak
+        );
+
+        line.push(node);
+
+        if is_break {
+            break;
+        }
+
+        plain_text(node, &mut first_line);
+    }
+
+    let rest = first_line.strip_prefix("[!")?;
+    let (kind, title) = rest.split_once(']')?;
+
+    let &(_, class, default_title) = ADMONITIONS
+        .iter()
+        .find(|(name, _, _)| name.eq_ignore_ascii_case(kind))?;
+
+    let title = match title.trim() {
+        "" => default_title.to_string(),
+        title => title.to_string(),
+    };
+
+    for node in line {
+        node.detach();
+    }
+
+    if paragraph.first_child().is_none() {
+        paragraph.detach();
+    }
+
+    Some((class, title))
+}
+
+pub fn template(
+    page: &Page,
+    section: Option<NavEntry>,
+    latest_news: &[NewsPost],
+    base_data: BaseData<'_>,
+) -> Template {
+    Template::render(
         "markdown",
         MarkdownContext {
             base: base_data,
-            html: &html,
-            front_matter,
+            html: &page.html,
+            front_matter: page.front_matter.clone(),
+            toc: &page.toc,
+            section,
+            latest_news,
         },
-    ))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_page(contents: &str) -> String {
+        let options = options();
+        let arena = Arena::new();
+        let root = comrak::parse_document(&arena, contents, &options);
+
+        render(&arena, root, &options).unwrap().html
+    }
+
+    #[test]
+    fn renders_admonitions() {
+        let html = render_page("> [!WARNING] Heads up!\n> This will delete your save.\n");
+
+        assert!(html.starts_with("<div class=\"info warning\">"), "{}", html);
+        assert!(html.contains("Heads up!</div>\n<p>This will delete your save.</p>\n</div>"));
+        assert!(!html.contains("blockquote"));
+    }
+
+    #[test]
+    fn omits_raw_html_in_pages() {
+        let html = render_page("<div class=\"info\">\n\nhi <b>there</b>\n\n> [!NOTE]\n> ok\n");
+
+        assert!(!html.contains("<b>"), "{}", html);
+        assert!(!html.contains("<div class=\"info\">\n\n"));
+        assert_eq!(html.matches("<!-- raw HTML omitted -->").count(), 3);
+    }
+
+    #[test]
+    fn sanitizes_admonitions_in_untrusted_markdown() {
+        let rendered = render_untrusted("> [!TIP] <script>x</script>\n> tip\n").unwrap();
+
+        assert!(
+            rendered.html.contains("<div class=\"info\">"),
+            "{}",
+            rendered.html
+        );
+        assert!(!rendered.html.contains("<script>"));
+    }
+
+    #[test]
+    fn prefixes_ids_in_untrusted_markdown() {
+        let rendered =
+            render_untrusted("## Logout\n\n<a id=\"login\" href=\"#login\">x</a>\n").unwrap();
+
+        assert!(!rendered.html.contains("id=\"login\""), "{}", rendered.html);
+        assert!(rendered
+            .html
+            .contains("id=\"user-content-login\" href=\"#user-content-login\""));
+        assert!(rendered.html.contains("href=\"#user-content-logout\""));
+        assert!(rendered.html.contains("id=\"user-content-logout\""));
+        assert_eq!(rendered.toc[0].id, "user-content-logout");
+    }
+
+    #[test]
+    fn caches_pages_by_their_path_under_markdown() {
+        let pages = PageCache::load().unwrap();
+
+        let page = pages.get(Path::new("rules/code_of_conduct.md")).unwrap();
+        assert_eq!(page.front_matter.title, "Code of Conduct");
+        assert!(pages.get(Path::new("rules/missing.md")).is_none());
+
+        // Nothing outside of `markdown/`, even if it exists
+        assert!(pages.get(Path::new("../README.md")).is_none());
+        assert!(pages.get(Path::new("rules/../index.md")).is_none());
+        assert!(pages.get(Path::new("/etc/passwd")).is_none());
+    }
+
+    #[test]
+    fn routes_pages_by_url_path() {
+        let pages = PageCache::load().unwrap();
+        let title = |url: &str| pages.route(url).map(|page| page.front_matter.title.clone());
+
+        assert_eq!(title("").as_deref(), Some("Home"));
+        assert_eq!(title("rules").as_deref(), Some("Rules"));
+        assert_eq!(title("rules/code").as_deref(), Some("Code of Conduct"));
+
+        assert_eq!(title("rules/code_of_conduct"), None);
+        assert_eq!(title("rules/index"), None);
+        assert_eq!(title("rules/missing"), None);
+        assert_eq!(title("rules/../about"), None);
+        // Served by routes of their own
+        assert_eq!(title("changelog/0.1.0"), None);
+        assert_eq!(title("news/2022-06-25-website-updates"), None);
+    }
 }
@@ -7,8 +7,39 @@ pub enum SiteMessages {
     UsernameInUse,
     UsernameInvalid,
     LoginFailed,
+    LoginThrottled,
     PasswordIncorrect,
     PasswordsDontMatch,
+    ReleaseExists,
+    ReleaseFileMissing,
+    ReleaseHashInvalid,
+    ReleaseDateInvalid,
+    ReleaseNotFound,
+    PatchExists,
+    ManifestInvalid,
+    ManifestKeyUnknown,
+    ManifestSignatureInvalid,
+    ManifestOutdated,
+    PoliciesNotAccepted,
+    GithubHandleInUse,
+    GithubNotVerified,
+    AgreementNotAccepted,
+    AgreementAlreadySigned,
+    WikiEditConflict,
+    WikiNoChanges,
+    ForumSlugInvalid,
+    ForumCategoryExists,
+    ForumThreadLocked,
+    ForumBanned,
+    MessagesBanned,
+    MessagesBlocked,
+    MessagesRateLimited,
+    MessagesUnknownRecipient,
+    MessagesNoRecipients,
+    MessagesTooManyMembers,
+    MessageAlreadyReported,
+    EmailAddressInvalid,
+    InviteInvalid,
 }
 
 impl SiteMessages {
@@ -19,8 +50,39 @@ impl SiteMessages {
             Self::UsernameInvalid => "Username contains invalid characters.",
             Self::UsernameInUse => "Username is in use.",
             Self::LoginFailed => "Invalid username/password.",
+            Self::LoginThrottled => "Too many login attempts. Wait a few minutes and try again.",
             Self::PasswordIncorrect => "Password is incorrect.",
             Self::PasswordsDontMatch => "Passwords don't match.",
+            Self::ReleaseExists => "A release with this version, channel and platform already exists.",
+            Self::ReleaseFileMissing => "Upload a file, or provide its URL, size and SHA-256 hash.",
+            Self::ReleaseHashInvalid => "SHA-256 hash must be 64 hexadecimal characters.",
+            Self::ReleaseDateInvalid => "Release date is invalid.",
+            Self::ReleaseNotFound => "No release with this version, channel and platform exists.",
+            Self::PatchExists => "A patch from this version already exists for the release.",
+            Self::ManifestInvalid => "Signed manifest is malformed.",
+            Self::ManifestKeyUnknown => "Manifest was signed with a key which is not trusted.",
+            Self::ManifestSignatureInvalid => "Manifest signature is invalid.",
+            Self::ManifestOutdated => "A manifest issued at the same time or later is already published.",
+            Self::PoliciesNotAccepted => "You must accept the rules to continue.",
+            Self::GithubHandleInUse => "This GitHub account was used by another user to sign the agreement.",
+            Self::GithubNotVerified => "Verify your GitHub account before signing.",
+            Self::AgreementNotAccepted => "You must agree to the contributor agreement to sign it.",
+            Self::AgreementAlreadySigned => "You have already signed this version of the agreement.",
+            Self::WikiEditConflict => "Someone else edited this page while you were editing it. Review their changes and try again.",
+            Self::WikiNoChanges => "Nothing was changed.",
+            Self::ForumSlugInvalid => "Slugs may only contain lowercase letters, digits and hyphens.",
+            Self::ForumCategoryExists => "A category with this slug already exists.",
+            Self::ForumThreadLocked => "This thread is locked.",
+            Self::ForumBanned => "Banned users can't post in the forums.",
+            Self::MessagesBanned => "Banned users can't send messages.",
+            Self::MessagesBlocked => "You can't message someone you have blocked or who has blocked you.",
+            Self::MessagesRateLimited => "You're sending messages too quickly. Wait a moment and try again.",
+            Self::MessagesUnknownRecipient => "One of these users doesn't exist.",
+            Self::MessagesNoRecipients => "Enter the username of at least one other user.",
+            Self::MessagesTooManyMembers => "Groups can have at most 10 members, including you.",
+            Self::MessageAlreadyReported => "You have already reported this message.",
+            Self::EmailAddressInvalid => "Email address is invalid.",
+            Self::InviteInvalid => "This invite code is invalid, expired or used up.",
         }
     }
 
@@ -29,6 +91,22 @@ impl SiteMessages {
             Self::UsernameInUse => Some("username"),
             Self::PasswordIncorrect => Some("current_password"),
             Self::PasswordsDontMatch => Some("verify_password"),
+            Self::ReleaseHashInvalid => Some("sha256"),
+            Self::ReleaseDateInvalid => Some("release_date"),
+            Self::ReleaseNotFound => Some("version"),
+            Self::PatchExists => Some("from_version"),
+            Self::ManifestInvalid
+            | Self::ManifestKeyUnknown
+            | Self::ManifestSignatureInvalid
+            | Self::ManifestOutdated => Some("signed_manifest"),
+            Self::PoliciesNotAccepted => Some("accept_policies"),
+            Self::AgreementNotAccepted => Some("agree"),
+            Self::ForumSlugInvalid | Self::ForumCategoryExists => Some("slug"),
+            Self::MessagesUnknownRecipient
+            | Self::MessagesNoRecipients
+            | Self::MessagesTooManyMembers => Some("to"),
+            Self::EmailAddressInvalid => Some("recipient"),
+            Self::InviteInvalid => Some("invite"),
             _ => None,
         }
     }
@@ -0,0 +1,226 @@
+use super::{
+    live::{LiveEvent, Topic},
+    markdown,
+};
+use crate::db::models::{
+    Conversation, ConversationMember, Message, MessageInfo, User, UserBlock, MAX_GROUP_MEMBERS,
+};
+use chrono::{Duration, Utc};
+use diesel::{
+    result::Error as DieselError, Connection, OptionalExtension, PgConnection, QueryResult,
+};
+use rocket::serde::Serialize;
+use thiserror::Error;
+
+pub const MAX_MESSAGE_LENGTH: usize = 4000;
+
+/// How many messages anyone can send within the window
+const RATE_LIMIT_MESSAGES: i64 = 10;
+const RATE_LIMIT_WINDOW: i64 = 30; // seconds
+
+#[derive(Error, Debug)]
+pub enum MessagingError {
+    #[error("Banned users can't send messages.")]
+    Banned,
+    #[error("Either side of the conversation has blocked the other.")]
+    Blocked,
+    #[error("Too many messages were sent recently.")]
+    RateLimited,
+    #[error("No user named {0} exists.")]
+    UnknownUser(String),
+    #[error("A conversation needs someone besides its author.")]
+    NoRecipients,
+    #[error("Groups can have at most {} members.", MAX_GROUP_MEMBERS)]
+    TooManyMembers,
+    #[error("Database error: {0}.")]
+    Database(#[from] DieselError),
+}
+
+/// A message as shown to one of the members of its conversation.
+#[derive(Serialize, Clone)]
+pub struct RenderedMessage {
+    #[serde(flatten)]
+    pub message: MessageInfo,
+    pub html: String,
+    /// Sent by someone the member has blocked, so the content is left out
+    pub hidden: bool,
+}
+
+impl RenderedMessage {
+    /// Hides the message if its author is one of `blocked`.
+    pub fn new(mut message: MessageInfo, blocked: &[i64]) -> RenderedMessage {
+        if blocked.contains(&message.author_id) {
+            message.content.clear();
+
+            return RenderedMessage {
+                message,
+                html: String::new(),
+                hidden: true,
+            };
+        }
+
+        RenderedMessage {
+            html: markdown::render_lite(&message.content),
+            message,
+            hidden: false,
+        }
+    }
+}
+
+/// Must be called in the transaction that sends the message.
+fn check_rate_limit(c: &PgConnection, author_id: i64) -> Result<(), MessagingError> {
+    let since = Utc::now() - Duration::seconds(RATE_LIMIT_WINDOW);
+
+    // Counted in the database, so the limit holds across every web instance.
+    // The author stays locked until the message is in, so that messages sent
+    // at once are counted one after another.
+    User::lock(c, author_id)?;
+
+    if Message::count_since(c, author_id, since)? >= RATE_LIMIT_MESSAGES {
+        return Err(MessagingError::RateLimited);
+    }
+
+    Ok(())
+}
+
+/// Whether the user can send messages to the conversation. Blocking ends
+/// a one-to-one conversation, while groups carry on with messages from
+/// blocked members hidden.
+pub fn can_send(
+    c: &PgConnection,
+    conversation: &Conversation,
+    members: &[ConversationMember],
+    author: &User,
+) -> Result<(), MessagingError> {
+    if author.banned {
+        return Err(MessagingError::Banned);
+    }
+
+    let others: Vec<i64> = members
+        .iter()
+        .map(|member| member.user_id)
+        .filter(|user_id| *user_id != author.id)
+        .collect();
+
+    if !conversation.is_group && UserBlock::any_between(c, author.id, &others)? {
+        return Err(MessagingError::Blocked);
+    }
+
+    Ok(())
+}
+
+/// Sends a message to a conversation the author is in, and passes it on to
+/// the live streams of its members.
+pub fn send(
+    c: &PgConnection,
+    conversation: &Conversation,
+    author: &User,
+    content: &str,
+) -> Result<MessageInfo, MessagingError> {
+    c.transaction(|| {
+        let members = Conversation::members(c, conversation.id)?;
+
+        can_send(c, conversation, &members, author)?;
+        check_rate_limit(c, author.id)?;
+
+        let message = Message::send(c, conversation.id, author.id, content)?;
+
+        let info = MessageInfo {
+            id: message.id,
+            conversation_id: message.conversation_id,
+            author_id: message.author_id,
+            content: message.content,
+            created_at: message.created_at,
+            author: author.username.clone(),
+        };
+
+        publish(c, &info, &members)?;
+
+        Ok(info)
+    })
+}
+
+fn publish(
+    c: &PgConnection,
+    message: &MessageInfo,
+    members: &[ConversationMember],
+) -> QueryResult<()> {
+    let rendered = RenderedMessage::new(message.clone(), &[]);
+
+    for member in members {
+        if UserBlock::has_blocked(c, member.user_id, message.author_id)? {
+            continue;
+        }
+
+        LiveEvent::new(Topic::Messages, Some(member.user_id), &rendered).publish(c);
+    }
+
+    Ok(())
+}
+
+/// Starts a conversation with the given users and sends its first message.
+/// Messaging a single user carries on the conversation they already have,
+/// if any, while messaging several starts a new group. Nothing is created
+/// unless the message is sent.
+pub fn start(
+    c: &mut PgConnection,
+    author: &User,
+    usernames: &[String],
+    title: Option<&str>,
+    content: &str,
+) -> Result<(Conversation, MessageInfo), MessagingError> {
+    if author.banned {
+        return Err(MessagingError::Banned);
+    }
+
+    let mut member_ids = vec![author.id];
+
+    for username in usernames {
+        let user = User::find(c, username)
+            .optional()?
+            .ok_or_else(|| MessagingError::UnknownUser(username.clone()))?;
+
+        if !member_ids.contains(&user.id) {
+            member_ids.push(user.id);
+        }
+    }
+
+    if member_ids.len() < 2 {
+        return Err(MessagingError::NoRecipients);
+    }
+
+    if member_ids.len() > MAX_GROUP_MEMBERS {
+        return Err(MessagingError::TooManyMembers);
+    }
+
+    let c: &PgConnection = c;
+
+    c.transaction(|| {
+        if UserBlock::any_between(c, author.id, &member_ids[1..])? {
+            return Err(MessagingError::Blocked);
+        }
+
+        // Checked before anything is created, as well as when sending
+        check_rate_limit(c, author.id)?;
+
+        let conversation = match member_ids[..] {
+            [_, recipient] => Conversation::find_or_create_direct(c, author.id, recipient)?,
+            _ => Conversation::create(c, &member_ids, true, title.filter(|t| !t.is_empty()))?,
+        };
+
+        let message = send(c, &conversation, author, content)?;
+
+        Ok((conversation, message))
+    })
+}
+
+/// Splits a list of usernames separated by commas or new lines. Usernames
+/// may contain spaces, so spaces don't separate them.
+pub fn parse_recipients(recipients: &str) -> Vec<String> {
+    recipients
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|username| !username.is_empty())
+        .map(str::to_string)
+        .collect()
+}
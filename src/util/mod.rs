@@ -8,23 +8,76 @@ use fern::{
 };
 use log::LevelFilter;
 use rand::{distributions::Alphanumeric, rngs::OsRng, Rng};
+use rocket::Request;
 use sha2::{Digest, Sha256};
+use std::{fs::File, io, path::Path};
 
 mod captcha;
+mod changelog;
 mod csrf;
+pub mod diff;
+pub mod game_server;
+mod github;
+pub mod invite;
+pub mod jobs;
+pub mod live;
+mod login;
+pub mod mail;
 pub mod markdown;
 mod messages;
+pub mod messaging;
+pub mod nav;
+mod news;
+mod notifier;
+mod pagination;
+pub mod policy;
+mod search;
 mod session;
+pub mod update;
+mod upload;
 
 pub use captcha::CaptchaVerifier;
 
+pub use changelog::{Changelog, ChangelogEntry};
+
+pub use game_server::{GameServerAuth, GameServerConfig, GameServerUtils, RegistrationAuth};
+
+pub use github::{GithubAccount, GithubOAuth};
+
+pub use invite::InviteConfig;
+
+pub use jobs::{JobRegistry, JobWorker};
+
 pub use csrf::CsrfToken;
 pub use csrf::CsrfVerify;
 
-pub use session::{SessionUtils, UserSession};
+pub use live::{LiveEvent, LiveEvents, LiveListener, Topic};
+
+pub use login::{authenticate, LoginError, PurgeLoginAttempts};
+
+pub use mail::{Mailer, SendEmails, MAIL_POLL};
+
+pub use session::{
+    ApiSession, PurgeExpiredPresence, PurgeExpiredSessions, SessionUtils, UserSession,
+    SESSION_PURGE,
+};
 
 pub use messages::SiteMessages;
 
+pub use news::{News, NewsPost};
+
+pub use notifier::notify;
+
+pub use pagination::Pagination;
+
+pub use policy::Policy;
+
+pub use search::SearchIndexer;
+
+pub use update::UpdateKeys;
+
+pub use upload::{AdminUpload, UploadedFile, UPLOAD_LIMIT};
+
 pub fn setup_logging(debug: bool) -> Result<(), InitError> {
     let colors = ColoredLevelConfig::new()
         .debug(Color::Green)
@@ -54,6 +107,34 @@ pub fn setup_logging(debug: bool) -> Result<(), InitError> {
     Ok(())
 }
 
+pub const USERNAME_MAX_LENGTH: usize = 32;
+
+fn valid_username_char(c: char) -> bool {
+    char::is_ascii_alphanumeric(&c)
+        || match c {
+            // a few symbols that are ok
+            ' ' => true,
+            '[' | ']' | '(' | ')' => true,
+            '-' | '_' => true,
+            _ => false,
+        }
+}
+
+/// Checks the characters in a username. Its length is checked separately.
+pub fn is_valid_username(username: &str) -> bool {
+    username.chars().all(valid_username_char)
+}
+
+/// Lowercase letters, digits and hyphens, and none of the route's
+/// `reserved` words. Its length is checked separately.
+pub fn is_valid_slug(slug: &str, reserved: &[&str]) -> bool {
+    !slug.is_empty()
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !reserved.contains(&slug)
+}
+
 pub fn hash_password(argon: &Argon2, password: &str) -> Result<String, ArgonError> {
     let salt = SaltString::generate(&mut OsRng);
     let hashed_pass = argon.hash_password(password.as_bytes(), &salt)?;
@@ -75,3 +156,77 @@ fn sha256(input: &str) -> Vec<u8> {
 
     hasher.finalize().as_slice().into()
 }
+
+pub fn sha256_file(path: &Path) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(hasher.finalize().as_slice().into())
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let chunks = hex.as_bytes().chunks_exact(2);
+
+    // `from_str_radix` would also take a sign
+    if !chunks.remainder().is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    chunks
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+fn bearer_token<'a>(request: &'a Request<'_>) -> Option<&'a str> {
+    request
+        .headers()
+        .get_one("Authorization")?
+        .strip_prefix("Bearer ")
+}
+
+/// For the few places HTML is built outside of templates.
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_round_trips() {
+        let bytes = vec![0x00, 0x7f, 0xab, 0xff];
+
+        assert_eq!(to_hex(&bytes), "007fabff");
+        assert_eq!(from_hex("007fabff"), Some(bytes.clone()));
+        assert_eq!(from_hex("007FABFF"), Some(bytes));
+    }
+
+    #[test]
+    fn from_hex_rejects_anything_else() {
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("+f"), None);
+        assert_eq!(from_hex("-f"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex(" f"), None);
+    }
+
+    #[test]
+    fn slugs_are_lowercase_words_and_not_reserved() {
+        assert!(is_valid_slug("fumo-types-2", &["recent"]));
+
+        assert!(!is_valid_slug("", &[]));
+        assert!(!is_valid_slug("Fumo", &[]));
+        assert!(!is_valid_slug("fumo types", &[]));
+        assert!(!is_valid_slug("../fumo", &[]));
+        assert!(!is_valid_slug("recent", &["recent"]));
+    }
+}
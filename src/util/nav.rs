@@ -0,0 +1,262 @@
+use crate::db::models::User;
+use rocket::serde::{Deserialize, Serialize};
+
+/// Links to pages which aren't markdown, as (URL path, label, order, parent).
+/// Content can be placed around these using `nav.order`.
+const APP_LINKS: &[(&str, &str, i32, Option<&str>)] = &[
+    ("servers", "Servers", 10, Some("")),
+    ("news", "News", 15, None),
+    ("changelog", "Changelog", 20, None),
+    ("wiki", "Wiki", 25, None),
+    ("forums", "Forums", 26, None),
+    ("download", "Download", 30, None),
+];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(crate = "rocket::serde", rename_all = "lowercase")]
+pub enum NavVisibility {
+    #[default]
+    Public,
+    /// Only shown to logged in users
+    Users,
+    /// Only shown to logged out visitors
+    Guests,
+    Hidden,
+}
+
+impl NavVisibility {
+    fn visible_to(&self, user: Option<&User>) -> bool {
+        match self {
+            NavVisibility::Public => true,
+            NavVisibility::Users => user.is_some(),
+            NavVisibility::Guests => user.is_none(),
+            NavVisibility::Hidden => false,
+        }
+    }
+}
+
+/// The `nav` block of a page's front matter. Pages without one aren't in the nav.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(crate = "rocket::serde", default)]
+pub struct NavOptions {
+    /// Defaults to the page's title
+    pub label: Option<String>,
+    /// Entries are sorted by order, then label
+    pub order: i32,
+    /// URL path (without the leading slash) of the entry to nest under
+    pub parent: Option<String>,
+    pub visibility: NavVisibility,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct NavEntry {
+    /// Usable as an HTML id
+    pub id: String,
+    pub path: String,
+    pub label: String,
+    pub href: String,
+    #[serde(skip)]
+    order: i32,
+    #[serde(skip)]
+    parent: Option<String>,
+    #[serde(skip)]
+    visibility: NavVisibility,
+    pub children: Vec<NavEntry>,
+}
+
+impl NavEntry {
+    pub fn new(path: &str, label: &str, options: &NavOptions) -> NavEntry {
+        let id = if path.is_empty() {
+            "home".to_string()
+        } else {
+            path.replace('/', "-")
+        };
+
+        NavEntry {
+            id,
+            path: path.to_string(),
+            label: options.label.clone().unwrap_or_else(|| label.to_string()),
+            href: format!("/{}", path),
+            order: options.order,
+            parent: options.parent.clone(),
+            visibility: options.visibility,
+            children: Vec::new(),
+        }
+    }
+
+    fn sort_key(&self) -> (i32, String) {
+        (self.order, self.label.to_lowercase())
+    }
+
+    fn visible_to(&self, user: Option<&User>) -> Option<NavEntry> {
+        if !self.visibility.visible_to(user) {
+            return None;
+        }
+
+        let mut entry = self.clone();
+        entry.children = filter(&self.children, user);
+
+        Some(entry)
+    }
+
+    fn find(&self, path: &str) -> Option<&NavEntry> {
+        if self.path == path {
+            return Some(self);
+        }
+
+        self.children.iter().find_map(|child| child.find(path))
+    }
+}
+
+fn filter(entries: &[NavEntry], user: Option<&User>) -> Vec<NavEntry> {
+    entries.iter().filter_map(|e| e.visible_to(user)).collect()
+}
+
+/// The navigation tree, built by the page cache whenever it loads the pages.
+#[derive(Default, Debug)]
+pub struct Nav {
+    roots: Vec<NavEntry>,
+}
+
+impl Nav {
+    /// Builds the tree from the given page entries and the app's own links.
+    pub fn build(mut entries: Vec<NavEntry>) -> Nav {
+        for &(path, label, order, parent) in APP_LINKS {
+            entries.push(NavEntry::new(
+                path,
+                label,
+                &NavOptions {
+                    order,
+                    parent: parent.map(str::to_string),
+                    ..Default::default()
+                },
+            ));
+        }
+
+        let depths: Vec<usize> = entries.iter().map(|e| depth(&entries, e)).collect();
+        let mut pending: Vec<(usize, NavEntry)> = depths.into_iter().zip(entries).collect();
+        pending.sort_by_key(|(depth, _)| *depth);
+
+        // Deepest entries are attached first, so every entry has all of its
+        // children by the time it is attached to its own parent
+        let mut roots = Vec::new();
+
+        while let Some((_, mut entry)) = pending.pop() {
+            entry.children.sort_by_key(NavEntry::sort_key);
+
+            let parent = match entry.parent {
+                Some(ref parent) => pending.iter_mut().find(|(_, e)| &e.path == parent),
+                None => {
+                    roots.push(entry);
+                    continue;
+                }
+            };
+
+            match parent {
+                Some((_, parent)) => parent.children.push(entry),
+                None => {
+                    warn!(
+                        "nav: parent of /{} is not in the nav, placing it at the top level",
+                        entry.path
+                    );
+                    roots.push(entry);
+                }
+            }
+        }
+
+        roots.sort_by_key(NavEntry::sort_key);
+
+        Nav { roots }
+    }
+
+    /// The navigation tree as seen by a user.
+    pub fn visible(&self, user: Option<&User>) -> Vec<NavEntry> {
+        filter(&self.roots, user)
+    }
+
+    /// The section a page belongs to, for its sidebar: the page's entry if it
+    /// has children, otherwise its parent. The home page has no section.
+    pub fn section(&self, path: &str, user: Option<&User>) -> Option<NavEntry> {
+        let nav = self.visible(user);
+        let entry = nav.iter().find_map(|e| e.find(path))?;
+
+        let section = if entry.children.is_empty() {
+            find_parent(&nav, path)?
+        } else {
+            entry
+        };
+
+        if section.path.is_empty() {
+            return None;
+        }
+
+        Some(section.clone())
+    }
+}
+
+/// How many ancestors an entry has among `entries`.
+fn depth(entries: &[NavEntry], entry: &NavEntry) -> usize {
+    let mut depth = 0;
+    let mut parent = entry.parent.as_deref();
+
+    while let Some(path) = parent {
+        // Guard against cycles
+        if depth > entries.len() {
+            break;
+        }
+
+        depth += 1;
+        parent = entries
+            .iter()
+            .find(|e| e.path == path)
+            .and_then(|e| e.parent.as_deref());
+    }
+
+    depth
+}
+
+fn find_parent<'a>(entries: &'a [NavEntry], path: &str) -> Option<&'a NavEntry> {
+    entries.iter().find_map(|e| {
+        if e.children.iter().any(|c| c.path == path) {
+            Some(e)
+        } else {
+            find_parent(&e.children, path)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, parent: Option<&str>, visibility: NavVisibility) -> NavEntry {
+        NavEntry::new(
+            path,
+            path,
+            &NavOptions {
+                parent: parent.map(str::to_string),
+                visibility,
+                ..Default::default()
+            },
+        )
+    }
+
+    #[test]
+    fn builds_sections_from_parents() {
+        let nav = Nav::build(vec![
+            entry("", None, NavVisibility::Public),
+            entry("rules", None, NavVisibility::Public),
+            entry("rules/chat", Some("rules"), NavVisibility::Public),
+            entry("staff", None, NavVisibility::Users),
+        ]);
+
+        let section = nav.section("rules/chat", None).unwrap();
+        assert_eq!(section.path, "rules");
+        assert_eq!(section.children[0].path, "rules/chat");
+
+        // App links such as servers nest under the home page
+        assert!(nav.section("servers", None).is_none());
+        assert!(nav.visible(None).iter().all(|e| e.path != "staff"));
+    }
+}
@@ -0,0 +1,126 @@
+use super::markdown::{FrontMatter, MarkdownError, Page, PageCache, PageText};
+use rocket::serde::Serialize;
+use std::{cmp::Reverse, env, error::Error, path::Path};
+
+/// Under `markdown/`
+const NEWS_DIR: &str = "news";
+
+#[derive(Serialize)]
+pub struct NewsPost {
+    /// The post's URL is `/news/<slug>`
+    pub slug: String,
+    pub front_matter: FrontMatter,
+    pub html: String,
+    #[serde(skip)]
+    pub text: PageText,
+}
+
+/// Posts from `markdown/news`, indexed once at startup from the pages
+/// already parsed into the `PageCache`. The listing, tag pages and feeds
+/// are all built from this index.
+pub struct News {
+    /// Newest first
+    posts: Vec<NewsPost>,
+    /// Where the website is hosted, since feeds need absolute links
+    site_url: String,
+}
+
+impl News {
+    pub fn load(pages: &PageCache) -> Result<News, Box<dyn Error>> {
+        let mut posts = Vec::new();
+
+        for (path, page) in pages.in_dir(NEWS_DIR) {
+            let post = Self::load_post(&path, &page)
+                .map_err(|err| format!("{}/{}: {}", NEWS_DIR, path.display(), err))?;
+
+            posts.push(post);
+        }
+
+        posts.sort_by(|a, b| {
+            (Reverse(a.front_matter.date), &a.slug).cmp(&(Reverse(b.front_matter.date), &b.slug))
+        });
+
+        info!("news: indexed {} posts", posts.len());
+
+        let site_url = env::var("SITE_URL").unwrap_or_else(|_| {
+            warn!("Did not find SITE_URL, feeds will link to http://localhost:8000.");
+            "http://localhost:8000".to_string()
+        });
+
+        Ok(News {
+            posts,
+            site_url: site_url.trim_end_matches('/').to_string(),
+        })
+    }
+
+    fn load_post(path: &Path, page: &Page) -> Result<NewsPost, Box<dyn Error>> {
+        let front_matter = page.front_matter.clone();
+
+        if front_matter.date.is_none() {
+            return Err(MarkdownError::MissingField("date").into());
+        }
+
+        if front_matter.author.is_none() {
+            return Err(MarkdownError::MissingField("author").into());
+        }
+
+        if front_matter.summary.is_none() {
+            return Err(MarkdownError::MissingField("summary").into());
+        }
+
+        let slug = match front_matter.slug {
+            Some(ref slug) => slug.trim_matches('/').to_string(),
+            None => path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or("news: file name is not valid UTF-8")?
+                .to_string(),
+        };
+
+        Ok(NewsPost {
+            slug,
+            front_matter,
+            html: page.html.clone(),
+            text: page.text.clone(),
+        })
+    }
+
+    pub fn site_url(&self) -> &str {
+        &self.site_url
+    }
+
+    /// Every post, or those tagged with `tag`.
+    pub fn posts(&self, tag: Option<&str>) -> Vec<&NewsPost> {
+        self.posts
+            .iter()
+            .filter(|p| tag.is_none_or(|tag| p.front_matter.tags.iter().any(|t| t == tag)))
+            .collect()
+    }
+
+    pub fn latest(&self, count: usize) -> &[NewsPost] {
+        &self.posts[..count.min(self.posts.len())]
+    }
+
+    pub fn find(&self, slug: &str) -> Option<&NewsPost> {
+        self.posts.iter().find(|p| p.slug == slug)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn posts_come_from_the_page_cache() {
+        let pages = PageCache::load().unwrap();
+        let news = News::load(&pages).unwrap();
+
+        let post = news.find("2022-06-25-website-updates").unwrap();
+        let page = pages
+            .get(Path::new("news/2022-06-25-website-updates.md"))
+            .unwrap();
+
+        assert_eq!(post.html, page.html);
+        assert_eq!(post.front_matter.title, page.front_matter.title);
+    }
+}
@@ -0,0 +1,31 @@
+use super::live::{LiveEvent, Topic};
+use crate::db::{
+    models::{Notification, NotificationPayload},
+    FumohouseDb,
+};
+
+/// Notifies a user, and passes the notification on to their live streams.
+/// Failing to notify someone shouldn't fail what they are being notified
+/// about, so errors are only logged.
+pub async fn notify(conn: &FumohouseDb, to: i64, payload: NotificationPayload) {
+    let kind = payload.kind();
+
+    let result = conn
+        .run(move |c| {
+            if let Some(info) = Notification::send(c, to, &payload)? {
+                LiveEvent::new(Topic::Notifications, Some(to), &info).publish(c);
+            }
+
+            Ok::<_, diesel::result::Error>(())
+        })
+        .await;
+
+    if let Err(err) = result {
+        error!(
+            "notifications: failed to send {} to user {}: {}",
+            kind.as_str(),
+            to,
+            err
+        );
+    }
+}
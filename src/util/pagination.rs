@@ -0,0 +1,45 @@
+use rocket::{http::uri::Origin, serde::Serialize};
+
+#[derive(Serialize)]
+pub struct Pagination {
+    pub page: i64,
+    pub total_pages: i64,
+    pub total_items: i64,
+    #[serde(skip)]
+    per_page: i64,
+}
+
+impl Pagination {
+    pub fn new(page: Option<i64>, per_page: i64, total_items: i64) -> Pagination {
+        let total_pages = ((total_items + per_page - 1) / per_page).max(1);
+
+        Pagination {
+            page: page.unwrap_or(1).clamp(1, total_pages),
+            total_pages,
+            total_items,
+            per_page,
+        }
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.per_page
+    }
+
+    /// The current query string without the page parameter,
+    /// for templates to build links to other pages with.
+    pub fn base_query(uri: &Origin<'_>) -> String {
+        match uri.query() {
+            Some(query) => query
+                .raw_segments()
+                .map(|seg| seg.as_str())
+                .filter(|seg| !seg.is_empty() && !seg.starts_with("page="))
+                .collect::<Vec<&str>>()
+                .join("&"),
+            None => String::new(),
+        }
+    }
+}
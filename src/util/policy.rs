@@ -0,0 +1,67 @@
+use crate::db::models::{NewPolicyAcceptance, PolicyAcceptance};
+use diesel::{PgConnection, QueryResult};
+use rocket::serde::{Deserialize, Serialize};
+
+/// The `policy` block of a page's front matter, which makes the page a
+/// versioned policy document. Its `version` is set in the front matter too.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct PolicyOptions {
+    /// Stays the same across versions
+    pub id: String,
+    /// Every user must accept the current version to register or log in
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// The id of the policy contributors sign from their account.
+pub const CONTRIBUTOR_AGREEMENT: &str = "contributor_agreement";
+
+/// The current version of a policy document.
+#[derive(Serialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Policy {
+    pub id: String,
+    pub version: String,
+    pub title: String,
+    pub url: String,
+    pub required: bool,
+    /// Hex encoded SHA-256 hash of the document's source
+    pub sha256: String,
+}
+
+impl Policy {
+    /// Required policies whose current version the user hasn't accepted.
+    pub fn outstanding(
+        c: &mut PgConnection,
+        user_id: i64,
+        policies: &[Policy],
+    ) -> QueryResult<Vec<Policy>> {
+        let accepted = PolicyAcceptance::for_user(c, user_id)?;
+
+        Ok(policies
+            .iter()
+            .filter(|p| p.required)
+            .filter(|p| {
+                !accepted
+                    .iter()
+                    .any(|a| a.policy == p.id && a.version == p.version)
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Records that the user accepted the given versions.
+    pub fn accept(c: &PgConnection, user_id: i64, policies: &[Policy]) -> QueryResult<usize> {
+        let acceptances: Vec<NewPolicyAcceptance> = policies
+            .iter()
+            .map(|p| NewPolicyAcceptance {
+                user_id,
+                policy: &p.id,
+                version: &p.version,
+            })
+            .collect();
+
+        PolicyAcceptance::accept(c, &acceptances)
+    }
+}
@@ -0,0 +1,146 @@
+use super::{markdown::PageCache, Changelog, News};
+use crate::db::models::{Channel, DocumentKind, NewSearchDocument, SearchDocument};
+use diesel::{
+    r2d2::{ConnectionManager, Pool},
+    PgConnection,
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Orbit, Rocket,
+};
+use std::sync::Arc;
+
+/// Everything indexed from the site's content, rather than the wiki
+const CONTENT_KINDS: &[DocumentKind] = &[
+    DocumentKind::Page,
+    DocumentKind::News,
+    DocumentKind::Changelog,
+];
+
+/// Keeps the search index in line with the site's content, so that
+/// searching only ever reads from it.
+///
+/// Everything is indexed at liftoff. News and release notes never change
+/// while the website is running, but pages are reindexed whenever the page
+/// cache reloads them, which only happens in debug builds.
+pub struct SearchIndexer;
+
+#[rocket::async_trait]
+impl Fairing for SearchIndexer {
+    fn info(&self) -> Info {
+        Info {
+            name: "index content for search",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        use rocket::tokio::{self, task};
+
+        let (pages, news, changelog) = match (
+            rocket.state::<PageCache>(),
+            rocket.state::<News>(),
+            rocket.state::<Changelog>(),
+        ) {
+            (Some(pages), Some(news), Some(changelog)) => (pages.clone(), news, changelog),
+            _ => {
+                error!("search: the site's content is not managed");
+                return;
+            }
+        };
+
+        let url: String = match rocket.figment().extract_inner("databases.fumohouse_db.url") {
+            Ok(url) => url,
+            Err(err) => {
+                error!("search: no database to index into: {}", err);
+                return;
+            }
+        };
+
+        // Only connected while indexing
+        let pool = Pool::builder()
+            .max_size(1)
+            .min_idle(Some(0))
+            .build_unchecked(ConnectionManager::<PgConnection>::new(url));
+
+        let mut fixed = news_documents(news);
+        fixed.extend(changelog_documents(changelog));
+        let fixed = Arc::new(fixed);
+
+        let mut changes = pages.subscribe();
+
+        tokio::spawn(async move {
+            loop {
+                let documents = page_documents(&pages);
+                let count = documents.len();
+                let pool = pool.clone();
+                let fixed = fixed.clone();
+
+                let result = task::spawn_blocking(move || {
+                    let mut c = pool.get().map_err(|err| err.to_string())?;
+                    let mut documents = documents;
+                    documents.extend(fixed.iter().cloned());
+
+                    SearchDocument::replace_kinds(&mut c, CONTENT_KINDS, &documents)
+                        .map_err(|err| err.to_string())
+                })
+                .await;
+
+                match result {
+                    Ok(Ok(())) => info!("search: indexed {} pages", count),
+                    Ok(Err(err)) => error!("search: failed to update the index: {}", err),
+                    Err(err) => error!("search: indexing panicked: {}", err),
+                }
+
+                // Only ends if the page cache is gone
+                if changes.changed().await.is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+fn page_documents(pages: &PageCache) -> Vec<NewSearchDocument> {
+    pages
+        .routed()
+        .into_iter()
+        .map(|(url, page)| NewSearchDocument {
+            kind: DocumentKind::Page,
+            url: format!("/{}", url),
+            title: page.front_matter.title.clone(),
+            headings: page.text.headings.clone(),
+            body: page.text.body.clone(),
+            channel: None,
+        })
+        .collect()
+}
+
+fn news_documents(news: &News) -> Vec<NewSearchDocument> {
+    news.posts(None)
+        .into_iter()
+        .map(|post| NewSearchDocument {
+            kind: DocumentKind::News,
+            url: format!("/news/{}", post.slug),
+            title: post.front_matter.title.clone(),
+            headings: post.text.headings.clone(),
+            body: post.text.body.clone(),
+            channel: None,
+        })
+        .collect()
+}
+
+fn changelog_documents(changelog: &Changelog) -> Vec<NewSearchDocument> {
+    changelog
+        .entries(None, Channel::ALL)
+        .into_iter()
+        .map(|entry| NewSearchDocument {
+            kind: DocumentKind::Changelog,
+            url: format!("/changelog/{}", entry.version),
+            title: entry.front_matter.title.clone(),
+            headings: entry.text.headings.clone(),
+            body: entry.text.body.clone(),
+            channel: Some(entry.channel),
+        })
+        .collect()
+}
@@ -1,16 +1,16 @@
+use super::jobs::{JobError, JobHandler};
 use crate::db::{
-    models::{NewSession, Session, User},
+    models::{NewSession, Presence, Role, Session, User},
     FumohouseDb,
 };
 use chrono::{offset::Utc, DateTime, Duration as ChronoDuration};
-use diesel::{prelude::*, result::Error as DieselError};
+use diesel::{prelude::*, result::Error as DieselError, PgConnection};
 use rocket::{
-    fairing::{Fairing, Info, Kind},
     http::{Cookie, CookieJar, Status},
-    outcome::Outcome::{Failure, Success},
+    outcome::Outcome::{Failure, Forward, Success},
     request::{FromRequest, Outcome, Request},
+    serde::{Deserialize, Serialize},
     time::{Duration as CookieDuration, OffsetDateTime},
-    Rocket,
 };
 use std::error::Error;
 use thiserror::Error;
@@ -20,47 +20,39 @@ const SESSION_ID_LENGTH: usize = 32;
 const SESSION_RENEW: i64 = 15; // minutes
 const SESSION_EXPIRY: i64 = 30 * 24; // hours
 
-const SESSION_PURGE: u64 = 30 * 60; // seconds
+pub const SESSION_PURGE: i64 = 30; // minutes
 
 pub struct SessionUtils;
 
-#[rocket::async_trait]
-impl Fairing for SessionUtils {
-    fn info(&self) -> Info {
-        Info {
-            name: "purge expired sessions",
-            kind: Kind::Liftoff,
-        }
-    }
+/// Deletes sessions which have expired.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PurgeExpiredSessions;
 
-    async fn on_liftoff(&self, rocket: &Rocket<rocket::Orbit>) {
+impl JobHandler for PurgeExpiredSessions {
+    const KIND: &'static str = "purge_expired_sessions";
+
+    fn run(self, c: &mut PgConnection) -> Result<(), JobError> {
         use crate::db::schema::sessions::dsl::*;
-        use rocket::tokio::{
-            self,
-            time::{self, Duration as TokioDuration},
-        };
 
-        let conn = FumohouseDb::get_one(rocket).await.unwrap();
+        let count = diesel::delete(sessions.filter(expires_at.lt(Utc::now()))).execute(c)?;
+        info!("session: purged {} expired sessions", count);
 
-        tokio::spawn(async move {
-            let mut interval = time::interval(TokioDuration::from_secs(SESSION_PURGE));
+        Ok(())
+    }
+}
 
-            loop {
-                interval.tick().await;
+/// Deletes presence which game servers stopped refreshing.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PurgeExpiredPresence;
 
-                let result = conn
-                    .run(|c| {
-                        let now = Utc::now();
-                        diesel::delete(sessions.filter(expires_at.lt(now))).execute(c)
-                    })
-                    .await;
+impl JobHandler for PurgeExpiredPresence {
+    const KIND: &'static str = "purge_expired_presence";
 
-                match result {
-                    Ok(count) => info!("session: purged {} expired sessions", count),
-                    Err(err) => error!("fairing: error purging sessions: {}", err),
-                }
-            }
-        });
+    fn run(self, c: &mut PgConnection) -> Result<(), JobError> {
+        Presence::purge_expired(c)?;
+        Ok(())
     }
 }
 
@@ -82,29 +74,40 @@ impl SessionUtils {
         cookies.add_private(cookie);
     }
 
-    pub async fn begin_session(
+    /// Starts a session for a client that does not use cookies (i.e. the game client).
+    /// The returned token should be sent as a `Bearer` token in the `Authorization` header.
+    pub async fn begin_api_session(
         user: &User,
         conn: &FumohouseDb,
-        cookies: &CookieJar<'_>,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<(String, Session), DieselError> {
         use crate::db::schema::sessions;
 
         let user_id = user.id;
         let (session_id, hash) = Self::new_session_id();
 
-        conn.run(move |c| {
-            let new_session = NewSession {
-                user_id,
-                session_id: &hash,
-                expires_at: Self::chrono_expiry_now(),
-            };
-
-            diesel::insert_into(sessions::table)
-                .values(&new_session)
-                .get_result::<Session>(c)
-        })
-        .await?;
+        let session = conn
+            .run(move |c| {
+                let new_session = NewSession {
+                    user_id,
+                    session_id: &hash,
+                    expires_at: Self::chrono_expiry_now(),
+                };
+
+                diesel::insert_into(sessions::table)
+                    .values(&new_session)
+                    .get_result::<Session>(c)
+            })
+            .await?;
+
+        Ok((session_id, session))
+    }
 
+    pub async fn begin_session(
+        user: &User,
+        conn: &FumohouseDb,
+        cookies: &CookieJar<'_>,
+    ) -> Result<(), Box<dyn Error>> {
+        let (session_id, _) = Self::begin_api_session(user, conn).await?;
         Self::set_cookie(cookies, session_id);
 
         Ok(())
@@ -135,11 +138,35 @@ impl SessionUtils {
         Ok(())
     }
 
+    /// Bearer tokens are held by the client, so they are not rotated on renewal.
+    async fn extend_session(conn: &FumohouseDb, session_primary_key: i64) -> Result<(), DieselError> {
+        use crate::db::schema::sessions::dsl::*;
+
+        conn.run(move |c| {
+            diesel::update(sessions.filter(id.eq(session_primary_key)))
+                .set((
+                    modified_at.eq(Utc::now()),
+                    expires_at.eq(Self::chrono_expiry_now()),
+                ))
+                .execute(c)
+        })
+        .await?;
+
+        Ok(())
+    }
+
     pub async fn end_session(
         conn: &FumohouseDb,
         cookies: &CookieJar<'_>,
         session: &Session,
     ) -> Result<(), DieselError> {
+        Self::end_api_session(conn, session).await?;
+        cookies.remove_private(Cookie::named(SESSION_COOKIE_NAME));
+
+        Ok(())
+    }
+
+    pub async fn end_api_session(conn: &FumohouseDb, session: &Session) -> Result<(), DieselError> {
         use crate::db::schema::sessions::dsl::*;
 
         let id_to_delete = session.id;
@@ -151,8 +178,6 @@ impl SessionUtils {
         })
         .await?;
 
-        cookies.remove_private(Cookie::named(SESSION_COOKIE_NAME));
-
         Ok(())
     }
 }
@@ -163,6 +188,8 @@ pub enum SessionError {
     RetrieveFailed { diesel_error: DieselError },
     #[error("Failed to renew user session: {diesel_error}.")]
     RenewFailed { diesel_error: DieselError },
+    #[error("A valid session token is required.")]
+    Unauthorized,
 }
 
 #[derive(Default)]
@@ -171,6 +198,12 @@ pub struct UserSession {
     pub session: Option<Session>,
 }
 
+impl UserSession {
+    pub fn has_role(&self, role: Role) -> bool {
+        self.user.as_ref().is_some_and(|u| u.has_role(role))
+    }
+}
+
 #[rocket::async_trait]
 impl<'a> FromRequest<'a> for UserSession {
     type Error = SessionError;
@@ -184,12 +217,13 @@ impl<'a> FromRequest<'a> for UserSession {
         let cookies = request.guard::<&CookieJar<'_>>().await.unwrap();
         let conn = request.guard::<FumohouseDb>().await.unwrap();
 
-        let session_cookie = match cookies.get_private(SESSION_COOKIE_NAME) {
-            Some(cookie) => cookie,
-            None => return Success(UserSession::default()),
-        };
+        let bearer = super::bearer_token(request);
 
-        let token_hash = super::sha256(session_cookie.value());
+        let token_hash = match (bearer, cookies.get_private(SESSION_COOKIE_NAME)) {
+            (Some(token), _) => super::sha256(token),
+            (None, Some(cookie)) => super::sha256(cookie.value()),
+            (None, None) => return Success(UserSession::default()),
+        };
 
         let result = conn
             .run(move |c| {
@@ -204,8 +238,10 @@ impl<'a> FromRequest<'a> for UserSession {
         match result {
             Ok((user, session)) => {
                 if session.since_last_modify().num_minutes() > SESSION_RENEW {
-                    let renew_result =
-                        SessionUtils::renew_session(&conn, cookies, session.id).await;
+                    let renew_result = match bearer {
+                        Some(_) => SessionUtils::extend_session(&conn, session.id).await,
+                        None => SessionUtils::renew_session(&conn, cookies, session.id).await,
+                    };
 
                     if let Err(diesel_error) = renew_result {
                         return Failure((
@@ -232,3 +268,33 @@ impl<'a> FromRequest<'a> for UserSession {
         }
     }
 }
+
+/// Guard for API routes used by the game client.
+/// Only accepts sessions authenticated with a `Bearer` token, since
+/// cookie authentication would leave these routes open to CSRF.
+pub struct ApiSession {
+    pub user: User,
+    pub session: Session,
+}
+
+#[rocket::async_trait]
+impl<'a> FromRequest<'a> for ApiSession {
+    type Error = SessionError;
+
+    async fn from_request(request: &'a Request<'_>) -> Outcome<Self, Self::Error> {
+        if super::bearer_token(request).is_none() {
+            return Failure((Status::Unauthorized, SessionError::Unauthorized));
+        }
+
+        match request.guard::<UserSession>().await {
+            Success(UserSession {
+                user: Some(user),
+                session: Some(session),
+                ..
+            }) => Success(ApiSession { user, session }),
+            Success(_) => Failure((Status::Unauthorized, SessionError::Unauthorized)),
+            Failure(failure) => Failure(failure),
+            Forward(forward) => Forward(forward),
+        }
+    }
+}
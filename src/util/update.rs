@@ -0,0 +1,318 @@
+use crate::db::models::{Channel, Platform, Release, ReleasePatch};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use rand::{rngs::OsRng, RngCore};
+use rocket::serde::{
+    json::{self, serde_json},
+    Deserialize, Serialize,
+};
+use std::env;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum UpdateError {
+    #[error("Malformed key: {0}.")]
+    InvalidKey(&'static str),
+    #[error("Manifest was signed by unknown key {0}.")]
+    UnknownKey(String),
+    #[error("Manifest signature is invalid.")]
+    BadSignature,
+    #[error("Malformed manifest: {0}.")]
+    InvalidManifest(#[from] serde_json::Error),
+}
+
+/// A public key launchers accept manifests from.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct TrustedKey {
+    pub key_id: String,
+    /// Base64 encoded Ed25519 public key
+    pub public_key: String,
+}
+
+impl TrustedKey {
+    fn verifying_key(&self) -> Result<VerifyingKey, UpdateError> {
+        let bytes = base64::decode(&self.public_key)
+            .map_err(|_| UpdateError::InvalidKey("public key is not base64"))?;
+        let bytes: [u8; 32] = bytes
+            .try_into()
+            .map_err(|_| UpdateError::InvalidKey("public key must be 32 bytes"))?;
+
+        VerifyingKey::from_bytes(&bytes)
+            .map_err(|_| UpdateError::InvalidKey("public key is not a valid point"))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ManifestFile {
+    /// Relative URLs are relative to the website
+    pub url: String,
+    pub size: i64,
+    /// Hex encoded SHA-256 hash
+    pub sha256: String,
+    /// Platform code signature, if the build has one
+    pub signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct ManifestPatch {
+    pub from_version: String,
+    pub url: String,
+    pub size: i64,
+    pub sha256: String,
+}
+
+/// The signed body of an update manifest.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "rocket::serde")]
+pub struct Manifest {
+    pub channel: Channel,
+    pub platform: Platform,
+    pub version: String,
+    pub release_date: DateTime<Utc>,
+    /// Launchers must reject manifests issued before one they have already seen
+    pub issued_at: DateTime<Utc>,
+    pub file: ManifestFile,
+    pub patches: Vec<ManifestPatch>,
+    /// Keys to trust from now on, which is how signing keys are rotated.
+    /// Only honored once this manifest's signature has been verified.
+    pub trusted_keys: Vec<TrustedKey>,
+}
+
+impl Manifest {
+    pub fn new(release: &Release, patches: &[ReleasePatch], keys: &UpdateKeys) -> Manifest {
+        Manifest {
+            channel: release.channel,
+            platform: release.platform,
+            version: release.version.clone(),
+            release_date: release.release_date,
+            issued_at: Utc::now(),
+            file: ManifestFile {
+                url: release.file_url.clone(),
+                size: release.size,
+                sha256: super::to_hex(&release.sha256),
+                signature: release.signature.clone(),
+            },
+            patches: patches
+                .iter()
+                .map(|patch| ManifestPatch {
+                    from_version: patch.from_version.clone(),
+                    url: patch.file_url.clone(),
+                    size: patch.size,
+                    sha256: super::to_hex(&patch.sha256),
+                })
+                .collect(),
+            trusted_keys: keys.keys.clone(),
+        }
+    }
+}
+
+/// A manifest as served to the launcher. `manifest` holds the exact JSON
+/// text which was signed, so it is verified before being parsed.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct SignedManifest {
+    pub key_id: String,
+    /// Base64 encoded Ed25519 signature of `manifest`
+    pub signature: String,
+    pub manifest: String,
+}
+
+/// Generates a signing key, returning the contents of its key file
+/// and the public key to add to `UPDATE_PUBLIC_KEYS`.
+pub fn generate_key(key_id: &str) -> (String, TrustedKey) {
+    let mut secret = [0u8; 32];
+    OsRng.fill_bytes(&mut secret);
+
+    let signing_key = SigningKey::from_bytes(&secret);
+
+    let key_file = format!("{}:{}\n", key_id, base64::encode(secret));
+    let trusted = TrustedKey {
+        key_id: key_id.to_string(),
+        public_key: base64::encode(signing_key.verifying_key().as_bytes()),
+    };
+
+    (key_file, trusted)
+}
+
+/// Signs a manifest using a key file written by `generate_key`.
+pub fn sign(key_file: &str, manifest: &str) -> Result<SignedManifest, UpdateError> {
+    let (key_id, secret) = key_file
+        .trim()
+        .split_once(':')
+        .ok_or(UpdateError::InvalidKey("expected <key id>:<secret>"))?;
+
+    let secret = base64::decode(secret)
+        .map_err(|_| UpdateError::InvalidKey("secret is not base64"))?;
+    let secret: [u8; 32] = secret
+        .try_into()
+        .map_err(|_| UpdateError::InvalidKey("secret must be 32 bytes"))?;
+
+    // Refuse to sign anything the launcher would fail to parse
+    json::from_str::<Manifest>(manifest)?;
+
+    let signature = SigningKey::from_bytes(&secret).sign(manifest.as_bytes());
+
+    Ok(SignedManifest {
+        key_id: key_id.to_string(),
+        signature: base64::encode(signature.to_bytes()),
+        manifest: manifest.to_string(),
+    })
+}
+
+/// Public keys which manifests may be signed with, from `UPDATE_PUBLIC_KEYS`.
+/// Private keys never reach the website; manifests are signed offline.
+pub struct UpdateKeys {
+    keys: Vec<TrustedKey>,
+}
+
+impl UpdateKeys {
+    pub fn new() -> UpdateKeys {
+        let value = env::var("UPDATE_PUBLIC_KEYS").unwrap_or_default();
+
+        let keys: Vec<TrustedKey> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (key_id, public_key) = entry
+                    .split_once(':')
+                    .expect("UPDATE_PUBLIC_KEYS entries must be <key id>:<public key>.");
+
+                let key = TrustedKey {
+                    key_id: key_id.to_string(),
+                    public_key: public_key.to_string(),
+                };
+
+                if let Err(err) = key.verifying_key() {
+                    panic!("Invalid update key {}: {}", key_id, err);
+                }
+
+                key
+            })
+            .collect();
+
+        if keys.is_empty() {
+            warn!("Did not find UPDATE_PUBLIC_KEYS, signed manifests will be rejected.");
+        }
+
+        UpdateKeys { keys }
+    }
+
+    pub fn keys(&self) -> &[TrustedKey] {
+        &self.keys
+    }
+
+    /// Checks a manifest was signed by a trusted key, then parses it.
+    pub fn verify(&self, signed: &SignedManifest) -> Result<Manifest, UpdateError> {
+        let key = self
+            .keys
+            .iter()
+            .find(|k| k.key_id == signed.key_id)
+            .ok_or_else(|| UpdateError::UnknownKey(signed.key_id.clone()))?;
+
+        let signature = base64::decode(&signed.signature)
+            .ok()
+            .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+            .map(|bytes| Signature::from_bytes(&bytes))
+            .ok_or(UpdateError::BadSignature)?;
+
+        key.verifying_key()?
+            .verify_strict(signed.manifest.as_bytes(), &signature)
+            .map_err(|_| UpdateError::BadSignature)?;
+
+        Ok(json::from_str(&signed.manifest)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(version: &str, trusted_keys: &[&TrustedKey]) -> String {
+        let manifest = Manifest {
+            channel: Channel::Stable,
+            platform: Platform::Windows,
+            version: version.to_string(),
+            release_date: Utc::now(),
+            issued_at: Utc::now(),
+            file: ManifestFile {
+                url: format!("/download/fumohouse-{}.zip", version),
+                size: 1024,
+                sha256: "00".repeat(32),
+                signature: None,
+            },
+            patches: Vec::new(),
+            trusted_keys: trusted_keys.iter().map(|&key| key.clone()).collect(),
+        };
+
+        json::to_string(&manifest).unwrap()
+    }
+
+    fn trusting(keys: &[&TrustedKey]) -> UpdateKeys {
+        UpdateKeys {
+            keys: keys.iter().map(|&key| key.clone()).collect(),
+        }
+    }
+
+    #[test]
+    fn signed_manifests_verify() {
+        let (key_file, key) = generate_key("first");
+        let signed = sign(&key_file, &manifest("1.0.0", &[&key])).unwrap();
+
+        let verified = trusting(&[&key]).verify(&signed).unwrap();
+        assert_eq!(verified.version, "1.0.0");
+    }
+
+    #[test]
+    fn tampered_or_unknown_manifests_fail() {
+        let (key_file, key) = generate_key("first");
+        let (other_file, _) = generate_key("other");
+        let keys = trusting(&[&key]);
+
+        let mut signed = sign(&key_file, &manifest("1.0.0", &[&key])).unwrap();
+        signed.manifest = signed.manifest.replace("1.0.0", "6.6.6");
+        assert!(matches!(
+            keys.verify(&signed),
+            Err(UpdateError::BadSignature)
+        ));
+
+        let signed = sign(&other_file, &manifest("1.0.0", &[&key])).unwrap();
+        assert!(matches!(
+            keys.verify(&signed),
+            Err(UpdateError::UnknownKey(_))
+        ));
+
+        // Signed by another key, claiming to be the trusted one
+        let mut signed = signed;
+        signed.key_id = key.key_id.clone();
+        assert!(matches!(
+            keys.verify(&signed),
+            Err(UpdateError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn rotated_keys_verify_once_trusted() {
+        let (old_file, old_key) = generate_key("old");
+        let (new_file, new_key) = generate_key("new");
+        let launcher = trusting(&[&old_key]);
+
+        // The new key is announced in a manifest signed with the old one
+        let announced = sign(&old_file, &manifest("1.0.0", &[&old_key, &new_key])).unwrap();
+        let announced = launcher.verify(&announced).unwrap();
+
+        let signed = sign(&new_file, &manifest("1.1.0", &[&new_key])).unwrap();
+        assert!(matches!(
+            launcher.verify(&signed),
+            Err(UpdateError::UnknownKey(_))
+        ));
+
+        let rotated = UpdateKeys {
+            keys: announced.trusted_keys,
+        };
+        assert_eq!(rotated.verify(&signed).unwrap().version, "1.1.0");
+    }
+}
@@ -0,0 +1,195 @@
+use super::UserSession;
+use crate::db::models::Role;
+use multer::{Constraints, Multipart, SizeLimit};
+use rocket::{
+    data::{self, Data, FromData, Limits},
+    form::ValueField,
+    http::Status,
+    outcome::Outcome,
+    request::Request,
+    tokio::{
+        fs::{self, File},
+        io::AsyncWriteExt,
+    },
+};
+use sha2::{Digest, Sha256};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// The name of the limit on uploaded files, set in `lib.rs`. Every other
+/// form keeps Rocket's default limits.
+pub const UPLOAD_LIMIT: &str = "upload";
+
+/// The multipart field holding the file
+const FILE_FIELD: &str = "file";
+
+/// A multipart form with a large file, such as a build, which only admins
+/// may send. The session is checked before any of the body is read. The
+/// file is then hashed as it's streamed to disk, and the other fields are
+/// kept to be parsed with `Form::parse_iter`.
+pub struct AdminUpload {
+    /// The admin's session, which the route shouldn't look up again
+    pub user_session: UserSession,
+    fields: Vec<(String, String)>,
+    /// `None` if the file input was left empty
+    pub file: Option<UploadedFile>,
+}
+
+impl AdminUpload {
+    pub fn fields(&self) -> impl Iterator<Item = ValueField<'_>> {
+        self.fields
+            .iter()
+            .map(|(name, value)| ValueField::from((name.as_str(), value.as_str())))
+    }
+}
+
+/// An uploaded file in Rocket's temporary directory, which is deleted
+/// unless it's persisted.
+pub struct UploadedFile {
+    path: PathBuf,
+    /// The extension of the name it was uploaded with, if it's a sensible one
+    pub extension: Option<String>,
+    pub size: i64,
+    pub sha256: Vec<u8>,
+}
+
+impl UploadedFile {
+    /// Moves the file to `path`, copying it if that's on another filesystem.
+    pub async fn persist_to(self, path: &Path) -> io::Result<()> {
+        if fs::rename(&self.path, path).await.is_err() {
+            fs::copy(&self.path, path).await?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for UploadedFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn sanitized_extension(file_name: &str) -> Option<String> {
+    file_name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext)
+        .filter(|ext| ext.len() <= 8 && ext.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(str::to_string)
+}
+
+async fn receive(
+    req: &Request<'_>,
+    data: Data<'_>,
+    user_session: UserSession,
+) -> Result<AdminUpload, (Status, io::Error)> {
+    let bad_request = |message: &str| (Status::BadRequest, io::Error::other(message.to_string()));
+
+    let boundary = req
+        .content_type()
+        .filter(|ct| ct.is_form_data())
+        .and_then(|ct| ct.param("boundary"))
+        .ok_or_else(|| bad_request("expected a multipart form"))?;
+
+    let limits = req.limits();
+    let file_limit = limits.get(UPLOAD_LIMIT).unwrap_or(Limits::FILE);
+    let field_limit = limits.get("form").unwrap_or(Limits::FORM);
+    let stream_limit = file_limit + limits.get("data-form").unwrap_or(Limits::DATA_FORM);
+
+    let constraints = Constraints::new().size_limit(
+        SizeLimit::new()
+            .whole_stream(stream_limit.as_u64())
+            .per_field(field_limit.as_u64())
+            .for_field(FILE_FIELD, file_limit.as_u64()),
+    );
+
+    let mut multipart =
+        Multipart::with_reader_with_constraints(data.open(stream_limit), boundary, constraints);
+
+    let mut upload = AdminUpload {
+        user_session,
+        fields: Vec::new(),
+        file: None,
+    };
+
+    let too_large = |err: multer::Error| match err {
+        multer::Error::FieldSizeExceeded { .. } | multer::Error::StreamSizeExceeded { .. } => {
+            (Status::PayloadTooLarge, io::Error::other(err))
+        }
+        err => (Status::BadRequest, io::Error::other(err)),
+    };
+
+    while let Some(mut field) = multipart.next_field().await.map_err(too_large)? {
+        let name = field.name().unwrap_or_default().to_string();
+
+        let file_name = match field.file_name() {
+            Some(file_name) => file_name.to_string(),
+            None => {
+                let value = field.text().await.map_err(too_large)?;
+                upload.fields.push((name, value));
+                continue;
+            }
+        };
+
+        if name != FILE_FIELD || upload.file.is_some() {
+            return Err(bad_request("unexpected file"));
+        }
+
+        let internal = |err: io::Error| (Status::InternalServerError, err);
+
+        let path = req
+            .rocket()
+            .config()
+            .temp_dir
+            .relative()
+            .join(format!("fumohouse-upload-{}", super::rand_string(16)));
+
+        // Deletes the file if anything goes wrong from here
+        let mut file = UploadedFile {
+            path,
+            extension: sanitized_extension(&file_name),
+            size: 0,
+            sha256: Vec::new(),
+        };
+
+        let mut output = File::create(&file.path).await.map_err(internal)?;
+        let mut hasher = Sha256::new();
+
+        while let Some(chunk) = field.chunk().await.map_err(too_large)? {
+            hasher.update(&chunk);
+            output.write_all(&chunk).await.map_err(internal)?;
+            file.size += chunk.len() as i64;
+        }
+
+        output.flush().await.map_err(internal)?;
+        file.sha256 = hasher.finalize().to_vec();
+
+        if file.size > 0 {
+            upload.file = Some(file);
+        }
+    }
+
+    Ok(upload)
+}
+
+#[rocket::async_trait]
+impl<'r> FromData<'r> for AdminUpload {
+    type Error = io::Error;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let user_session = match req.guard::<UserSession>().await {
+            Outcome::Success(user_session) if user_session.has_role(Role::Admin) => user_session,
+            _ => {
+                let err = io::Error::other("only admins may upload files");
+                return data::Outcome::Failure((Status::Forbidden, err));
+            }
+        };
+
+        match receive(req, data, user_session).await {
+            Ok(upload) => data::Outcome::Success(upload),
+            Err(failure) => data::Outcome::Failure(failure),
+        }
+    }
+}
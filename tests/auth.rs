@@ -0,0 +1,120 @@
+mod common;
+
+use common::{api_login, multipart, PASSWORD};
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::{login_attempts, sessions},
+    models::Role,
+};
+use rocket::http::Status;
+
+#[test]
+fn api_login_checks_the_password() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+
+    assert_eq!(
+        api_login(&client, "192.0.2.1", &user.username, "wrong password"),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        api_login(&client, "192.0.2.1", &common::unique("nobody"), PASSWORD),
+        Status::Unauthorized
+    );
+    assert_eq!(
+        api_login(
+            &client,
+            "192.0.2.1",
+            &user.username.to_uppercase(),
+            PASSWORD
+        ),
+        Status::Ok
+    );
+}
+
+#[test]
+fn api_login_throttles_an_account() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+
+    // From a different address each time, so only the account's limit applies
+    for i in 0..10 {
+        let ip = format!("198.51.100.{}", i);
+        assert_eq!(
+            api_login(&client, &ip, &user.username, "wrong password"),
+            Status::Unauthorized
+        );
+    }
+
+    assert_eq!(
+        api_login(&client, "198.51.100.200", &user.username, PASSWORD),
+        Status::TooManyRequests
+    );
+}
+
+#[test]
+fn api_login_throttles_an_address() {
+    let Some(client) = common::client() else {
+        return;
+    };
+    let ip = format!("203.0.113.{}", rand::random::<u8>());
+
+    for _ in 0..30 {
+        api_login(&client, &ip, &common::unique("nobody"), "wrong password");
+    }
+
+    assert_eq!(
+        api_login(&client, &ip, &common::unique("nobody"), "wrong password"),
+        Status::TooManyRequests
+    );
+}
+
+#[test]
+fn api_login_clears_attempts_on_success() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+
+    for _ in 0..3 {
+        api_login(&client, "192.0.2.2", &user.username, "wrong password");
+    }
+    assert_eq!(
+        api_login(&client, "192.0.2.2", &user.username, PASSWORD),
+        Status::Ok
+    );
+
+    let attempts: i64 = login_attempts::table
+        .filter(login_attempts::username.eq(user.username.to_lowercase()))
+        .count()
+        .get_result(&c)
+        .unwrap();
+    assert_eq!(attempts, 0);
+}
+
+#[test]
+fn logging_out_ends_the_session() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let sessions = sessions::table.filter(sessions::user_id.eq(user.id));
+
+    common::web_login(&client, &user);
+    assert_eq!(sessions.count().get_result::<i64>(&c).unwrap(), 1);
+
+    let token = common::csrf_token(&client, "/account");
+    let (content_type, body) = multipart(&[], None);
+    let response = client
+        .post(format!("/auth/logout?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/login"));
+    assert_eq!(sessions.count().get_result::<i64>(&c).unwrap(), 0);
+}
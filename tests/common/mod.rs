@@ -0,0 +1,291 @@
+//! Shared by the tests which need a database. They run against the one
+//! named by TEST_DATABASE_URL, and are skipped when it isn't set.
+
+#![allow(dead_code)]
+
+use argon2::Argon2;
+use diesel::{prelude::*, PgConnection};
+use fumohouse_web::{
+    db::schema::users,
+    models::{NewUser, Role, User},
+    util::{self, markdown::PageCache, Policy},
+};
+use log::LevelFilter;
+use rand::{distributions::Alphanumeric, Rng};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    env,
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Once, OnceLock},
+    thread,
+};
+
+pub const PASSWORD: &str = "correct horse battery staple";
+
+static SETUP: Once = Once::new();
+
+/// The test database's URL, or `None` if the test should be skipped.
+pub fn database_url() -> Option<String> {
+    match env::var("TEST_DATABASE_URL") {
+        Ok(url) if !url.is_empty() => Some(url),
+        _ => {
+            eprintln!("skipping: TEST_DATABASE_URL is not set");
+            None
+        }
+    }
+}
+
+fn setup(url: &str) {
+    SETUP.call_once(|| {
+        // Only errors, rather than every request
+        let _ = fern::Dispatch::new()
+            .level(LevelFilter::Error)
+            .chain(std::io::stderr())
+            .apply();
+
+        env::set_var("DATABASE_URL", url);
+        env::set_var("HCAPTCHA_SITEKEY", "10000000-ffff-ffff-ffff-000000000001");
+        env::set_var(
+            "HCAPTCHA_SECRET",
+            "0x0000000000000000000000000000000000000000",
+        );
+        env::set_var("GAME_SERVER_SECRET", "test");
+
+        let github = mock_github();
+        env::set_var("GITHUB_URL", &github);
+        env::set_var("GITHUB_API_URL", &github);
+        env::set_var("GITHUB_CLIENT_ID", "test");
+        env::set_var("GITHUB_CLIENT_SECRET", "test");
+    });
+}
+
+/// A connection to the test database, migrated to the latest schema.
+pub fn connection() -> Option<PgConnection> {
+    let url = database_url()?;
+    setup(&url);
+
+    let c = PgConnection::establish(&url).expect("failed to connect to the test database");
+    fumohouse_web::db::migrations::run(&c).expect("failed to migrate the test database");
+
+    Some(c)
+}
+
+/// The site, running against the test database.
+pub fn client() -> Option<Client> {
+    let url = database_url()?;
+    setup(&url);
+
+    Some(Client::tracked(fumohouse_web::rocket()).expect("failed to build the site"))
+}
+
+/// Tests share the database, so everything they create is named uniquely.
+pub fn unique(prefix: &str) -> String {
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .map(char::from)
+        .collect();
+
+    format!("{}_{}", prefix, suffix)
+}
+
+/// The current versions of the required policies, which `create_user` accepts.
+fn required_policies() -> &'static [Policy] {
+    static POLICIES: OnceLock<Vec<Policy>> = OnceLock::new();
+
+    POLICIES.get_or_init(|| {
+        let pages = PageCache::load().expect("failed to load markdown pages");
+        pages
+            .policies()
+            .into_iter()
+            .filter(|p| p.required)
+            .collect()
+    })
+}
+
+/// A new user whose password is `PASSWORD`, who has accepted the rules.
+pub fn create_user(c: &mut PgConnection, role: Role) -> User {
+    let user = create_user_without_policies(c, role);
+    Policy::accept(c, user.id, required_policies()).unwrap();
+
+    user
+}
+
+/// A new user whose password is `PASSWORD`, who has yet to accept the rules.
+pub fn create_user_without_policies(c: &mut PgConnection, role: Role) -> User {
+    let username = unique("user");
+    let hash = util::hash_password(&Argon2::default(), PASSWORD).unwrap();
+
+    diesel::insert_into(users::table)
+        .values((
+            &NewUser {
+                username: &username,
+                password: &hash,
+            },
+            users::role.eq(role),
+        ))
+        .get_result(c)
+        .unwrap()
+}
+
+/// Logs in through the API, as a given address, returning the status.
+pub fn api_login(client: &Client, ip: &str, username: &str, password: &str) -> Status {
+    client
+        .post("/api/v1/auth/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Real-IP", ip.to_string()))
+        .body(
+            rocket::serde::json::json!({ "username": username, "password": password }).to_string(),
+        )
+        .dispatch()
+        .status()
+}
+
+/// The CSRF token in a page's forms, whose cookie the client keeps.
+pub fn csrf_token(client: &Client, path: &str) -> String {
+    let page = client.get(path).dispatch().into_string().unwrap();
+    let start = page.find("csrf_token=").expect("no form on the page") + "csrf_token=".len();
+
+    page[start..]
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .collect()
+}
+
+/// Logs in through the site, so that the client has a session cookie.
+pub fn web_login(client: &Client, user: &User) {
+    let token = csrf_token(client, "/auth/login");
+    let (content_type, body) = multipart(
+        &[("username", &user.username), ("password", PASSWORD)],
+        None,
+    );
+
+    let response = client
+        .post(format!("/auth/login?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    assert_eq!(response.status(), Status::SeeOther, "failed to log in");
+}
+
+/// A `multipart/form-data` body, the way the site's forms are sent, with a
+/// `file` field if given a file name and contents.
+pub fn multipart(fields: &[(&str, &str)], file: Option<(&str, &[u8])>) -> (ContentType, Vec<u8>) {
+    const BOUNDARY: &str = "fumohouse-test-boundary";
+
+    let mut body = Vec::new();
+
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                BOUNDARY, name, value
+            )
+            .as_bytes(),
+        );
+    }
+
+    if let Some((file_name, contents)) = file {
+        body.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                BOUNDARY, file_name
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(contents);
+        body.extend_from_slice(b"\r\n");
+    }
+
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+
+    let content_type =
+        ContentType::new("multipart", "form-data").with_params(("boundary", BOUNDARY));
+
+    (content_type, body)
+}
+
+/// The GitHub account that logging in with `code` verifies. The mock GitHub
+/// hands back the code as the access token, and the token's login.
+pub fn github_account(code: &str) -> (i64, String) {
+    let mut hasher = DefaultHasher::new();
+    code.hash(&mut hasher);
+
+    ((hasher.finish() >> 1) as i64, code.to_string())
+}
+
+/// Serves just enough of GitHub's OAuth flow for the site, returning its URL.
+fn mock_github() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || serve_github(stream));
+        }
+    });
+
+    url
+}
+
+fn serve_github(stream: TcpStream) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).unwrap();
+
+    let mut content_length = 0;
+    let mut token = String::new();
+
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+        match name.to_ascii_lowercase().as_str() {
+            "content-length" => content_length = value.parse().unwrap(),
+            "authorization" => token = value.trim_start_matches("Bearer ").to_string(),
+            _ => (),
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).unwrap();
+    let body = String::from_utf8(body).unwrap();
+
+    let response = if request_line.starts_with("POST /login/oauth/access_token") {
+        let code = body
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("code="))
+            .unwrap_or_default();
+
+        rocket::serde::json::json!({ "access_token": code })
+    } else if request_line.starts_with("GET /user") {
+        let (id, login) = github_account(&token);
+
+        rocket::serde::json::json!({ "id": id, "login": login })
+    } else {
+        rocket::serde::json::json!({})
+    }
+    .to_string();
+
+    let mut stream = stream;
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.len(),
+        response
+    );
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use common::multipart;
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::{contributor_signatures, users},
+    models::{ContributorSignature, NewContributorSignature, Role, User},
+};
+use rocket::{http::Status, local::blocking::Client};
+
+/// Logs in to GitHub as the account for `code`, the way GitHub would send
+/// the user back.
+fn verify_github(client: &Client, code: &str) {
+    let response = client.get("/account/contributor/github").dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let location = response.headers().get_one("Location").unwrap();
+    let state = location
+        .split(['?', '&'])
+        .find_map(|pair| pair.strip_prefix("state="))
+        .expect("no state in the authorize URL");
+
+    let response = client
+        .get(format!(
+            "/account/contributor/github/callback?code={}&state={}",
+            code, state
+        ))
+        .dispatch();
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("/account/contributor")
+    );
+}
+
+/// Signs the agreement, returning the page if the form was shown again.
+fn sign(client: &Client) -> Option<String> {
+    let token = common::csrf_token(client, "/account/contributor");
+    let (content_type, body) = multipart(&[("legal_name", "Test Signer"), ("agree", "true")], None);
+
+    let response = client
+        .post(format!("/account/contributor?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    if response.status() == Status::SeeOther {
+        None
+    } else {
+        response.into_string()
+    }
+}
+
+fn signatures_for(c: &PgConnection, user_id: i64) -> Vec<ContributorSignature> {
+    contributor_signatures::table
+        .filter(contributor_signatures::user_id.eq(user_id))
+        .load(c)
+        .unwrap()
+}
+
+#[test]
+fn signing_uses_the_verified_github_account() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    common::web_login(&client, &user);
+
+    // Nothing to sign as yet
+    let page = sign(&client).expect("signed without a GitHub account");
+    assert!(page.contains("Verify your GitHub account before signing."));
+    assert!(signatures_for(&c, user.id).is_empty());
+
+    let code = common::unique("octocat");
+    verify_github(&client, &code);
+    assert_eq!(sign(&client), None);
+
+    let (github_id, login) = common::github_account(&code);
+    let signatures = signatures_for(&c, user.id);
+    assert_eq!(signatures.len(), 1);
+    assert_eq!(signatures[0].github_id, Some(github_id));
+    assert_eq!(signatures[0].github_handle, login);
+}
+
+#[test]
+fn github_accounts_are_signed_for_once() {
+    let (Some(first), Some(second), Some(mut c)) =
+        (common::client(), common::client(), common::connection())
+    else {
+        return;
+    };
+    let code = common::unique("octocat");
+
+    let user = common::create_user(&mut c, Role::User);
+    common::web_login(&first, &user);
+    verify_github(&first, &code);
+    assert_eq!(sign(&first), None);
+
+    let other = common::create_user(&mut c, Role::User);
+    common::web_login(&second, &other);
+    verify_github(&second, &code);
+    let page = sign(&second).expect("signed as someone else's GitHub account");
+    assert!(page.contains("used by another user"));
+    assert!(signatures_for(&c, other.id).is_empty());
+}
+
+#[test]
+fn signatures_outlive_their_accounts() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let handle = common::unique("octocat");
+
+    let signature: ContributorSignature = diesel::insert_into(contributor_signatures::table)
+        .values(NewContributorSignature {
+            user_id: user.id,
+            legal_name: "Test Signer",
+            github_handle: &handle,
+            version: "1",
+            document_sha256: &[0; 32],
+            github_id: None,
+        })
+        .get_result(&c)
+        .unwrap();
+
+    // Only detaching from a deleted account is allowed
+    let renamed = diesel::update(contributor_signatures::table.find(signature.id))
+        .set(contributor_signatures::legal_name.eq("Someone Else"))
+        .execute(&c);
+    assert!(renamed.is_err());
+
+    let detached = diesel::update(contributor_signatures::table.find(signature.id))
+        .set(contributor_signatures::user_id.eq(None::<i64>))
+        .execute(&c);
+    assert!(detached.is_err());
+
+    diesel::delete(users::table.find(user.id))
+        .execute(&c)
+        .unwrap();
+    assert!(User::find_by_id(&c, user.id).is_err());
+
+    let signature = ContributorSignature::latest_for_handle(&mut c, &handle)
+        .unwrap()
+        .unwrap();
+    assert_eq!(signature.user_id, None);
+    assert_eq!(signature.legal_name, "Test Signer");
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use common::multipart;
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::{forum_posts, users},
+    models::{ForumCategory, ForumPost, ForumThread, NewForumCategory, NewForumThread, Role, User},
+};
+use rocket::{http::Status, local::blocking::Client};
+
+fn create_thread(c: &mut PgConnection, author: &User) -> (ForumCategory, ForumThread) {
+    let slug = common::unique("cat").replace('_', "-").to_lowercase();
+    let category = ForumCategory::create(
+        c,
+        &NewForumCategory {
+            slug: &slug,
+            name: "Test",
+            description: "",
+            position: 0,
+        },
+    )
+    .unwrap();
+
+    let thread = ForumThread::create(
+        c,
+        NewForumThread {
+            category_id: category.id,
+            author_id: author.id,
+            title: "Test thread",
+        },
+        "first",
+        "<p>first</p>\n",
+    )
+    .unwrap();
+
+    (category, thread)
+}
+
+/// Replies through the site, returning the page if the form was shown again.
+fn reply(client: &Client, thread_id: i64, content: &str) -> Option<String> {
+    let token = common::csrf_token(client, "/");
+    let (content_type, body) = multipart(&[("content", content)], None);
+
+    let response = client
+        .post(format!(
+            "/forums/threads/{}/reply?csrf_token={}",
+            thread_id, token
+        ))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    if response.status() == Status::SeeOther {
+        None
+    } else {
+        response.into_string()
+    }
+}
+
+fn latest_post(c: &PgConnection, thread_id: i64) -> ForumPost {
+    forum_posts::table
+        .filter(forum_posts::thread_id.eq(thread_id))
+        .order(forum_posts::id.desc())
+        .first(c)
+        .unwrap()
+}
+
+#[test]
+fn replies_are_rendered_when_saved() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let (_, thread) = create_thread(&mut c, &user);
+    common::web_login(&client, &user);
+
+    assert_eq!(reply(&client, thread.id, "**hello**"), None);
+
+    let post = latest_post(&c, thread.id);
+    assert_eq!(
+        post.content_html.as_deref(),
+        Some("<p><strong>hello</strong></p>\n")
+    );
+}
+
+#[test]
+fn banned_users_are_told_they_are_banned() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let (_, thread) = create_thread(&mut c, &user);
+    common::web_login(&client, &user);
+
+    diesel::update(users::table.find(user.id))
+        .set(users::banned.eq(true))
+        .execute(&c)
+        .unwrap();
+
+    let page = reply(&client, thread.id, "hello").expect("banned user replied");
+    assert!(
+        page.contains("Banned users can&#x27;t post in the forums."),
+        "{}",
+        page
+    );
+    assert!(!page.contains("This thread is locked."));
+}
+
+#[test]
+fn edits_keep_the_content_they_replace() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let (_, thread) = create_thread(&mut c, &user);
+    let post = latest_post(&c, thread.id);
+
+    assert!(ForumPost::edit(&mut c, post.id, user.id, "second", "<p>second</p>\n").unwrap());
+    assert!(!ForumPost::edit(&mut c, post.id, user.id, "second", "<p>second</p>\n").unwrap());
+    assert!(ForumPost::edit(&mut c, post.id, user.id, "third", "<p>third</p>\n").unwrap());
+
+    let edits: Vec<String> = ForumPost::edits(&mut c, post.id)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.content)
+        .collect();
+    assert_eq!(edits, ["first", "second"]);
+
+    let post = ForumPost::find(&mut c, post.id).unwrap().unwrap();
+    assert_eq!(post.content_html.as_deref(), Some("<p>third</p>\n"));
+}
+
+#[test]
+fn summaries_count_threads_and_posts() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let (category, thread) = create_thread(&mut c, &user);
+    ForumThread::reply(&mut c, thread.id, user.id, "second", "<p>second</p>\n").unwrap();
+
+    let summary = ForumCategory::summaries(&mut c)
+        .unwrap()
+        .into_iter()
+        .find(|s| s.category.id == category.id)
+        .unwrap();
+
+    assert_eq!(summary.thread_count, 1);
+    assert_eq!(summary.post_count, 2);
+}
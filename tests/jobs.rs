@@ -0,0 +1,171 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use diesel::{dsl::now, prelude::*, result::Error as DieselError};
+use fumohouse_web::{
+    db::schema::jobs,
+    models::{Job, JobStatus},
+    util::jobs::{enqueue, JobError, JobHandler, JobRegistry},
+};
+use rocket::serde::{Deserialize, Serialize};
+
+/// Each test has kinds of its own, since registries only take jobs of the
+/// kinds they know.
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Claimed;
+
+impl JobHandler for Claimed {
+    const KIND: &'static str = "test_claimed";
+
+    fn run(self, _: &mut PgConnection) -> Result<(), JobError> {
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Failing;
+
+impl JobHandler for Failing {
+    const KIND: &'static str = "test_failing";
+    const MAX_ATTEMPTS: i32 = 3;
+
+    fn run(self, _: &mut PgConnection) -> Result<(), JobError> {
+        Err(JobError::Database(DieselError::NotFound))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "rocket::serde")]
+struct Recurring;
+
+impl JobHandler for Recurring {
+    const KIND: &'static str = "test_recurring";
+
+    fn run(self, _: &mut PgConnection) -> Result<(), JobError> {
+        Ok(())
+    }
+}
+
+fn find(c: &PgConnection, job_id: i64) -> Job {
+    jobs::table.find(job_id).first(c).unwrap()
+}
+
+/// Makes a job due now rather than whenever it was going to run.
+fn make_due(c: &PgConnection, job_id: i64) {
+    diesel::update(jobs::table.find(job_id))
+        .set(jobs::run_at.eq(now))
+        .execute(c)
+        .unwrap();
+}
+
+fn waiting(c: &PgConnection) -> Vec<Job> {
+    jobs::table
+        .filter(jobs::kind.eq(Recurring::KIND))
+        .filter(jobs::status.eq(JobStatus::Pending))
+        .load(c)
+        .unwrap()
+}
+
+fn assert_about(at: chrono::DateTime<Utc>, from_now: Duration) {
+    let off = at - (Utc::now() + from_now);
+    assert!(off.num_seconds().abs() < 5, "{} is off by {}", at, off);
+}
+
+#[test]
+fn jobs_are_claimed_by_one_worker_at_a_time() {
+    let (Some(mut first), Some(mut second)) = (common::connection(), common::connection()) else {
+        return;
+    };
+    let registry = JobRegistry::default().register::<Claimed>();
+    let job_id = enqueue(&mut first, &Claimed, None).unwrap();
+
+    let claimed = registry.claim_due(&mut first, 100).unwrap();
+    let job = claimed.into_iter().find(|job| job.id == job_id).unwrap();
+    assert_eq!(job.status, JobStatus::Running);
+    assert_eq!(job.attempts, 1);
+
+    let claimed = registry.claim_due(&mut second, 100).unwrap();
+    assert!(claimed.iter().all(|job| job.id != job_id));
+
+    // Held for longer while it runs
+    assert!(Job::extend_lease(&mut first, job.id, job.attempts, Duration::minutes(20)).unwrap());
+    assert_about(find(&first, job_id).run_at, Duration::minutes(20));
+
+    // The first worker died, so its lease runs out
+    make_due(&first, job_id);
+    let claimed = registry.claim_due(&mut second, 100).unwrap();
+    let retaken = claimed.into_iter().find(|job| job.id == job_id).unwrap();
+    assert_eq!(retaken.attempts, 2);
+
+    // The first worker can no longer touch it
+    assert!(!Job::extend_lease(&mut first, job.id, job.attempts, Duration::minutes(20)).unwrap());
+    assert!(!Job::finish(&mut first, &job, None, None).unwrap());
+    assert_eq!(find(&first, job_id).status, JobStatus::Running);
+
+    registry.run(&mut second, retaken).unwrap();
+    assert_eq!(find(&second, job_id).status, JobStatus::Succeeded);
+}
+
+#[test]
+fn failed_jobs_back_off_until_they_die() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    // Retried runs from earlier tests would be claimed alongside this one
+    diesel::delete(jobs::table.filter(jobs::kind.eq(Failing::KIND)))
+        .execute(&c)
+        .unwrap();
+
+    let registry = JobRegistry::default().register::<Failing>();
+    let job_id = enqueue(&mut c, &Failing, None).unwrap();
+
+    for (attempt, delay) in [(1, 30), (2, 60)] {
+        make_due(&c, job_id);
+        registry.run_due(&mut c).unwrap();
+
+        let job = find(&c, job_id);
+        assert_eq!(job.status, JobStatus::Pending);
+        assert_eq!(job.attempts, attempt);
+        assert_about(job.run_at, Duration::seconds(delay));
+        assert_eq!(job.last_error.as_deref(), Some("Database error: NotFound."));
+    }
+
+    make_due(&c, job_id);
+    registry.run_due(&mut c).unwrap();
+
+    let job = find(&c, job_id);
+    assert_eq!(job.status, JobStatus::Dead);
+    assert_eq!(job.attempts, 3);
+    assert!(job.finished_at.is_some());
+
+    assert!(Job::retry(&mut c, job_id).unwrap());
+    assert_eq!(find(&c, job_id).status, JobStatus::Pending);
+}
+
+#[test]
+fn recurring_jobs_run_again_after_finishing() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    diesel::delete(jobs::table.filter(jobs::kind.eq(Recurring::KIND)))
+        .execute(&c)
+        .unwrap();
+
+    let registry = JobRegistry::default().recurring(Recurring, Duration::hours(1));
+    // Every instance schedules it, but only one run waits
+    registry.schedule_recurring(&mut c).unwrap();
+    registry.schedule_recurring(&mut c).unwrap();
+    let first = waiting(&c);
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].repeat_every, Some(60 * 60));
+
+    registry.run_due(&mut c).unwrap();
+    assert_eq!(find(&c, first[0].id).status, JobStatus::Succeeded);
+
+    let next = waiting(&c);
+    assert_eq!(next.len(), 1);
+    assert_ne!(next[0].id, first[0].id);
+    assert_about(next[0].run_at, Duration::hours(1));
+}
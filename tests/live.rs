@@ -0,0 +1,26 @@
+mod common;
+
+use diesel::{dsl::sql, prelude::*, result::Error, sql_types::Integer};
+use fumohouse_web::{
+    models::Role,
+    util::{LiveEvent, Topic},
+};
+
+#[test]
+fn failing_to_publish_keeps_the_transaction() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+
+    // Postgres refuses notifications of 8000 bytes or more
+    let event = LiveEvent::new(Topic::Notifications, Some(user.id), &"x".repeat(10_000));
+
+    let result = c.transaction::<_, Error, _>(|| {
+        event.publish(&c);
+
+        // An aborted transaction would refuse this
+        diesel::select(sql::<Integer>("1")).get_result::<i32>(&c)
+    });
+    assert_eq!(result, Ok(1));
+}
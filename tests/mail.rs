@@ -0,0 +1,103 @@
+mod common;
+
+use common::multipart;
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::email_outbox,
+    models::{EmailStatus, NewOutboxEmail, OutboxEmail, Role},
+    util::mail,
+};
+use rocket::http::Status;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Emails are written here rather than sent. The sender reads MAIL_URL once
+/// per process, so every test shares the directory.
+fn mail_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+
+    DIR.get_or_init(|| {
+        let dir = env::temp_dir().join(common::unique("fumohouse_mail"));
+        env::set_var("MAIL_URL", format!("file:{}", dir.display()));
+        dir
+    })
+}
+
+/// Sends whatever is due, including what other tests left behind.
+fn send_all(c: &mut PgConnection) {
+    while mail::send_due(c).unwrap() > 0 {}
+}
+
+fn status(c: &PgConnection, email_id: i64) -> EmailStatus {
+    email_outbox::table
+        .find(email_id)
+        .select(email_outbox::status)
+        .first(c)
+        .unwrap()
+}
+
+#[test]
+fn test_emails_are_written_by_the_worker() {
+    let dir = mail_dir();
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let admin = common::create_user(&mut c, Role::Admin);
+    common::web_login(&client, &admin);
+
+    let recipient = format!("{}@example.com", common::unique("someone"));
+    let token = common::csrf_token(&client, "/admin/email");
+    let (content_type, body) = multipart(&[("recipient", &recipient)], None);
+
+    let response = client
+        .post(format!("/admin/email?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let email_id: i64 = email_outbox::table
+        .filter(email_outbox::recipient.eq(&recipient))
+        .select(email_outbox::id)
+        .first(&c)
+        .unwrap();
+
+    send_all(&mut c);
+    assert_eq!(status(&c, email_id), EmailStatus::Sent);
+
+    let eml = fs::read_to_string(dir.join(format!("{}.eml", email_id))).unwrap();
+    assert!(eml.contains(&format!("To: {}", recipient)), "{}", eml);
+    assert!(eml.contains("Subject: Fumohouse test email"), "{}", eml);
+    assert!(
+        eml.contains(&format!("This is a test email sent by {}", admin.username)),
+        "{}",
+        eml
+    );
+}
+
+#[test]
+fn emails_to_bad_addresses_are_given_up_on() {
+    let dir = mail_dir();
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+
+    let email_id = OutboxEmail::queue(
+        &mut c,
+        &NewOutboxEmail {
+            recipient: "not an address".into(),
+            subject: "Test".into(),
+            template: "test".into(),
+            html_body: "<p>Test</p>".into(),
+            text_body: "Test".into(),
+        },
+    )
+    .unwrap();
+
+    send_all(&mut c);
+    assert_eq!(status(&c, email_id), EmailStatus::Failed);
+    assert!(!dir.join(format!("{}.eml", email_id)).exists());
+}
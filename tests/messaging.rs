@@ -0,0 +1,202 @@
+mod common;
+
+use common::multipart;
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::{messages, users},
+    models::{Conversation, MessageReport, Role, User, UserBlock},
+    util::messaging::{self, MessagingError},
+};
+use rocket::http::Status;
+use std::thread;
+
+fn start(c: &mut PgConnection, author: &User, to: &User) -> Result<Conversation, MessagingError> {
+    messaging::start(c, author, std::slice::from_ref(&to.username), None, "hello").map(|(c, _)| c)
+}
+
+#[test]
+fn each_pair_has_one_direct_conversation() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let a = common::create_user(&mut c, Role::User);
+    let b = common::create_user(&mut c, Role::User);
+
+    // Both start one at once, from either side
+    let ids: Vec<i64> = (0..6)
+        .map(|n| {
+            let (author, to) = if n % 2 == 0 {
+                (a.clone(), b.clone())
+            } else {
+                (b.clone(), a.clone())
+            };
+
+            thread::spawn(move || {
+                let mut c = common::connection().unwrap();
+                start(&mut c, &author, &to).unwrap().id
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+
+    assert!(ids.iter().all(|id| *id == ids[0]), "{:?}", ids);
+    assert_eq!(Conversation::count_for_user(&mut c, a.id).unwrap(), 1);
+}
+
+#[test]
+fn the_rate_limit_holds_for_messages_sent_at_once() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let author = common::create_user(&mut c, Role::User);
+    let to = common::create_user(&mut c, Role::User);
+    let conversation = start(&mut c, &author, &to).unwrap();
+
+    let results: Vec<_> = (0..20)
+        .map(|_| {
+            let (author, conversation) = (author.clone(), conversation.clone());
+
+            thread::spawn(move || {
+                let c = common::connection().unwrap();
+                messaging::send(&c, &conversation, &author, "hello")
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect();
+
+    let sent = results.iter().filter(|result| result.is_ok()).count();
+    assert_eq!(sent, 9);
+    assert!(results
+        .iter()
+        .filter_map(|result| result.as_ref().err())
+        .all(|err| matches!(err, MessagingError::RateLimited)));
+
+    let count: i64 = messages::table
+        .filter(messages::author_id.eq(author.id))
+        .count()
+        .get_result(&c)
+        .unwrap();
+    assert_eq!(count, 10);
+}
+
+#[test]
+fn starting_nothing_leaves_nothing_behind() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let author = common::create_user(&mut c, Role::User);
+    let others: Vec<User> = (0..11)
+        .map(|_| common::create_user(&mut c, Role::User))
+        .collect();
+
+    for other in &others[..10] {
+        start(&mut c, &author, other).unwrap();
+    }
+
+    let result = start(&mut c, &author, &others[10]);
+    assert!(matches!(result, Err(MessagingError::RateLimited)));
+    assert!(Conversation::find_direct(&c, author.id, others[10].id)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn reports_outlive_the_message_and_its_author() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let author = common::create_user(&mut c, Role::User);
+    let reporter = common::create_user(&mut c, Role::User);
+    let moderator = common::create_user(&mut c, Role::Moderator);
+
+    let (_, message) = messaging::start(
+        &mut c,
+        &author,
+        std::slice::from_ref(&reporter.username),
+        None,
+        "something awful",
+    )
+    .unwrap();
+    assert!(MessageReport::create(&mut c, &message, reporter.id, "awful").unwrap());
+
+    diesel::delete(users::table.find(author.id))
+        .execute(&c)
+        .unwrap();
+
+    let open = MessageReport::open(&mut c, 1000, 0).unwrap();
+    let report = &open
+        .iter()
+        .find(|open| open.report.reporter_id == reporter.id)
+        .expect("the report went with the message")
+        .report;
+
+    assert_eq!(report.message_id, None);
+    assert_eq!(report.author_id, None);
+    assert_eq!(report.author, author.username);
+    assert_eq!(report.content, "something awful");
+
+    assert_eq!(
+        MessageReport::resolve(&mut c, report.id, moderator.id).unwrap(),
+        1
+    );
+}
+
+#[test]
+fn usernames_with_spaces_can_be_messaged_and_blocked() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let author = common::create_user(&mut c, Role::User);
+    let other = common::create_user(&mut c, Role::User);
+    let spaced = common::create_user(&mut c, Role::User);
+    let spaced: User = diesel::update(users::table.find(spaced.id))
+        .set(users::username.eq(common::unique("Cirno (9)")))
+        .get_result(&c)
+        .unwrap();
+
+    assert_eq!(
+        messaging::parse_recipients(&format!(" {},\n{} ", spaced.username, other.username)),
+        [spaced.username.clone(), other.username.clone()]
+    );
+
+    common::web_login(&client, &author);
+
+    let token = common::csrf_token(&client, "/messages/new");
+    let (content_type, body) = multipart(
+        &[
+            ("to", &spaced.username),
+            ("title", ""),
+            ("content", "hello"),
+        ],
+        None,
+    );
+    let response = client
+        .post(format!("/messages/new?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let token = common::csrf_token(&client, "/messages/new");
+    let response = client
+        .post(format!(
+            "/users/{}/block?csrf_token={}",
+            spaced
+                .username
+                .replace(' ', "%20")
+                .replace('(', "%28")
+                .replace(')', "%29"),
+            token
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert!(UserBlock::has_blocked(&c, author.id, spaced.id).unwrap());
+
+    // The profile it goes back to can be followed
+    let location = response.headers().get_one("Location").unwrap().to_string();
+    assert_eq!(client.get(location).dispatch().status(), Status::Ok);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use diesel::{prelude::*, result::Error, sql_types::Text};
+use fumohouse_web::db::migrations;
+use rocket::{http::Status, serde::json::Value};
+
+#[test]
+fn pending_migrations_are_found_without_writing() {
+    let Some(c) = common::connection() else {
+        return;
+    };
+    let latest = migrations::latest_version().unwrap();
+    assert!(migrations::pending(&c).unwrap().is_empty());
+
+    c.test_transaction::<_, Error, _>(|| {
+        diesel::sql_query("DELETE FROM __diesel_schema_migrations WHERE version = $1")
+            .bind::<Text, _>(latest)
+            .execute(&c)?;
+
+        // Anything that tried to apply it would fail from here on
+        diesel::sql_query("SET TRANSACTION READ ONLY").execute(&c)?;
+
+        assert_eq!(migrations::pending(&c)?, vec![latest.to_string()]);
+        assert!(migrations::schema_version(&c)?.as_deref() < Some(latest));
+        Ok(())
+    });
+}
+
+#[test]
+fn health_expects_the_latest_migration() {
+    let (Some(client), Some(_)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let latest = migrations::VERSIONS.last().unwrap();
+
+    let response = client.get("/api/v1/health").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let health: Value = response.into_json().unwrap();
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["schema_version"], *latest);
+    assert_eq!(health["expected_schema_version"], *latest);
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::notifications,
+    models::{Notification, NotificationPayload, Role, User},
+};
+
+fn payload(n: i64) -> NotificationPayload {
+    NotificationPayload::WikiEdit {
+        slug: "test".into(),
+        title: "Test".into(),
+        revision_id: n,
+        author: "someone".into(),
+    }
+}
+
+fn unread(c: &mut PgConnection, user: &User) -> i64 {
+    User::find_by_id(c, user.id).unwrap().unread_notifications
+}
+
+#[test]
+fn unread_counts_follow_notifications() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    assert_eq!(unread(&mut c, &user), 0);
+
+    let ids: Vec<i64> = (0..3)
+        .map(|n| {
+            Notification::send(&mut c, user.id, &payload(n))
+                .unwrap()
+                .unwrap()
+                .id
+        })
+        .collect();
+    assert_eq!(unread(&mut c, &user), 3);
+
+    Notification::mark_read(&mut c, user.id, &ids[..1]).unwrap();
+    // Already read, so nothing changes
+    Notification::mark_read(&mut c, user.id, &ids[..1]).unwrap();
+    assert_eq!(unread(&mut c, &user), 2);
+    assert_eq!(Notification::unread_count(&mut c, user.id).unwrap(), 2);
+
+    diesel::delete(notifications::table.find(ids[1]))
+        .execute(&c)
+        .unwrap();
+    assert_eq!(unread(&mut c, &user), 1);
+
+    // Deleting one that was read leaves the count alone
+    diesel::delete(notifications::table.find(ids[0]))
+        .execute(&c)
+        .unwrap();
+    assert_eq!(unread(&mut c, &user), 1);
+
+    Notification::mark_all_read(&mut c, user.id).unwrap();
+    assert_eq!(unread(&mut c, &user), 0);
+}
+
+#[test]
+fn the_nav_shows_the_unread_count() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    common::web_login(&client, &user);
+
+    for n in 0..2 {
+        Notification::send(&mut c, user.id, &payload(n)).unwrap();
+    }
+
+    let page = client.get("/").dispatch().into_string().unwrap();
+    assert!(
+        page.contains("<span class=\"nav__badge\">2</span>"),
+        "{}",
+        page
+    );
+}
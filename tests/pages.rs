@@ -0,0 +1,20 @@
+mod common;
+
+use rocket::http::Status;
+
+#[test]
+fn pages_are_served_by_path() {
+    let Some(client) = common::client() else {
+        return;
+    };
+    let status = |path: &str| client.get(path).dispatch().status();
+
+    assert_eq!(status("/rules"), Status::Ok);
+    assert_eq!(status("/rules/code"), Status::Ok);
+    assert_eq!(status("/rules/missing"), Status::NotFound);
+    assert_eq!(status("/rules/code.md"), Status::NotFound);
+
+    // Neither form of `..` reaches past `markdown/`
+    assert_eq!(status("/rules/../Cargo.toml"), Status::NotFound);
+    assert_eq!(status("/rules/%2e%2e/%2e%2e/Cargo.toml"), Status::NotFound);
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::{api_login, multipart, PASSWORD};
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::{sessions, users},
+    models::Role,
+    util::{markdown::PageCache, Policy},
+};
+use rocket::http::Status;
+
+fn sessions_for(c: &PgConnection, user_id: i64) -> i64 {
+    sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .count()
+        .get_result(c)
+        .unwrap()
+}
+
+#[test]
+fn api_login_waits_on_outstanding_policies() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user_without_policies(&mut c, Role::User);
+
+    assert_eq!(
+        api_login(&client, "192.0.2.10", &user.username, PASSWORD),
+        Status::Forbidden
+    );
+    assert_eq!(sessions_for(&c, user.id), 0);
+
+    let policies = PageCache::load().unwrap().required_policies();
+    Policy::accept(&c, user.id, &policies).unwrap();
+
+    assert_eq!(
+        api_login(&client, "192.0.2.10", &user.username, PASSWORD),
+        Status::Ok
+    );
+}
+
+#[test]
+fn accepting_outstanding_policies_starts_the_session() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user_without_policies(&mut c, Role::User);
+
+    let token = common::csrf_token(&client, "/auth/login");
+    let (content_type, body) = multipart(
+        &[("username", &user.username), ("password", PASSWORD)],
+        None,
+    );
+    client
+        .post(format!("/auth/login?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    // Sent without accepting, the policies are asked for again
+    let token = common::csrf_token(&client, "/auth/login");
+    let (content_type, body) = multipart(&[], None);
+    let response = client
+        .post(format!("/auth/policies?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::UnprocessableEntity);
+    assert_eq!(sessions_for(&c, user.id), 0);
+
+    let token = common::csrf_token(&client, "/auth/login");
+    let (content_type, body) = multipart(&[("accept_policies", "true")], None);
+    let response = client
+        .post(format!("/auth/policies?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(sessions_for(&c, user.id), 1);
+
+    let policies = PageCache::load().unwrap().required_policies();
+    assert!(Policy::outstanding(&mut c, user.id, &policies)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn pending_logins_end_when_the_password_changes() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user_without_policies(&mut c, Role::User);
+
+    let token = common::csrf_token(&client, "/auth/login");
+    let (content_type, body) = multipart(
+        &[("username", &user.username), ("password", PASSWORD)],
+        None,
+    );
+    let response = client
+        .post(format!("/auth/login?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    assert_eq!(
+        response.headers().get_one("Location"),
+        Some("/auth/policies")
+    );
+
+    diesel::update(users::table.find(user.id))
+        .set(users::password.eq("changed elsewhere"))
+        .execute(&c)
+        .unwrap();
+
+    let token = common::csrf_token(&client, "/auth/login");
+    let (content_type, body) = multipart(&[("accept_policies", "true")], None);
+    let response = client
+        .post(format!("/auth/policies?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    assert_eq!(response.headers().get_one("Location"), Some("/auth/login"));
+    assert_eq!(sessions_for(&c, user.id), 0);
+}
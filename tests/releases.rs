@@ -0,0 +1,97 @@
+mod common;
+
+use common::multipart;
+use fumohouse_web::{
+    models::{Channel, Platform, Release, Role},
+    util,
+};
+use rocket::{
+    data::{Limits, ToByteUnit},
+    http::Status,
+};
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+
+fn release_fields(version: &str) -> Vec<(&str, &str)> {
+    vec![
+        ("version", version),
+        ("channel", "stable"),
+        ("platform", "linux"),
+        ("release_date", "2022-07-01"),
+        ("file_url", ""),
+        ("size", ""),
+        ("sha256", ""),
+        ("signature", ""),
+    ]
+}
+
+#[test]
+fn admins_upload_builds() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let admin = common::create_user(&mut c, Role::Admin);
+    common::web_login(&client, &admin);
+
+    let version = common::unique("v");
+    let contents = b"not really a build";
+    let token = common::csrf_token(&client, "/admin/releases");
+    let (content_type, body) = multipart(
+        &release_fields(&version),
+        Some(("fumohouse.tar.gz", contents)),
+    );
+
+    let response = client
+        .post(format!("/admin/releases?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+    assert_eq!(response.status(), Status::SeeOther);
+
+    let release = Release::find(&mut c, &version, Channel::Stable, Platform::Linux).unwrap();
+    let path = release.file_url.trim_start_matches('/');
+
+    assert_eq!(release.size, contents.len() as i64);
+    assert_eq!(release.sha256, Sha256::digest(contents).to_vec());
+    assert!(path.ends_with(".gz"));
+    assert_eq!(fs::read(path).unwrap(), contents);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn only_admins_can_upload() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::Moderator);
+    common::web_login(&client, &user);
+
+    let version = common::unique("v");
+    let token = common::csrf_token(&client, "/");
+    let (content_type, body) = multipart(&release_fields(&version), Some(("build.zip", b"zip")));
+
+    let response = client
+        .post(format!("/admin/releases?csrf_token={}", token))
+        .header(content_type)
+        .body(body)
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Forbidden);
+    assert!(Release::find(&mut c, &version, Channel::Stable, Platform::Linux).is_err());
+    assert!(!Path::new("releases")
+        .join(format!("fumohouse-{}-stable-linux.zip", version))
+        .exists());
+}
+
+#[test]
+fn only_uploads_get_the_large_limit() {
+    let Some(client) = common::client() else {
+        return;
+    };
+    let limits = &client.rocket().config().limits;
+
+    assert_eq!(limits.get("data-form"), Some(Limits::DATA_FORM));
+    assert_eq!(limits.get("file"), Some(Limits::FILE));
+    assert_eq!(limits.get(util::UPLOAD_LIMIT), Some(1.gibibytes()));
+}
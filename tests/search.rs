@@ -0,0 +1,43 @@
+mod common;
+
+use diesel::prelude::*;
+use fumohouse_web::db::schema::search_documents;
+use rocket::http::Status;
+use std::{thread, time::Duration};
+
+const PAGE_URL: &str = "/rules/code";
+
+fn indexed(c: &PgConnection, url: &str) -> bool {
+    search_documents::table
+        .filter(search_documents::url.eq(url))
+        .count()
+        .get_result::<i64>(c)
+        .unwrap()
+        > 0
+}
+
+#[test]
+fn pages_are_indexed_at_liftoff_and_searching_only_reads() {
+    let (Some(client), Some(c)) = (common::client(), common::connection()) else {
+        return;
+    };
+
+    // Indexing happens in the background, once the site has lifted off
+    for _ in 0..50 {
+        if indexed(&c, PAGE_URL) && indexed(&c, "/news/2022-06-25-website-updates") {
+            break;
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    assert!(indexed(&c, PAGE_URL));
+
+    diesel::delete(search_documents::table.filter(search_documents::url.eq(PAGE_URL)))
+        .execute(&c)
+        .unwrap();
+
+    let response = client.get("/search?q=conduct").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert!(!indexed(&c, PAGE_URL));
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use fumohouse_web::{
+    db::schema::{game_servers, presences},
+    models::Role,
+    util::GameServerUtils,
+};
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::Client,
+    serde::json::{json, Value},
+};
+
+fn bearer(token: &str) -> Header<'static> {
+    Header::new("Authorization", format!("Bearer {}", token))
+}
+
+/// Registers a server with a unique address, returning its ID and token.
+fn register(client: &Client, secret: &str, region: &str) -> Result<(i64, String), Status> {
+    let response = client
+        .post("/api/v1/servers/register")
+        .header(ContentType::JSON)
+        .header(bearer(secret))
+        .body(
+            json!({
+                "name": common::unique("server"),
+                "address": format!("{}.example.com", common::unique("server")),
+                "port": 7777,
+                "version": "1.0.0",
+                "max_players": 16,
+                "region": region,
+            })
+            .to_string(),
+        )
+        .dispatch();
+
+    if response.status() != Status::Ok {
+        return Err(response.status());
+    }
+
+    let body: Value = response.into_json().unwrap();
+    Ok((
+        body["id"].as_i64().unwrap(),
+        body["token"].as_str().unwrap().to_string(),
+    ))
+}
+
+fn heartbeat(client: &Client, token: &str) -> Status {
+    client
+        .post("/api/v1/servers/heartbeat")
+        .header(ContentType::JSON)
+        .header(bearer(token))
+        .body(json!({ "player_count": 1 }).to_string())
+        .dispatch()
+        .status()
+}
+
+/// A session token for the game client, from logging in through the API.
+fn api_token(client: &Client, username: &str) -> String {
+    let body: Value = client
+        .post("/api/v1/auth/login")
+        .header(ContentType::JSON)
+        .header(Header::new("X-Real-IP", "192.0.2.30"))
+        .body(json!({ "username": username, "password": common::PASSWORD }).to_string())
+        .dispatch()
+        .into_json()
+        .unwrap();
+
+    body["token"].as_str().unwrap().to_string()
+}
+
+fn verify(client: &Client, server_token: &str, ticket: &str) -> Status {
+    client
+        .post("/api/v1/servers/verify")
+        .header(ContentType::JSON)
+        .header(bearer(server_token))
+        .body(json!({ "ticket": ticket }).to_string())
+        .dispatch()
+        .status()
+}
+
+/// The names of the servers listed in a region.
+fn listed(client: &Client, region: &str) -> Vec<String> {
+    let list: Value = client
+        .get(format!("/api/v1/servers?region={}", region))
+        .dispatch()
+        .into_json()
+        .unwrap();
+
+    list["servers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|server| server["name"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn servers_need_the_secret_to_register() {
+    let Some(client) = common::client() else {
+        return;
+    };
+    let region = common::unique("region");
+
+    assert_eq!(
+        register(&client, "wrong secret", &region),
+        Err(Status::Unauthorized)
+    );
+
+    let (_, token) = register(&client, "test", &region).unwrap();
+    assert_eq!(heartbeat(&client, &token), Status::NoContent);
+    assert_eq!(heartbeat(&client, "wrong token"), Status::Unauthorized);
+}
+
+#[test]
+fn servers_without_heartbeats_are_unlisted() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let region = common::unique("region");
+    register(&client, "test", &region).unwrap();
+    assert_eq!(listed(&client, &region).len(), 1);
+
+    diesel::update(game_servers::table.filter(game_servers::region.eq(&region)))
+        .set(game_servers::last_heartbeat.eq(Utc::now() - Duration::minutes(5)))
+        .execute(&c)
+        .unwrap();
+    assert!(GameServerUtils::mark_stale_offline(&mut c).unwrap() >= 1);
+
+    assert!(listed(&client, &region).is_empty());
+}
+
+#[test]
+fn join_tickets_are_used_once() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let (server_id, server_token) = register(&client, "test", &common::unique("region")).unwrap();
+
+    let join: Value = client
+        .post(format!("/api/v1/servers/{}/join", server_id))
+        .header(bearer(&api_token(&client, &user.username)))
+        .dispatch()
+        .into_json()
+        .unwrap();
+    let ticket = join["ticket"].as_str().unwrap();
+
+    assert_eq!(verify(&client, &server_token, ticket), Status::Ok);
+    assert_eq!(verify(&client, &server_token, ticket), Status::Unauthorized);
+}
+
+#[test]
+fn presence_updates_are_applied_whole() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let (_, token) = register(&client, "test", &common::unique("region")).unwrap();
+
+    let presence = |joined: &[i64]| {
+        client
+            .post("/api/v1/servers/presence")
+            .header(ContentType::JSON)
+            .header(bearer(&token))
+            .body(json!({ "joined": joined }).to_string())
+            .dispatch()
+            .status()
+    };
+    let present = |c: &PgConnection| -> i64 {
+        presences::table
+            .filter(presences::user_id.eq(user.id))
+            .count()
+            .get_result(c)
+            .unwrap()
+    };
+
+    // Nobody has a negative ID
+    assert_eq!(presence(&[user.id, -1]), Status::UnprocessableEntity);
+    assert_eq!(present(&c), 0);
+
+    assert_eq!(presence(&[user.id]), Status::NoContent);
+    assert_eq!(present(&c), 1);
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::multipart;
+use fumohouse_web::models::{Role, User, WikiEdit, WikiPage, WikiSave};
+use rocket::{http::Status, local::blocking::Client};
+
+fn edit<'a>(author: &User, content: &'a str) -> WikiEdit<'a> {
+    WikiEdit {
+        author_id: author.id,
+        author_role: author.role,
+        title: "Test Page",
+        content,
+        summary: "",
+        reverted_from: None,
+    }
+}
+
+fn protect(client: &Client, slug: &str, protection: &str) -> Status {
+    let token = common::csrf_token(client, "/");
+    let (content_type, body) = multipart(&[("protection", protection)], None);
+
+    client
+        .post(format!("/wiki/{}/protect?csrf_token={}", slug, token))
+        .header(content_type)
+        .body(body)
+        .dispatch()
+        .status()
+}
+
+#[test]
+fn pages_are_only_created_once() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let slug = common::unique("page").replace('_', "-").to_lowercase();
+
+    let first = WikiPage::save(&mut c, &slug, edit(&user, "first"), None).unwrap();
+    assert!(matches!(first, WikiSave::Saved(_)));
+
+    // A second editor who also started from nothing
+    let second = WikiPage::save(&mut c, &slug, edit(&user, "second"), None).unwrap();
+    assert!(matches!(second, WikiSave::Conflict));
+
+    let page = WikiPage::find(&mut c, &slug).unwrap().unwrap();
+    assert_eq!(page.latest_revision(&mut c).unwrap().content, "first");
+}
+
+#[test]
+fn moderators_cannot_unprotect_pages_above_them() {
+    let (Some(client), Some(mut c)) = (common::client(), common::connection()) else {
+        return;
+    };
+    let admin = common::create_user(&mut c, Role::Admin);
+    let moderator = common::create_user(&mut c, Role::Moderator);
+    let slug = common::unique("page").replace('_', "-").to_lowercase();
+
+    WikiPage::save(&mut c, &slug, edit(&admin, "content"), None).unwrap();
+    let page = WikiPage::find(&mut c, &slug).unwrap().unwrap();
+    WikiPage::set_protection(&mut c, page.id, Role::Admin).unwrap();
+
+    common::web_login(&client, &moderator);
+    assert_eq!(protect(&client, &slug, "user"), Status::Forbidden);
+
+    let page = WikiPage::find(&mut c, &slug).unwrap().unwrap();
+    assert_eq!(page.protection, Role::Admin);
+
+    WikiPage::set_protection(&mut c, page.id, Role::Moderator).unwrap();
+    assert_eq!(protect(&client, &slug, "user"), Status::SeeOther);
+
+    let page = WikiPage::find(&mut c, &slug).unwrap().unwrap();
+    assert_eq!(page.protection, Role::User);
+}
+
+#[test]
+fn edits_are_refused_once_the_page_is_protected() {
+    let Some(mut c) = common::connection() else {
+        return;
+    };
+    let user = common::create_user(&mut c, Role::User);
+    let slug = common::unique("page").replace('_', "-").to_lowercase();
+
+    let first = match WikiPage::save(&mut c, &slug, edit(&user, "first"), None).unwrap() {
+        WikiSave::Saved(revision) => revision,
+        _ => panic!("the page wasn't created"),
+    };
+
+    // Protected after the user opened the editor, before they saved
+    let page = WikiPage::find(&mut c, &slug).unwrap().unwrap();
+    WikiPage::set_protection(&mut c, page.id, Role::Moderator).unwrap();
+
+    let second = WikiPage::save(&mut c, &slug, edit(&user, "second"), Some(first.id)).unwrap();
+    assert!(matches!(second, WikiSave::Forbidden));
+    assert_eq!(page.latest_revision(&mut c).unwrap().content, "first");
+}